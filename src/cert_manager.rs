@@ -1,7 +1,99 @@
+use serde::Serialize;
 use crate::certificate::{ProductType, Certificate};
-use crate::state::GLOBAL_STATE;
+use crate::player::{PlayerData, StakingPlayer};
+use crate::state::{with_state, GlobalState};
 use crate::error::*;
+use crate::math_safe::{safe_add, safe_sub, safe_mul, to_u8_checked, to_bool_checked};
 use zkwasm_rest_abi::{StorageData, MERKLE_MAP};
+use std::collections::HashMap;
+
+/// Key-value store the managers write to, a missing key reads as an empty Vec
+/// Handlers pass `MerkleStorage`, tests run the same steps against a `HashMap`
+pub trait Storage {
+    fn get(&self, key: &[u64; 4]) -> Vec<u64>;
+    fn set(&mut self, key: &[u64; 4], data: &[u64]);
+}
+
+/// The merkle map the chain keeps its state in
+pub struct MerkleStorage;
+
+impl Storage for MerkleStorage {
+    fn get(&self, key: &[u64; 4]) -> Vec<u64> {
+        let kvpair = unsafe { &mut MERKLE_MAP };
+        kvpair.get(key)
+    }
+
+    fn set(&mut self, key: &[u64; 4], data: &[u64]) {
+        let kvpair = unsafe { &mut MERKLE_MAP };
+        kvpair.set(key, data);
+    }
+}
+
+/// In-memory storage for running handlers off chain
+impl Storage for HashMap<[u64; 4], Vec<u64>> {
+    fn get(&self, key: &[u64; 4]) -> Vec<u64> {
+        HashMap::get(self, key).cloned().unwrap_or_default()
+    }
+
+    fn set(&mut self, key: &[u64; 4], data: &[u64]) {
+        self.insert(*key, data.to_vec());
+    }
+}
+
+/// Hard upper bound on entries a single enumeration may return
+pub const MAX_ITERATE_KEYS: usize = 256;
+
+/// Iterate a key family `[prefix[0], prefix[1], prefix[2], id]` for ids in `[start, end)`
+///
+/// Calls `f` for every non-empty entry, stopping after `max` entries (clamped to MAX_ITERATE_KEYS)
+/// or after visiting MAX_ITERATE_KEYS ids, so a sparse range costs no more than a dense one
+/// Returns the id to resume from if iteration stopped early, None if the range was exhausted
+pub fn iterate_keys<F>(prefix: [u64; 3], start: u64, end: u64, max: usize, f: F) -> Option<u64>
+where
+    F: FnMut(u64, &mut Vec<u64>),
+{
+    let kvpair = unsafe { &mut MERKLE_MAP };
+    iterate_keys_with(|key| kvpair.get(key), prefix, start, end, max, f)
+}
+
+/// Storage-independent core of `iterate_keys`, the getter returns an empty Vec for missing keys
+pub fn iterate_keys_with<G, F>(
+    mut get: G,
+    prefix: [u64; 3],
+    start: u64,
+    end: u64,
+    max: usize,
+    mut f: F
+) -> Option<u64>
+where
+    G: FnMut(&[u64; 4]) -> Vec<u64>,
+    F: FnMut(u64, &mut Vec<u64>),
+{
+    let max = max.min(MAX_ITERATE_KEYS);
+    let scan_end = end.min(start.saturating_add(MAX_ITERATE_KEYS as u64));
+    let mut found = 0;
+    let mut id = start;
+    while id < scan_end {
+        if found >= max {
+            return Some(id);
+        }
+        let mut data = get(&[prefix[0], prefix[1], prefix[2], id]);
+        if !data.is_empty() {
+            f(id, &mut data);
+            found += 1;
+        }
+        id += 1;
+    }
+    (id < end).then_some(id)
+}
+
+/// One page of a list query, resume from `next` while `has_more` is set
+#[derive(Serialize, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub has_more: bool,
+    pub next: u64,
+}
 
 /// Manager for ProductType storage operations
 pub struct ProductTypeManager;
@@ -9,31 +101,59 @@ pub struct ProductTypeManager;
 /// Default recharge product uses maximum duration
 impl ProductTypeManager {
     /// Store a product type with the given ID
-    pub fn store_product_type(product_type: &ProductType) {
+    pub fn store_product_type<S: Storage>(storage: &mut S, product_type: &ProductType) {
         let mut data = vec![];
         product_type.to_data(&mut data);
-        let kvpair = unsafe { &mut MERKLE_MAP };
         // Key format: [1, 0, 0, product_type_id] for product types
-        kvpair.set(&[1, 0, 0, product_type.id], data.as_slice());
+        storage.set(&[1, 0, 0, product_type.id], data.as_slice());
     }
     
     /// Retrieve a product type by ID
     pub fn get_product_type(id: u64) -> Option<ProductType> {
+        let kvpair = unsafe { &mut MERKLE_MAP };
+        Self::get_product_type_with(|key| kvpair.get(key), id)
+    }
+    
+    /// Storage-independent core of `get_product_type`
+    pub fn get_product_type_with<G>(mut get: G, id: u64) -> Option<ProductType>
+    where
+        G: FnMut(&[u64; 4]) -> Vec<u64>,
+    {
         // Special case: ID 0 is always the default recharge product
         if id == 0 {
             return Some(Self::get_default_recharge_product());
         }
         
-        let kvpair = unsafe { &mut MERKLE_MAP };
-        let mut data = kvpair.get(&[1, 0, 0, id]);
+        let mut data = get(&[1, 0, 0, id]);
         if !data.is_empty() {
             let mut u64data = data.iter_mut();
-            Some(ProductType::from_data(&mut u64data))
+            ProductType::try_from_data(&mut u64data).ok()
         } else {
             None
         }
     }
     
+    /// Every product type in id order, the recharge product 0 first and inactive products included
+    pub fn list_product_types() -> Vec<ProductType> {
+        let end = with_state(|state| state.product_type_counter);
+        let kvpair = unsafe { &mut MERKLE_MAP };
+        Self::list_product_types_with(|key| kvpair.get(key), end)
+    }
+    
+    /// Storage-independent core of `list_product_types`, over the ids below `end`
+    pub fn list_product_types_with<G>(get: G, end: u64) -> Vec<ProductType>
+    where
+        G: FnMut(&[u64; 4]) -> Vec<u64>,
+    {
+        let mut products = vec![Self::get_default_recharge_product()];
+        iterate_keys_with(get, [1, 0, 0], 1, end, MAX_ITERATE_KEYS, |_, data| {
+            if let Ok(product_type) = ProductType::try_from_data(&mut data.iter_mut()) {
+                products.push(product_type);
+            }
+        });
+        products
+    }
+    
     /// Get the default recharge product (ID 0)
     fn get_default_recharge_product() -> ProductType {
         ProductType {
@@ -42,16 +162,80 @@ impl ProductTypeManager {
             apy: 0,                   // 0% APY for recharge
             min_amount: 1,            // 1 USDT minimum
             is_active: true,
+            display_order: 0,
+            compounding: crate::certificate::COMPOUNDING_SIMPLE,
+            principal_currency: crate::certificate::PRINCIPAL_CURRENCY_USDT,
+            whitelist_only: false,
+            max_total_principal: 0,
+            current_total_principal: 0,
+            apy_steps: vec![],
+            interest_currency: crate::certificate::PRINCIPAL_CURRENCY_USDT,
+            early_withdrawal_penalty_bps: crate::certificate::NO_EARLY_WITHDRAWAL,
+            max_certificates: 0,
+            certificate_count: 0,
+            grace_ticks: 0,
+            accrual_pauses: vec![],
+            min_claim_interval_ticks: 0,
+        }
+    }
+
+    /// Active product types in front-end display order (display_order, then id)
+    /// Pages scan at most `max` stored products in id order from `start`, and each page is sorted
+    /// The synthetic recharge product 0 is not listed
+    pub fn list_active_products(start: u64, max: usize) -> Page<ProductType> {
+        let end = with_state(|state| state.product_type_counter);
+        let kvpair = unsafe { &mut MERKLE_MAP };
+        Self::list_active_products_with(|key| kvpair.get(key), start, end, max)
+    }
+
+    /// Storage-independent core of `list_active_products`
+    pub fn list_active_products_with<G>(get: G, start: u64, end: u64, max: usize) -> Page<ProductType>
+    where
+        G: FnMut(&[u64; 4]) -> Vec<u64>,
+    {
+        let mut products = vec![];
+        let resume = iterate_keys_with(get, [1, 0, 0], start.max(1), end, max, |_, data| {
+            if let Ok(product_type) = ProductType::try_from_data(&mut data.iter_mut()) {
+                if product_type.is_active {
+                    products.push(product_type);
+                }
+            }
+        });
+        products.sort_by_key(|p| (p.display_order, p.id));
+        Page {
+            items: products,
+            has_more: resume.is_some(),
+            next: resume.unwrap_or(end),
         }
     }
     
     /// Create a new product type (admin only)
-    pub fn create_product_type(
+    pub fn create_product_type<S: Storage>(
+        state: &mut GlobalState,
+        storage: &mut S,
         duration_ticks: u64, 
         apy: u64, 
         min_amount: u64,
-        is_active: bool
-    ) -> Result<u64, u32> {
+        is_active: bool,
+        options: &ProductTypeOptions
+    ) -> Result<ProductType, u32> {
+        let product_type = Self::create_product_type_with(state, duration_ticks, apy, min_amount, is_active, options)?;
+        
+        // Store the new product type
+        Self::store_product_type(storage, &product_type);
+        
+        Ok(product_type)
+    }
+    
+    /// Storage-independent core of `create_product_type`, numbering the product from `state`
+    pub fn create_product_type_with(
+        state: &mut GlobalState,
+        duration_ticks: u64,
+        apy: u64,
+        min_amount: u64,
+        is_active: bool,
+        options: &ProductTypeOptions
+    ) -> Result<ProductType, u32> {
         // Validate parameters using certificate constants
         if duration_ticks == 0 || duration_ticks > crate::certificate::MAX_CERTIFICATE_DURATION_TICKS {
             return Err(ERROR_INVALID_DURATION);
@@ -62,34 +246,48 @@ impl ProductTypeManager {
         if !(crate::certificate::MIN_CERTIFICATE_AMOUNT..=crate::certificate::MAX_CERTIFICATE_AMOUNT).contains(&min_amount) {
             return Err(ERROR_INVALID_PRINCIPAL_AMOUNT);
         }
+        options.validate()?;
         
-        // Generate new product type ID
-        let product_type_id = {
-            let mut state = GLOBAL_STATE.0.borrow_mut();
-            let id = state.product_type_counter;
-            state.product_type_counter += 1;
-            id
-        };
-        
-        // Create and store product type
-        let mut product_type = ProductType::new(product_type_id, duration_ticks, apy, min_amount);
+        let mut product_type = ProductType::new(0, duration_ticks, apy, min_amount);
         product_type.is_active = is_active; // Set the specified active status
-        Self::store_product_type(&product_type);
+        options.apply(&mut product_type);
+        product_type.validate_currencies()?;
         
-        Ok(product_type_id)
+        // Generate new product type ID
+        product_type.id = state.product_type_counter;
+        state.product_type_counter = safe_add(product_type.id, 1).map_err(|_| ERROR_COUNTER_OVERFLOW)?;
+        state.record_product_status(false, is_active);
+        Ok(product_type)
     }
     
-    /// Modify an existing product type (admin only)
-    pub fn modify_product_type(
+    /// Modify an existing product type (admin only) to the new (apy, duration, min_amount) terms
+    pub fn modify_product_type<S: Storage>(
+        state: &mut GlobalState,
+        storage: &mut S,
         product_type_id: u64,
+        (new_apy, new_duration, new_min_amount): (u64, u64, u64),
+        is_active: bool,
+        options: &ProductTypeOptions
+    ) -> Result<ProductType, u32> {
+        let mut product_type = Self::get_product_type_with(|key| storage.get(key), product_type_id)
+            .ok_or(ERROR_PRODUCT_TYPE_NOT_EXIST)?;
+        Self::modify_product_type_with(&mut product_type, state, new_apy, new_duration, new_min_amount, is_active, options)?;
+        
+        // Store updated product type
+        Self::store_product_type(storage, &product_type);
+        Ok(product_type)
+    }
+    
+    /// Storage-independent core of `modify_product_type`
+    pub fn modify_product_type_with(
+        product_type: &mut ProductType,
+        state: &mut GlobalState,
         new_apy: u64,
         new_duration: u64,
         new_min_amount: u64,
-        is_active: bool
+        is_active: bool,
+        options: &ProductTypeOptions
     ) -> Result<(), u32> {
-        let mut product_type = Self::get_product_type(product_type_id)
-            .ok_or(ERROR_PRODUCT_TYPE_NOT_EXIST)?;
-        
         // Validate new values
         if new_apy > crate::certificate::MAX_APY_BASIS_POINTS {
             return Err(ERROR_INVALID_APY);
@@ -100,140 +298,1152 @@ impl ProductTypeManager {
         if new_min_amount == 0 {
             return Err(ERROR_INVALID_STAKE_AMOUNT);
         }
+        options.validate()?;
         
         // Update fields
+        let was_active = product_type.is_active;
         product_type.apy = new_apy;
         product_type.duration_ticks = new_duration;
         product_type.min_amount = new_min_amount;
         product_type.is_active = is_active;
+        if is_active {
+            product_type.resume_accrual(state.counter);
+        }
+        // Existing certificates keep the mode and currency they were purchased with
+        options.apply(product_type);
+        product_type.validate_currencies()?;
         
-        // Store updated product type
-        Self::store_product_type(&product_type);
+        state.record_product_status(was_active, is_active);
+        Ok(())
+    }
+    
+    /// Open or close a product for purchase, leaving its terms unchanged
+    /// The synthetic recharge product 0 is always open and cannot be toggled
+    /// Closing with `freeze_accrual` also stops its certificates accruing until it is opened again
+    pub fn set_product_type_status<S: Storage>(
+        state: &mut GlobalState,
+        storage: &mut S,
+        product_type_id: u64,
+        is_active: bool,
+        freeze_accrual: bool
+    ) -> Result<ProductType, u32> {
+        let mut product_type = Self::get_product_type_with(|key| storage.get(key), product_type_id)
+            .ok_or(ERROR_PRODUCT_TYPE_NOT_EXIST)?;
+        Self::set_product_type_status_with(&mut product_type, state, is_active, freeze_accrual)?;
+        Self::store_product_type(storage, &product_type);
+        Ok(product_type)
+    }
+    
+    /// Storage-independent core of `set_product_type_status`
+    pub fn set_product_type_status_with(
+        product_type: &mut ProductType,
+        state: &mut GlobalState,
+        is_active: bool,
+        freeze_accrual: bool
+    ) -> Result<(), u32> {
+        if product_type.id == 0 {
+            return Err(ERROR_INVALID_PARAM);
+        }
+        let current_time = state.counter;
+        if is_active {
+            product_type.resume_accrual(current_time);
+        } else if freeze_accrual {
+            product_type.pause_accrual(current_time)?;
+        }
+        let was_active = product_type.is_active;
+        product_type.is_active = is_active;
+        state.record_product_status(was_active, is_active);
+        Ok(())
+    }
+    
+    /// Replace a product's APY schedule, an empty schedule leaves only the base APY
+    /// Certificates already sold keep the schedule locked at their purchase
+    pub fn set_apy_steps<S: Storage>(storage: &mut S, product_type_id: u64, apy_steps: Vec<(u64, u64)>) -> Result<ProductType, u32> {
+        let mut product_type = Self::get_product_type_with(|key| storage.get(key), product_type_id)
+            .ok_or(ERROR_PRODUCT_TYPE_NOT_EXIST)?;
+        Self::set_apy_steps_with(&mut product_type, apy_steps)?;
+        Self::store_product_type(storage, &product_type);
+        Ok(product_type)
+    }
+    
+    /// Storage-independent core of `set_apy_steps`
+    pub fn set_apy_steps_with(product_type: &mut ProductType, apy_steps: Vec<(u64, u64)>) -> Result<(), u32> {
+        if product_type.id == 0 {
+            return Err(ERROR_INVALID_PARAM);
+        }
+        let options = ProductTypeOptions { apy_steps: Some(apy_steps), ..Default::default() };
+        options.validate()?;
+        options.apply(product_type);
         Ok(())
     }
 }
 
+/// Scalar options a create or modify may carry, the APY schedule is set with SET_APY_STEPS
+pub const PRODUCT_OPTION_COUNT: usize = 10;
+
+/// Optional product settings passed as trailing create/modify params
+/// A missing value keeps the default on create and the current value on modify
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProductTypeOptions {
+    pub display_order: Option<u64>,
+    pub compounding: Option<u8>,
+    pub principal_currency: Option<u8>,
+    pub whitelist_only: Option<bool>,
+    pub max_total_principal: Option<u64>,
+    pub interest_currency: Option<u8>,
+    pub early_withdrawal_penalty_bps: Option<u64>,
+    pub max_certificates: Option<u64>,
+    pub grace_ticks: Option<u64>,
+    pub min_claim_interval_ticks: Option<u64>,
+    pub apy_steps: Option<Vec<(u64, u64)>>,
+}
+
+impl ProductTypeOptions {
+    /// Decode `[display_order, compounding, principal_currency, whitelist_only, max_total_principal,
+    /// interest_currency, early_withdrawal_penalty_bps, max_certificates, grace_ticks,
+    /// min_claim_interval_ticks]`, any suffix may be omitted; the APY schedule is never positional
+    pub fn from_params(params: &[u64]) -> Result<Self, u32> {
+        if params.len() > PRODUCT_OPTION_COUNT {
+            return Err(ERROR_INVALID_PARAM);
+        }
+        Ok(ProductTypeOptions {
+            display_order: params.first().copied(),
+            compounding: params.get(1).map(|v| to_u8_checked(*v)).transpose()?,
+            principal_currency: params.get(2).map(|v| to_u8_checked(*v)).transpose()?,
+            whitelist_only: params.get(3).map(|v| to_bool_checked(*v)).transpose()?,
+            max_total_principal: params.get(4).copied(),
+            interest_currency: params.get(5).map(|v| to_u8_checked(*v)).transpose()?,
+            early_withdrawal_penalty_bps: params.get(6).copied(),
+            max_certificates: params.get(7).copied(),
+            grace_ticks: params.get(8).copied(),
+            min_claim_interval_ticks: params.get(9).copied(),
+            apy_steps: None,
+        })
+    }
+
+    /// Decode SET_APY_STEPS `(threshold_ticks, apy)` pairs, no pairs clears the schedule
+    pub fn apy_steps_from_params(params: &[u64]) -> Result<Vec<(u64, u64)>, u32> {
+        if params.len() % 2 != 0 {
+            return Err(ERROR_INVALID_PARAM);
+        }
+        Ok(params.chunks(2).map(|step| (step[0], step[1])).collect())
+    }
+
+    pub fn validate(&self) -> Result<(), u32> {
+        if self.compounding.is_some_and(|c| c > crate::certificate::COMPOUNDING_MONTHLY) {
+            return Err(ERROR_INVALID_COMPOUNDING);
+        }
+        if self.principal_currency.is_some_and(|c| c > crate::certificate::PRINCIPAL_CURRENCY_POINTS) {
+            return Err(ERROR_INVALID_PRINCIPAL_CURRENCY);
+        }
+        if self.early_withdrawal_penalty_bps.is_some_and(|bps| bps > crate::certificate::NO_EARLY_WITHDRAWAL) {
+            return Err(ERROR_INVALID_PARAM);
+        }
+        let too_long = |ticks: Option<u64>| ticks.is_some_and(|ticks| ticks > crate::certificate::MAX_CERTIFICATE_DURATION_TICKS);
+        if too_long(self.grace_ticks) || too_long(self.min_claim_interval_ticks) {
+            return Err(ERROR_INVALID_DURATION);
+        }
+        if let Some(apy_steps) = &self.apy_steps {
+            // Thresholds start after purchase and strictly increase
+            let ordered = apy_steps.windows(2).all(|pair| pair[0].0 < pair[1].0);
+            if apy_steps.len() > crate::certificate::MAX_APY_STEPS
+                || !ordered
+                || apy_steps.iter().any(|(threshold, apy)| *threshold == 0 || *apy > crate::certificate::MAX_APY_BASIS_POINTS)
+            {
+                return Err(ERROR_INVALID_APY);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, product_type: &mut ProductType) {
+        if let Some(display_order) = self.display_order {
+            product_type.display_order = display_order;
+        }
+        if let Some(compounding) = self.compounding {
+            product_type.compounding = compounding;
+        }
+        if let Some(principal_currency) = self.principal_currency {
+            product_type.principal_currency = principal_currency;
+        }
+        // Interest follows a changed principal currency unless set explicitly
+        if let Some(interest_currency) = self.interest_currency.or(self.principal_currency) {
+            product_type.interest_currency = interest_currency;
+        }
+        if let Some(whitelist_only) = self.whitelist_only {
+            product_type.whitelist_only = whitelist_only;
+        }
+        if let Some(max_total_principal) = self.max_total_principal {
+            product_type.max_total_principal = max_total_principal;
+        }
+        if let Some(early_withdrawal_penalty_bps) = self.early_withdrawal_penalty_bps {
+            product_type.early_withdrawal_penalty_bps = early_withdrawal_penalty_bps;
+        }
+        if let Some(max_certificates) = self.max_certificates {
+            product_type.max_certificates = max_certificates;
+        }
+        if let Some(grace_ticks) = self.grace_ticks {
+            product_type.grace_ticks = grace_ticks;
+        }
+        if let Some(min_claim_interval_ticks) = self.min_claim_interval_ticks {
+            product_type.min_claim_interval_ticks = min_claim_interval_ticks;
+        }
+        if let Some(apy_steps) = &self.apy_steps {
+            product_type.apy_steps = apy_steps.clone();
+        }
+    }
+}
+
+/// Manager for per-product purchase whitelists
+pub struct WhitelistManager;
+
+impl WhitelistManager {
+    /// Whether a user may purchase a whitelist-only product
+    pub fn is_whitelisted(product_type_id: u64, user: &[u64; 2]) -> bool {
+        let kvpair = unsafe { &mut MERKLE_MAP };
+        Self::is_whitelisted_with(|key| kvpair.get(key), product_type_id, user)
+    }
+
+    /// Storage-independent core of `is_whitelisted`
+    pub fn is_whitelisted_with<G>(mut get: G, product_type_id: u64, user: &[u64; 2]) -> bool
+    where
+        G: FnMut(&[u64; 4]) -> Vec<u64>,
+    {
+        // Key format: [3, product_type_id, user_high, user_low] for whitelist entries
+        get(&[3, product_type_id, user[0], user[1]]) == [1]
+    }
+
+    /// Add or remove a user, removed entries are stored as [0]
+    pub fn set_whitelisted<S: Storage>(storage: &mut S, product_type_id: u64, user: &[u64; 2], whitelisted: bool) {
+        storage.set(&[3, product_type_id, user[0], user[1]], &[if whitelisted { 1 } else { 0 }]);
+    }
+}
+
 /// Manager for Certificate storage operations
+/// An amount paid out of a certificate together with its PRINCIPAL_CURRENCY_* currency
+pub type Payout = (u64, u8);
+
+/// A claim worked out by `prepare_claim` without writing anything, stored by `commit_claim`
+/// so a caller can check that `payout` can be credited before the claim is recorded
+pub struct PreparedClaim {
+    pub certificate: Certificate,
+    interest: u64,
+    budgeted: bool,
+    previous_drift: u64,
+    pub payout: Payout,
+}
+
+/// A redemption worked out by `prepare_redeem_all` or `prepare_redeem_early` without writing anything,
+/// stored by `commit_redemption` so a caller can check both payouts can be credited first
+pub struct PreparedRedemption {
+    pub certificate: Certificate,
+    claim: Option<PreparedClaim>,
+    forfeited: u64,
+    outstanding: u64,
+    pub interest: Payout,
+    pub principal: Payout,
+}
+
 pub struct CertificateManager;
 
 impl CertificateManager {
     /// Store a certificate
-    pub fn store_certificate(cert: &Certificate) {
+    pub fn store_certificate<S: Storage>(storage: &mut S, cert: &Certificate) {
         let mut data = vec![];
         cert.to_data(&mut data);
-        let kvpair = unsafe { &mut MERKLE_MAP };
         // Key format: [2, owner_high, owner_low, certificate_id] for certificates
-        kvpair.set(&[2, cert.owner[0], cert.owner[1], cert.id], data.as_slice());
+        storage.set(&[2, cert.owner[0], cert.owner[1], cert.id], data.as_slice());
+    }
+    
+    /// Remove a certificate from its owner's key (used when the owner changes)
+    fn delete_certificate<S: Storage>(storage: &mut S, cert: &Certificate) {
+        storage.set(&[2, cert.owner[0], cert.owner[1], cert.id], &[]);
+    }
+    
+    /// Add an opted-in certificate to the auto-claim registry visited by the tick pass
+    pub fn register_auto_claim<S: Storage>(state: &mut GlobalState, storage: &mut S, cert: &Certificate) {
+        state.auto_claim_count += 1;
+        let index = state.auto_claim_count - 1;
+        // Key format: [4, 0, 0, index] for auto-claim registry entries [owner_high, owner_low, certificate_id]
+        storage.set(&[4, 0, 0, index], &[cert.owner[0], cert.owner[1], cert.id]);
+    }
+    
+    /// The next `max` auto-claim certificates, round-robin from the registry cursor
+    /// Entries whose certificate was redeemed or moved to another owner are dropped on the way,
+    /// the last entry takes their slot; each entry is returned at most once per call
+    pub fn next_auto_claims<S: Storage>(state: &mut GlobalState, storage: &mut S, max: usize) -> Vec<([u64; 2], u64)> {
+        let mut due: Vec<([u64; 2], u64)> = vec![];
+        for _ in 0..max {
+            let (count, cursor) = (state.auto_claim_count, state.auto_claim_cursor);
+            if count == 0 {
+                break;
+            }
+            let index = cursor % count;
+            let entry = storage.get(&[4, 0, 0, index]);
+            let (owner, cert_id) = ([entry[0], entry[1]], entry[2]);
+            if due.contains(&(owner, cert_id)) {
+                break;
+            }
+            let live = Self::validate_certificate_ownership_with(|key| storage.get(key), &owner, cert_id)
+                .is_ok_and(|cert| Self::is_auto_claim_live(&cert));
+            if live {
+                due.push((owner, cert_id));
+                state.auto_claim_cursor = index + 1;
+            } else {
+                let last = storage.get(&[4, 0, 0, count - 1]);
+                storage.set(&[4, 0, 0, index], &last);
+                storage.set(&[4, 0, 0, count - 1], &[]);
+                state.auto_claim_count = count - 1;
+                state.auto_claim_cursor = index;
+            }
+        }
+        due
+    }
+    
+    /// Whether the tick pass still claims for a registered certificate
+    pub fn is_auto_claim_live(cert: &Certificate) -> bool {
+        cert.auto_claim
+            && cert.is_interest_bearing()
+            && !matches!(cert.status, crate::certificate::CertificateStatus::Redeemed)
+    }
+    
+    /// Certificates stored under `owner` in id order, the first page of `certificates_page`
+    /// This is every certificate unless the owner's ids span more than MAX_ITERATE_KEYS
+    pub fn list_certificates(owner: &[u64; 2]) -> Vec<Certificate> {
+        Self::certificates_page(owner, 0).items
+    }
+    
+    /// One page of the certificates stored under `owner` in id order, scanning at most
+    /// MAX_ITERATE_KEYS ids from `start`; ids are per owner, so the scan is clamped to the stored
+    /// player's range from its first certificate id up to its next one
+    pub fn certificates_page(owner: &[u64; 2], start: u64) -> Page<Certificate> {
+        let legacy_end = with_state(|state| state.certificate_counter);
+        let id_range = StakingPlayer::get_from_pid(owner).map_or((0, 0), |player| player.data.certificate_id_range(legacy_end));
+        let kvpair = unsafe { &mut MERKLE_MAP };
+        Self::certificates_page_with(|key| kvpair.get(key), owner, id_range, start)
+    }
+    
+    /// Storage-independent core of `certificates_page`, over the owner's `(first, end)` id range
+    pub fn certificates_page_with<G>(mut get: G, owner: &[u64; 2], (first, end): (u64, u64), start: u64) -> Page<Certificate>
+    where
+        G: FnMut(&[u64; 4]) -> Vec<u64>,
+    {
+        let mut certificates = vec![];
+        let resume = iterate_keys_with(&mut get, [2, owner[0], owner[1]], start.max(first), end, MAX_ITERATE_KEYS, |_, data| {
+            // A record that fails the checked decode is left out of listings, direct reads report it
+            if let Ok(certificate) = Certificate::try_from_data(&mut data.iter_mut()) {
+                certificates.push(certificate);
+            }
+        });
+        Page {
+            items: certificates.into_iter().map(|certificate| Self::with_accrual_pauses(&mut get, certificate)).collect(),
+            has_more: resume.is_some(),
+            next: resume.unwrap_or(end),
+        }
+    }
+    
+    /// Attach the product's accrual pauses, which apply to certificates already sold
+    fn with_accrual_pauses<G>(get: G, mut certificate: Certificate) -> Certificate
+    where
+        G: FnMut(&[u64; 4]) -> Vec<u64>,
+    {
+        if certificate.product_type_id != 0 {
+            if let Some(product_type) = ProductTypeManager::get_product_type_with(get, certificate.product_type_id) {
+                certificate.accrual_pauses = product_type.accrual_pauses;
+            }
+        }
+        certificate
+    }
+    
+    /// Up to `max` of an owner's closed (redeemed) certificates in id order, for history views
+    /// The final status and total_interest_claimed are kept on the certificate once it closes
+    pub fn closed_certificates(owner: &[u64; 2], max: usize) -> Vec<Certificate> {
+        Self::closed_certificates_from(Self::list_certificates(owner), max)
+    }
+    
+    /// Storage-independent core of `closed_certificates`, over an owner's listed certificates
+    pub fn closed_certificates_from(certificates: Vec<Certificate>, max: usize) -> Vec<Certificate> {
+        certificates
+            .into_iter()
+            .filter(|certificate| matches!(certificate.status, crate::certificate::CertificateStatus::Redeemed))
+            .take(max)
+            .collect()
+    }
+    
+    /// An owner's certificate with its status refreshed to the current counter, not written back
+    pub fn get_certificate_with_status(owner: &[u64; 2], cert_id: u64) -> Result<Certificate, u32> {
+        let certificate = Self::validate_certificate_ownership(owner, cert_id)?;
+        Ok(Self::with_current_status(certificate, with_state(|state| state.counter)))
+    }
+    
+    /// Stored status only changes when a mutating operation runs, views refresh Matured at `counter`
+    pub fn with_current_status(mut certificate: Certificate, counter: u64) -> Certificate {
+        certificate.update_status(counter);
+        certificate
     }
     
     /// Validate certificate ownership and retrieve certificate for operations
     /// This is used internally for certificate operations (withdraw/redeem)
     pub fn validate_certificate_ownership(owner: &[u64; 2], cert_id: u64) -> Result<Certificate, u32> {
         let kvpair = unsafe { &mut MERKLE_MAP };
-        let mut data = kvpair.get(&[2, owner[0], owner[1], cert_id]);
+        Self::validate_certificate_ownership_with(|key| kvpair.get(key), owner, cert_id)
+    }
+    
+    /// Storage-independent core of `validate_certificate_ownership`
+    pub fn validate_certificate_ownership_with<G>(mut get: G, owner: &[u64; 2], cert_id: u64) -> Result<Certificate, u32>
+    where
+        G: FnMut(&[u64; 4]) -> Vec<u64>,
+    {
+        let mut data = get(&[2, owner[0], owner[1], cert_id]);
         if !data.is_empty() {
             let mut u64data = data.iter_mut();
-            Ok(Certificate::from_data(&mut u64data))
+            Ok(Self::with_accrual_pauses(get, Certificate::try_from_data(&mut u64data)?))
         } else {
             Err(ERROR_CERTIFICATE_NOT_OWNED)
         }
     }
     
-    /// Create a new certificate (purchase)
-    pub fn purchase_certificate(
-        owner: [u64; 2],
+    /// Validate a purchase without touching storage or global state
+    /// Returns the product type and the maturity time the certificate would get
+    pub fn prepare_purchase<S: Storage>(
+        state: &GlobalState,
+        storage: &S,
+        owner: &[u64; 2],
+        player: &PlayerData,
         product_type_id: u64,
         principal_amount: u64
-    ) -> Result<u64, u32> {
+    ) -> Result<(ProductType, u64), u32> {
+        let product_type = ProductTypeManager::get_product_type_with(|key| storage.get(key), product_type_id);
+        let is_whitelisted = || WhitelistManager::is_whitelisted_with(|key| storage.get(key), product_type_id, owner);
+        Self::prepare_purchase_with(product_type, is_whitelisted, state, player, principal_amount, 0, true)
+    }
+    
+    /// `prepare_purchase` for sweeping `dust` idle funds, below MIN_CERTIFICATE_AMOUNT, into a certificate
+    /// The global and product minimums are skipped; the product must still be open and take idle funds
+    pub fn prepare_dust_sweep<S: Storage>(
+        state: &GlobalState,
+        storage: &S,
+        owner: &[u64; 2],
+        player: &PlayerData,
+        product_type_id: u64,
+        dust: u64
+    ) -> Result<(ProductType, u64), u32> {
+        let product_type = ProductTypeManager::get_product_type_with(|key| storage.get(key), product_type_id);
+        let is_whitelisted = || WhitelistManager::is_whitelisted_with(|key| storage.get(key), product_type_id, owner);
+        Self::prepare_dust_sweep_with(product_type, is_whitelisted, state, player, dust)
+    }
+    
+    /// Storage-independent core of `prepare_dust_sweep`, see `prepare_purchase_with`
+    pub fn prepare_dust_sweep_with<W>(
+        product_type: Option<ProductType>,
+        is_whitelisted: W,
+        state: &GlobalState,
+        player: &PlayerData,
+        dust: u64
+    ) -> Result<(ProductType, u64), u32>
+    where
+        W: FnOnce() -> bool,
+    {
+        if dust == 0 || dust >= crate::certificate::MIN_CERTIFICATE_AMOUNT {
+            return Err(ERROR_IDLE_FUNDS_NOT_DUST);
+        }
+        let prepared = Self::prepare_purchase_with(product_type, is_whitelisted, state, player, dust, 0, false)?;
+        if prepared.0.principal_currency != crate::certificate::PRINCIPAL_CURRENCY_USDT {
+            return Err(ERROR_INVALID_PRINCIPAL_CURRENCY);
+        }
+        Ok(prepared)
+    }
+    
+    /// Storage-independent core of `prepare_purchase` for the stored `product_type`, None when
+    /// it does not exist; `is_whitelisted` is only asked for whitelist-only products
+    /// A purchase that first frees `released` principal of the same product counts it as sold
+    /// already, without `minimums` the global and product minimum amounts are not enforced
+    pub fn prepare_purchase_with<W>(
+        product_type: Option<ProductType>,
+        is_whitelisted: W,
+        state: &GlobalState,
+        player: &PlayerData,
+        principal_amount: u64,
+        released: u64,
+        minimums: bool
+    ) -> Result<(ProductType, u64), u32>
+    where
+        W: FnOnce() -> bool,
+    {
         // Validate principal amount within global limits first
-        if !(crate::certificate::MIN_CERTIFICATE_AMOUNT..=crate::certificate::MAX_CERTIFICATE_AMOUNT).contains(&principal_amount) {
+        let minimum = if minimums { crate::certificate::MIN_CERTIFICATE_AMOUNT } else { 1 };
+        if !(minimum..=crate::certificate::MAX_CERTIFICATE_AMOUNT).contains(&principal_amount) {
             return Err(ERROR_INVALID_PRINCIPAL_AMOUNT);
         }
         
         // Validate product type exists and is active
-        let product_type = ProductTypeManager::get_product_type(product_type_id)
-            .ok_or(ERROR_PRODUCT_TYPE_NOT_EXIST)?;
+        let product_type = product_type.ok_or(ERROR_PRODUCT_TYPE_NOT_EXIST)?;
             
         if !product_type.is_active {
             return Err(ERROR_PRODUCT_TYPE_INACTIVE);
         }
         
+        if product_type.whitelist_only && !is_whitelisted() {
+            return Err(ERROR_NOT_WHITELISTED);
+        }
+        
         // Validate minimum investment amount for this product type
-        if principal_amount < product_type.min_amount {
+        if minimums && principal_amount < product_type.min_amount {
             return Err(ERROR_PRINCIPAL_AMOUNT_TOO_SMALL);
         }
         
-        // Generate new certificate ID
-        let certificate_id = {
-            let mut state = GLOBAL_STATE.0.borrow_mut();
-            let id = state.certificate_counter;
-            state.certificate_counter += 1;
-            id
-        };
+        // Capped products only sell up to max_total_principal outstanding
+        let outstanding = product_type.current_total_principal.saturating_sub(released);
+        if product_type.max_total_principal > 0
+            && safe_add(outstanding, principal_amount)? > product_type.max_total_principal
+        {
+            return Err(ERROR_PRODUCT_CAP_EXCEEDED);
+        }
+        
+        // Count-capped products only sell max_certificates certificates in total
+        if product_type.max_certificates > 0 && product_type.certificate_count >= product_type.max_certificates {
+            return Err(ERROR_PRODUCT_CERT_LIMIT);
+        }
+        
+        // The certificate id must not exhaust the owner's counter
+        let (current_time, legacy_end, seconds_per_tick) =
+            (state.counter, state.certificate_counter, state.seconds_per_tick);
+        safe_add(player.next_certificate_id(legacy_end), 1).map_err(|_| ERROR_COUNTER_OVERFLOW)?;
         
         // Calculate maturity time
-        let current_time = GLOBAL_STATE.0.borrow().counter;
         let maturity_time = product_type.calculate_maturity_time(current_time)?;
         
-        // Create and store certificate
-        let certificate = Certificate::new(
+        // Interest through maturity is booked as a liability at purchase, so it must be computable
+        let mut probe = Certificate::new(0, [0, 0], product_type.id, principal_amount, current_time, maturity_time, product_type.apy);
+        Self::lock_product_terms(&mut probe, &product_type);
+        probe.seconds_per_tick = seconds_per_tick;
+        Self::liability_of(&probe)?;
+        
+        Ok((product_type, maturity_time))
+    }
+    
+    /// Validate a batch of `(product_type_id, amount)` purchases as a whole, before anything is stored
+    /// Each item must pass `prepare_purchase` and items sharing a capped product must fit its caps together
+    pub fn prepare_batch_purchase<S: Storage>(
+        state: &GlobalState,
+        storage: &S,
+        owner: &[u64; 2],
+        player: &PlayerData,
+        items: &[(u64, u64)]
+    ) -> Result<Vec<ProductType>, u32> {
+        let get_product_type = |product_type_id| ProductTypeManager::get_product_type_with(|key| storage.get(key), product_type_id);
+        let is_whitelisted = |product_type_id| WhitelistManager::is_whitelisted_with(|key| storage.get(key), product_type_id, owner);
+        Self::prepare_batch_purchase_with(get_product_type, is_whitelisted, state, player, items)
+    }
+    
+    /// Storage-independent core of `prepare_batch_purchase`, looking products and whitelist
+    /// entries up by product type id
+    pub fn prepare_batch_purchase_with<P, W>(
+        mut get_product_type: P,
+        mut is_whitelisted: W,
+        state: &GlobalState,
+        player: &PlayerData,
+        items: &[(u64, u64)]
+    ) -> Result<Vec<ProductType>, u32>
+    where
+        P: FnMut(u64) -> Option<ProductType>,
+        W: FnMut(u64) -> bool,
+    {
+        let mut products = Vec::with_capacity(items.len());
+        for &(product_type_id, principal_amount) in items {
+            let product_type = get_product_type(product_type_id);
+            let whitelisted = || is_whitelisted(product_type_id);
+            let (product_type, _) = Self::prepare_purchase_with(product_type, whitelisted, state, player, principal_amount, 0, true)?;
+            products.push(product_type);
+        }
+        let legacy_end = state.certificate_counter;
+        safe_add(player.next_certificate_id(legacy_end), items.len() as u64).map_err(|_| ERROR_COUNTER_OVERFLOW)?;
+        
+        for product_type in products.iter().filter(|product_type| product_type.max_total_principal > 0) {
+            let combined = items
+                .iter()
+                .filter(|(product_type_id, _)| *product_type_id == product_type.id)
+                .try_fold(product_type.current_total_principal, |sum, (_, amount)| safe_add(sum, *amount))?;
+            if combined > product_type.max_total_principal {
+                return Err(ERROR_PRODUCT_CAP_EXCEEDED);
+            }
+        }
+        for product_type in products.iter().filter(|product_type| product_type.max_certificates > 0) {
+            let count = items.iter().filter(|(product_type_id, _)| *product_type_id == product_type.id).count() as u64;
+            if safe_add(product_type.certificate_count, count)? > product_type.max_certificates {
+                return Err(ERROR_PRODUCT_CERT_LIMIT);
+            }
+        }
+        Ok(products)
+    }
+    
+    /// Create a new certificate (purchase), numbered from the owner's own counter on `player`
+    /// With `auto_claim` the certificate joins the registry the tick pass claims interest for
+    /// Fails only during `prepare_purchase`, before the counter or storage is touched
+    pub fn purchase_certificate<S: Storage>(
+        state: &mut GlobalState,
+        storage: &mut S,
+        owner: [u64; 2],
+        player: &mut PlayerData,
+        product_type_id: u64,
+        principal_amount: u64,
+        auto_claim: bool
+    ) -> Result<Certificate, u32> {
+        let prepared = Self::prepare_purchase(state, storage, &owner, player, product_type_id, principal_amount)?;
+        Self::issue_certificate(state, storage, owner, player, prepared, principal_amount, auto_claim)
+    }
+    
+    /// Store a certificate for a purchase that passed its prepare step, with the product and
+    /// maturity time it returned; the product must not have been stored since
+    pub fn issue_certificate<S: Storage>(
+        state: &mut GlobalState,
+        storage: &mut S,
+        owner: [u64; 2],
+        player: &mut PlayerData,
+        (mut product_type, maturity_time): (ProductType, u64),
+        principal_amount: u64,
+        auto_claim: bool
+    ) -> Result<Certificate, u32> {
+        let certificate = Self::issue_certificate_with(owner, player, &mut product_type, state, maturity_time, principal_amount, auto_claim)?;
+        // The recharge product 0 is synthetic, only stored products keep their counters
+        if product_type.id != 0 {
+            ProductTypeManager::store_product_type(storage, &product_type);
+        }
+        Self::store_certificate(storage, &certificate);
+        if auto_claim {
+            Self::register_auto_claim(state, storage, &certificate);
+        }
+        Ok(certificate)
+    }
+    
+    /// Storage-independent core of `issue_certificate`, booking the new certificate against
+    /// `product_type` and `state`; storing both and the auto-claim registry are left to the caller
+    pub fn issue_certificate_with(
+        owner: [u64; 2],
+        player: &mut PlayerData,
+        product_type: &mut ProductType,
+        state: &mut GlobalState,
+        maturity_time: u64,
+        principal_amount: u64,
+        auto_claim: bool
+    ) -> Result<Certificate, u32> {
+        // Generate new certificate ID (the counter was checked in prepare_purchase)
+        let certificate_id = player.take_certificate_id(state.certificate_counter)?;
+        
+        let mut certificate = Certificate::new(
             certificate_id,
             owner,
-            product_type_id,
+            product_type.id,
             principal_amount,
-            current_time,
+            state.counter,
             maturity_time,
             product_type.apy
         );
+        Self::lock_product_terms(&mut certificate, product_type);
+        // Later changes to the tick length do not reprice interest already being earned
+        certificate.seconds_per_tick = state.seconds_per_tick;
+        certificate.auto_claim = auto_claim;
+        let (principal_liability, interest_liability) = Self::liability_of(&certificate)?;
         
-        Self::store_certificate(&certificate);
-        Ok(certificate_id)
+        // Track outstanding principal and the sold count on stored products (the recharge product 0 is synthetic)
+        if product_type.id != 0 {
+            product_type.current_total_principal += principal_amount; // bounded by the cap check above
+            product_type.certificate_count += 1; // bounded by max_certificates or by the certificate ids
+        }
+        state.record_certificate_liability(principal_liability, interest_liability);
+        state.record_certificate_opened();
+        Ok(certificate)
     }
     
-    /// Claim all available interest from a certificate
-    pub fn claim_interest(
+    /// Copy the terms a certificate keeps for its lifetime from its product
+    fn lock_product_terms(certificate: &mut Certificate, product_type: &ProductType) {
+        certificate.compounding = product_type.compounding;
+        certificate.principal_currency = product_type.principal_currency;
+        certificate.apy_steps = product_type.apy_steps.clone();
+        certificate.interest_currency = product_type.interest_currency;
+        certificate.early_withdrawal_penalty_bps = product_type.early_withdrawal_penalty_bps;
+        certificate.grace_ticks = product_type.grace_ticks;
+    }
+    
+    /// USDT liabilities of an open certificate: its principal and the interest it earns through maturity
+    fn liability_of(cert: &Certificate) -> Result<(u64, u64), u32> {
+        let principal = if cert.principal_currency == crate::certificate::PRINCIPAL_CURRENCY_USDT {
+            cert.principal
+        } else {
+            0
+        };
+        let interest = if cert.interest_currency == crate::certificate::PRINCIPAL_CURRENCY_USDT {
+            cert.calculate_total_interest(cert.accrual_end())?
+        } else {
+            0
+        };
+        Ok((principal, interest))
+    }
+    
+    /// Release redeemed principal from the liability total, the product's issuance capacity is
+    /// released by `release_product_principal` once the certificate is stored
+    fn release_principal_liability(cert: &Certificate, amount: u64, state: &mut GlobalState) {
+        if cert.principal_currency == crate::certificate::PRINCIPAL_CURRENCY_USDT {
+            state.release_certificate_principal(amount);
+        }
+    }
+    
+    /// Work out a claim of `amount` of a certificate's available interest, or all of it when `amount`
+    /// is 0, at most max_claim_lookback_ticks' worth; nothing is stored until `commit_claim`
+    /// Less than MIN_INTEREST_CLAIM is rejected and a certificate that never pays interest
+    /// (such as recharge product 0) fails distinctly
+    pub fn prepare_claim<S: Storage>(
+        state: &GlobalState,
+        storage: &S,
         owner: &[u64; 2],
-        cert_id: u64
-    ) -> Result<u64, u32> {
-        let mut cert = Self::validate_certificate_ownership(owner, cert_id)?;
+        cert_id: u64,
+        amount: u64
+    ) -> Result<PreparedClaim, u32> {
+        let cert = Self::validate_certificate_ownership_with(|key| storage.get(key), owner, cert_id)?;
+        // User claims are spaced by the product's current claim interval
+        let min_interval = ProductTypeManager::get_product_type_with(|key| storage.get(key), cert.product_type_id)
+            .map_or(0, |product_type| product_type.min_claim_interval_ticks);
+        Self::prepare_claim_with(cert, state, true, min_interval, amount)
+    }
+    
+    /// Record a prepared claim, the global accounting first and then the certificate
+    /// Every check ran while preparing, the accounting update itself validates before it changes anything
+    pub fn commit_claim<S: Storage>(state: &mut GlobalState, storage: &mut S, claim: &PreparedClaim) -> Result<Payout, u32> {
+        let payout = Self::record_claim(state, claim)?;
+        Self::store_certificate(storage, &claim.certificate);
+        Ok(payout)
+    }
+    
+    /// Global accounting of a prepared claim, see `commit_claim`
+    pub fn record_claim(state: &mut GlobalState, claim: &PreparedClaim) -> Result<Payout, u32> {
+        let cert = &claim.certificate;
+        if cert.interest_currency == crate::certificate::PRINCIPAL_CURRENCY_USDT {
+            state.record_usdt_claim(claim.interest, claim.budgeted, claim.previous_drift, cert.rounding_drift)?;
+        }
+        Ok(claim.payout)
+    }
+    
+    /// Interest that will be available at `at_time` if nothing more is claimed, clamped at maturity
+    /// Read-only; a time before the current counter is rejected
+    pub fn project_interest(owner: &[u64; 2], cert_id: u64, at_time: u64) -> Result<u64, u32> {
+        let cert = Self::validate_certificate_ownership(owner, cert_id)?;
+        with_state(|state| Self::project_interest_with(&cert, state, at_time))
+    }
+    
+    /// Storage-independent core of `project_interest`
+    pub fn project_interest_with(cert: &Certificate, state: &GlobalState, at_time: u64) -> Result<u64, u32> {
+        if at_time < state.counter {
+            return Err(ERROR_INVALID_PARAM);
+        }
+        cert.calculate_available_interest(at_time.min(cert.accrual_end()))
+    }
+    
+    /// Work out the claim that leaves exactly `target_remaining` of the available interest
+    /// unclaimed, everything when it is 0; a target above the available interest is rejected
+    pub fn prepare_claim_to_target<S: Storage>(
+        state: &GlobalState,
+        storage: &S,
+        owner: &[u64; 2],
+        cert_id: u64,
+        target_remaining: u64
+    ) -> Result<PreparedClaim, u32> {
+        let cert = Self::validate_certificate_ownership_with(|key| storage.get(key), owner, cert_id)?;
+        let amount = Self::target_claim_amount(&cert, state, target_remaining)?;
+        Self::prepare_claim(state, storage, owner, cert_id, amount)
+    }
+    
+    /// Storage-independent core of `prepare_claim_to_target`, the amount it claims
+    pub fn target_claim_amount(cert: &Certificate, state: &GlobalState, target_remaining: u64) -> Result<u64, u32> {
+        let available_interest = cert.calculate_available_interest(state.interest_claim_cutoff(state.counter))?;
+        if target_remaining > available_interest {
+            return Err(ERROR_INVALID_CLAIM_TARGET);
+        }
+        // A target equal to the available interest leaves nothing to claim
+        if target_remaining == available_interest {
+            return Err(ERROR_INSUFFICIENT_INTEREST);
+        }
+        Ok(available_interest - target_remaining)
+    }
+    
+    /// Work out a claim on a loaded certificate against `state`, spaced by `min_interval`
+    /// Redemptions pass `capped` false to settle the whole backlog, skipping the lookback cap, the
+    /// claim minimum and the per-tick interest budget so no dust is left behind; crediting any USDT
+    /// payout still needs the recharge reserve to back it and its booster bonus
+    pub fn prepare_claim_with(
+        mut cert: Certificate,
+        state: &GlobalState,
+        capped: bool,
+        min_interval: u64,
+        amount: u64
+    ) -> Result<PreparedClaim, u32> {
+        if capped && !cert.is_interest_bearing() {
+            return Err(ERROR_PRODUCT_NON_INTEREST_BEARING);
+        }
+        // While payouts are paused only interest accrued before the pause can be claimed
+        let (current_time, claim_time, lookback) =
+            (state.counter, state.interest_claim_cutoff(state.counter), state.max_claim_lookback_ticks);
+        if capped {
+            cert.check_claim_interval(min_interval, current_time)?;
+        }
+        let mut available_interest = cert.calculate_available_interest(claim_time)?;
+        
+        // A long-dormant certificate is paid its backlog over several claims, each at most
+        // what the certificate earns in `lookback` ticks (at least the claim minimum so claims always progress)
+        if capped && lookback > 0 {
+            let window = cert.calculate_total_interest(cert.purchase_time.saturating_add(lookback))?;
+            available_interest = available_interest.min(window.max(crate::certificate::MIN_INTEREST_CLAIM));
+        }
         
-        let current_time = GLOBAL_STATE.0.borrow().counter;
-        let available_interest = cert.calculate_available_interest(current_time)?;
+        // A specific amount must be covered by what is available now
+        if amount > 0 {
+            if amount > available_interest {
+                return Err(ERROR_INSUFFICIENT_INTEREST);
+            }
+            available_interest = amount;
+        }
         
-        // Only claim if there's at least 1 unit of interest available
+        if capped && available_interest < crate::certificate::MIN_INTEREST_CLAIM {
+            return Err(ERROR_CLAIM_TOO_SMALL);
+        }
+        // Redemptions only need at least 1 unit of interest available
         if available_interest == 0 {
             return Err(ERROR_INSUFFICIENT_INTEREST);
         }
         
+        // Claims paid in USDT share the system-wide per-tick budget
+        let budgeted = capped && cert.interest_currency == crate::certificate::PRINCIPAL_CURRENCY_USDT;
+        if budgeted {
+            available_interest = state.interest_tick_allowance(available_interest)?;
+            state.check_interest_reserve(available_interest, state.booster_bonus(available_interest, current_time)?)?;
+        }
+        let payout = Self::interest_payout(&cert, available_interest, state.interest_points_rate)?;
+        
         // Record the claim (add to total claimed)
         cert.claim_interest(available_interest)?;
-        Self::store_certificate(&cert);
         
-        Ok(available_interest)
+        // Committing settles the liability, charges the tick budget and moves the protocol's rounding
+        // surplus from the previous claim's drift to the current one; interest paid in points is
+        // outside the USDT accounting
+        let previous_drift = cert.rounding_drift;
+        if cert.interest_currency == crate::certificate::PRINCIPAL_CURRENCY_USDT {
+            let drift = cert.calculate_rounding_drift(claim_time)?;
+            state.rounding_surplus_after(previous_drift, drift)?;
+            cert.rounding_drift = drift;
+        }
+        // A claim after maturity also records that the certificate has matured
+        cert.last_claim_counter = current_time;
+        cert.update_status(current_time);
+        
+        Ok(PreparedClaim { certificate: cert, interest: available_interest, budgeted, previous_drift, payout })
     }
     
-    /// Redeem principal from a matured certificate
-    pub fn redeem_principal(
+    /// USDT-principal interest paid in points is converted at interest_points_rate
+    /// Computed before a claim or exit changes anything, the conversion can overflow
+    pub fn interest_payout(cert: &Certificate, interest: u64, interest_points_rate: u64) -> Result<Payout, u32> {
+        let payout = if cert.interest_currency != cert.principal_currency {
+            safe_mul(interest, interest_points_rate)?
+        } else {
+            interest
+        };
+        Ok((payout, cert.interest_currency))
+    }
+    
+    /// Validate rolling a matured certificate over into a new one of the same product, before anything is stored
+    /// The new principal is the old principal plus its unclaimed interest, so both must be in one currency
+    /// Returns the product type and the new principal
+    pub fn prepare_rollover<S: Storage>(
+        state: &GlobalState,
+        storage: &S,
         owner: &[u64; 2],
+        player: &PlayerData,
         cert_id: u64
-    ) -> Result<u64, u32> {
-        let mut cert = Self::validate_certificate_ownership(owner, cert_id)?;
+    ) -> Result<(ProductType, u64), u32> {
+        let cert = Self::validate_certificate_ownership_with(|key| storage.get(key), owner, cert_id)?;
+        let product_type_id = cert.product_type_id;
+        let product_type = ProductTypeManager::get_product_type_with(|key| storage.get(key), product_type_id);
+        let is_whitelisted = || WhitelistManager::is_whitelisted_with(|key| storage.get(key), product_type_id, owner);
+        Self::prepare_rollover_with(cert, product_type, is_whitelisted, state, player)
+    }
+    
+    /// Storage-independent core of `prepare_rollover` for the certificate's stored `product_type`,
+    /// see `prepare_purchase_with`
+    pub fn prepare_rollover_with<W>(
+        mut cert: Certificate,
+        product_type: Option<ProductType>,
+        is_whitelisted: W,
+        state: &GlobalState,
+        player: &PlayerData
+    ) -> Result<(ProductType, u64), u32>
+    where
+        W: FnOnce() -> bool,
+    {
+        let (current_time, claim_time) = (state.counter, state.interest_claim_cutoff(state.counter));
+        cert.update_status(current_time);
         
-        let current_time = GLOBAL_STATE.0.borrow().counter;
+        if matches!(cert.status, crate::certificate::CertificateStatus::Redeemed) {
+            return Err(ERROR_CERTIFICATE_ALREADY_REDEEMED);
+        }
+        if !cert.is_matured(current_time) {
+            return Err(ERROR_CERTIFICATE_NOT_MATURED);
+        }
+        if cert.interest_currency != cert.principal_currency {
+            return Err(ERROR_INVALID_INTEREST_CURRENCY);
+        }
         
-        // Update certificate status
+        let principal = safe_add(cert.principal, cert.calculate_available_interest(claim_time)?)?;
+        let (product_type, _) = Self::prepare_purchase_with(product_type, is_whitelisted, state, player, principal, cert.principal, true)?;
+        // The product may have switched currency since the certificate was bought
+        if product_type.principal_currency != cert.principal_currency {
+            return Err(ERROR_INVALID_PRINCIPAL_CURRENCY);
+        }
+        Ok((product_type, principal))
+    }
+    
+    /// Work out claiming any available interest and redeeming the full principal of a matured
+    /// certificate; nothing is stored until `commit_redemption`
+    pub fn prepare_redeem_all<S: Storage>(
+        state: &GlobalState,
+        storage: &S,
+        owner: &[u64; 2],
+        cert_id: u64
+    ) -> Result<PreparedRedemption, u32> {
+        let cert = Self::validate_certificate_ownership_with(|key| storage.get(key), owner, cert_id)?;
+        Self::prepare_redeem_all_with(cert, state)
+    }
+    
+    /// Storage-independent core of `prepare_redeem_all`, on a loaded certificate against `state`
+    pub fn prepare_redeem_all_with(mut cert: Certificate, state: &GlobalState) -> Result<PreparedRedemption, u32> {
+        let current_time = state.counter;
         cert.update_status(current_time);
         
-        // Check if already redeemed
         if matches!(cert.status, crate::certificate::CertificateStatus::Redeemed) {
             return Err(ERROR_CERTIFICATE_ALREADY_REDEEMED);
         }
+        if !cert.is_matured(current_time) {
+            return Err(ERROR_CERTIFICATE_NOT_MATURED);
+        }
         
-        // Redeem principal
+        let claim = match Self::prepare_claim_with(cert.clone(), state, false, 0, 0) {
+            Ok(claim) => Some(claim),
+            Err(ERROR_INSUFFICIENT_INTEREST) => None,
+            Err(e) => return Err(e),
+        };
+        let interest = claim.as_ref().map_or((0, cert.interest_currency), |claim| claim.payout);
+        if let Some(claim) = &claim {
+            cert = claim.certificate.clone();
+        }
         cert.redeem_principal(current_time)?;
-        Self::store_certificate(&cert);
         
-        Ok(cert.principal)
+        let principal = (cert.principal, cert.principal_currency);
+        Ok(PreparedRedemption { certificate: cert, claim, forfeited: 0, outstanding: 0, interest, principal })
+    }
+    
+    /// Work out exiting a certificate before maturity, forfeiting early_withdrawal_penalty_bps of the
+    /// unclaimed interest into the reserve pool; a matured certificate is redeemed in full instead,
+    /// without any penalty. Nothing is stored until `commit_redemption`
+    pub fn prepare_redeem_early<S: Storage>(
+        state: &GlobalState,
+        storage: &S,
+        owner: &[u64; 2],
+        cert_id: u64
+    ) -> Result<PreparedRedemption, u32> {
+        let cert = Self::validate_certificate_ownership_with(|key| storage.get(key), owner, cert_id)?;
+        Self::prepare_redeem_early_with(cert, state)
+    }
+    
+    /// Storage-independent core of `prepare_redeem_early`
+    pub fn prepare_redeem_early_with(mut cert: Certificate, state: &GlobalState) -> Result<PreparedRedemption, u32> {
+        let (current_time, claim_time) = (state.counter, state.interest_claim_cutoff(state.counter));
+        cert.update_status(current_time);
+        
+        if matches!(cert.status, crate::certificate::CertificateStatus::Redeemed) {
+            return Err(ERROR_CERTIFICATE_ALREADY_REDEEMED);
+        }
+        if cert.is_matured(current_time) {
+            return Self::prepare_redeem_all_with(cert, state);
+        }
+        
+        let accrued = cert.calculate_available_interest(claim_time)?;
+        let (_, scheduled) = Self::liability_of(&cert)?;
+        // Whatever the exit pays or forfeits, nothing more is owed on this certificate
+        let outstanding = scheduled.saturating_sub(cert.total_interest_claimed);
+        let interest = cert.redeem_early(accrued)?;
+        let payout = Self::interest_payout(&cert, interest, state.interest_points_rate)?;
+        // The forfeited share is protocol revenue when interest is paid in USDT, points just lapse
+        let forfeited = if cert.interest_currency == crate::certificate::PRINCIPAL_CURRENCY_USDT {
+            safe_sub(accrued, interest)?
+        } else {
+            0
+        };
+        
+        let principal = (cert.principal, cert.principal_currency);
+        Ok(PreparedRedemption { certificate: cert, claim: None, forfeited, outstanding, interest: payout, principal })
+    }
+    
+    /// Record a prepared redemption, the global accounting first and then the certificate and
+    /// its product's issuance capacity
+    pub fn commit_redemption<S: Storage>(state: &mut GlobalState, storage: &mut S, redemption: &PreparedRedemption) -> Result<(), u32> {
+        Self::record_redemption(state, redemption)?;
+        let cert = &redemption.certificate;
+        Self::store_certificate(storage, cert);
+        Self::release_product_principal(storage, cert.product_type_id, cert.principal);
+        Ok(())
+    }
+    
+    /// Global accounting of a prepared redemption, see `commit_redemption`
+    /// Only its first step can fail and that validates before it changes anything
+    pub fn record_redemption(state: &mut GlobalState, redemption: &PreparedRedemption) -> Result<(), u32> {
+        let cert = &redemption.certificate;
+        match &redemption.claim {
+            Some(claim) => {
+                Self::record_claim(state, claim)?;
+            }
+            None => {
+                state.record_forfeited_interest(redemption.forfeited)?;
+            }
+        }
+        Self::release_principal_liability(cert, cert.principal, state);
+        state.settle_interest_owed(redemption.outstanding);
+        state.record_certificate_closed();
+        Ok(())
+    }
+    
+    /// Mature a certificate at the current counter, for admin sweeps of a player's position
+    pub fn force_mature<S: Storage>(state: &mut GlobalState, storage: &mut S, owner: &[u64; 2], cert_id: u64) -> Result<(), u32> {
+        let mut cert = Self::validate_certificate_ownership_with(|key| storage.get(key), owner, cert_id)?;
+        Self::force_mature_with(&mut cert, state)?;
+        Self::store_certificate(storage, &cert);
+        Ok(())
+    }
+    
+    /// Storage-independent core of `force_mature`
+    pub fn force_mature_with(cert: &mut Certificate, state: &mut GlobalState) -> Result<(), u32> {
+        if matches!(cert.status, crate::certificate::CertificateStatus::Redeemed) {
+            return Err(ERROR_CERTIFICATE_ALREADY_REDEEMED);
+        }
+        let (_, scheduled) = Self::liability_of(cert)?;
+        cert.force_mature(state.counter);
+        let (_, remaining) = Self::liability_of(cert)?;
+        
+        // Interest the shortened term no longer earns drops out of the liability
+        state.settle_interest_owed(scheduled.saturating_sub(remaining));
+        Ok(())
+    }
+    
+    /// Redeem part of a matured certificate's principal
+    /// Interest is computed from the principal, so unclaimed interest must be claimed first or
+    /// the smaller principal would shrink it; accrual has stopped at maturity so nothing new accrues
+    pub fn redeem_principal_partial<S: Storage>(
+        state: &mut GlobalState,
+        storage: &mut S,
+        owner: &[u64; 2],
+        cert_id: u64,
+        amount: u64
+    ) -> Result<(u64, u8), u32> {
+        let mut cert = Self::validate_certificate_ownership_with(|key| storage.get(key), owner, cert_id)?;
+        let principal = Self::redeem_principal_partial_with(&mut cert, state, amount)?;
+        Self::store_certificate(storage, &cert);
+        Self::release_product_principal(storage, cert.product_type_id, amount);
+        Ok(principal)
+    }
+    
+    /// Storage-independent core of `redeem_principal_partial`
+    pub fn redeem_principal_partial_with(cert: &mut Certificate, state: &mut GlobalState, amount: u64) -> Result<Payout, u32> {
+        let current_time = state.counter;
+        cert.update_status(current_time);
+        
+        if matches!(cert.status, crate::certificate::CertificateStatus::Redeemed) {
+            return Err(ERROR_CERTIFICATE_ALREADY_REDEEMED);
+        }
+        if cert.calculate_available_interest(current_time)? > 0 {
+            return Err(ERROR_UNCLAIMED_INTEREST);
+        }
+        
+        cert.redeem_principal_partial(current_time, amount)?;
+        Self::release_principal_liability(cert, amount, state);
+        if matches!(cert.status, crate::certificate::CertificateStatus::Redeemed) {
+            state.record_certificate_closed();
+        }
+        
+        Ok((amount, cert.principal_currency))
+    }
+    
+    /// Move a certificate to a new owner, rekeying it under `[2, new_owner, new_id]`
+    /// Ids are per owner, so the certificate is renumbered from the recipient's counter on `recipient`
+    pub fn transfer_certificate<S: Storage>(
+        state: &mut GlobalState,
+        storage: &mut S,
+        owner: &[u64; 2],
+        cert_id: u64,
+        new_owner: [u64; 2],
+        recipient: &mut PlayerData
+    ) -> Result<Certificate, u32> {
+        let cert = Self::validate_certificate_ownership_with(|key| storage.get(key), owner, cert_id)?;
+        let moved = Self::transfer_certificate_with(&cert, new_owner, recipient, state)?;
+        Self::delete_certificate(storage, &cert);
+        Self::store_certificate(storage, &moved);
+        // The registry entry under the old owner is dropped by the next pass that reaches it
+        if moved.auto_claim {
+            Self::register_auto_claim(state, storage, &moved);
+        }
+        Ok(moved)
+    }
+    
+    /// Storage-independent core of `transfer_certificate`, the certificate as it is stored under `new_owner`
+    pub fn transfer_certificate_with(
+        cert: &Certificate,
+        new_owner: [u64; 2],
+        recipient: &mut PlayerData,
+        state: &GlobalState
+    ) -> Result<Certificate, u32> {
+        if matches!(cert.status, crate::certificate::CertificateStatus::Redeemed) {
+            return Err(ERROR_CERTIFICATE_ALREADY_REDEEMED);
+        }
+        if new_owner == cert.owner {
+            return Err(ERROR_CERTIFICATE_SAME_OWNER);
+        }
+        
+        let mut moved = cert.clone();
+        moved.id = recipient.take_certificate_id(state.certificate_counter)?;
+        moved.owner = new_owner;
+        Ok(moved)
+    }
+    
+    /// Close a certificate for auto-redeem on claim if it has matured and its interest is drained
+    /// Returns the closed copy, None when there is nothing to redeem; nothing is recorded until
+    /// `commit_auto_redeem`, so the caller can check its principal can be credited first
+    pub fn prepare_auto_redeem(cert: &Certificate, state: &GlobalState) -> Result<Option<Certificate>, u32> {
+        let (current_time, claim_time) = (state.counter, state.interest_claim_cutoff(state.counter));
+        if !cert.is_matured(current_time) || matches!(cert.status, crate::certificate::CertificateStatus::Redeemed) {
+            return Ok(None);
+        }
+        // A capped claim may leave interest behind, keep the certificate until it is drained
+        if cert.calculate_available_interest(claim_time)? > 0 {
+            return Ok(None);
+        }
+        let mut closed = cert.clone();
+        closed.update_status(current_time);
+        closed.redeem_principal(current_time)?;
+        Ok(Some(closed))
+    }
+    
+    /// Record a certificate closed by `prepare_auto_redeem`, the global accounting and then storage
+    pub fn commit_auto_redeem<S: Storage>(state: &mut GlobalState, storage: &mut S, closed: &Certificate) {
+        Self::release_principal_liability(closed, closed.principal, state);
+        state.record_certificate_closed();
+        Self::store_certificate(storage, closed);
+        Self::release_product_principal(storage, closed.product_type_id, closed.principal);
+    }
+    
+    /// Return redeemed principal to its product's issuance capacity
+    fn release_product_principal<S: Storage>(storage: &mut S, product_type_id: u64, amount: u64) {
+        if product_type_id == 0 {
+            return;
+        }
+        if let Some(mut product_type) = ProductTypeManager::get_product_type_with(|key| storage.get(key), product_type_id) {
+            product_type.release_principal(amount);
+            ProductTypeManager::store_product_type(storage, &product_type);
+        }
     }
     
     // Certificate info retrieval functions removed - handled by TypeScript service layer
@@ -250,8 +1460,13 @@ pub struct CertificateInfo {
 
 #[cfg(test)]
 mod tests {
-    use crate::certificate::{Certificate, CertificateStatus};
+    use super::{iterate_keys_with, MAX_ITERATE_KEYS, CertificateManager, ProductTypeManager, ProductTypeOptions};
+    use crate::certificate::{Certificate, CertificateStatus, ProductType};
+    use zkwasm_rest_abi::StorageData;
     use crate::config::TICKS_PER_DAY;
+    use crate::error::{ERROR_OVERFLOW, ERROR_INVALID_COMPOUNDING, ERROR_INVALID_PRINCIPAL_CURRENCY,
+                       ERROR_INVALID_PARAM, ERROR_INVALID_APY};
+    use std::collections::HashMap;
 
     // Extension trait for certificate testing
     trait CertificateTestExt {
@@ -417,6 +1632,86 @@ mod tests {
         assert!(!redeemed_cert.can_redeem_at_time(after_maturity));
     }
 
+    #[test]
+    fn test_interest_payout_converts_points_and_rejects_overflow() {
+        use crate::certificate::{PRINCIPAL_CURRENCY_POINTS, PRINCIPAL_CURRENCY_USDT};
+
+        let mut cert = Certificate::new(1, [1, 2], 0, 10_000, 0, 100, 1000);
+        assert_eq!(CertificateManager::interest_payout(&cert, 40, 3), Ok((40, PRINCIPAL_CURRENCY_USDT)));
+
+        cert.interest_currency = PRINCIPAL_CURRENCY_POINTS;
+        assert_eq!(CertificateManager::interest_payout(&cert, 40, 3), Ok((120, PRINCIPAL_CURRENCY_POINTS)));
+        // Claims compute this before the first change, so an overflowing rate fails cleanly
+        assert_eq!(CertificateManager::interest_payout(&cert, 40, u64::MAX), Err(ERROR_OVERFLOW));
+    }
+
+    #[test]
+    fn test_iterate_keys_respects_start_and_max() {
+        let mut storage: HashMap<[u64; 4], Vec<u64>> = HashMap::new();
+        for id in [1u64, 2, 4, 5, 7] {
+            storage.insert([2, 10, 20, id], vec![id * 100]);
+        }
+        // Entry from another owner must never be visited
+        storage.insert([2, 11, 20, 3], vec![300]);
+        let get = |key: &[u64; 4]| storage.get(key).cloned().unwrap_or_default();
+
+        // Full scan skips the gaps and the foreign key
+        let mut seen = vec![];
+        let next = iterate_keys_with(get, [2, 10, 20], 1, 8, 10, |id, data| seen.push((id, data[0])));
+        assert_eq!(seen, vec![(1, 100), (2, 200), (4, 400), (5, 500), (7, 700)]);
+        assert_eq!(next, None);
+
+        // Start offset and max bound the page and report where to resume
+        let mut seen = vec![];
+        let next = iterate_keys_with(get, [2, 10, 20], 2, 8, 2, |id, _| seen.push(id));
+        assert_eq!(seen, vec![2, 4]);
+        assert_eq!(next, Some(5));
+
+        // max = 0 yields nothing
+        let mut seen = vec![];
+        let next = iterate_keys_with(get, [2, 10, 20], 1, 8, 0, |id, _| seen.push(id));
+        assert!(seen.is_empty());
+        assert_eq!(next, Some(1));
+    }
+
+    #[test]
+    fn test_iterate_keys_max_is_clamped() {
+        let get = |_key: &[u64; 4]| vec![1u64];
+        let mut count = 0;
+        let next = iterate_keys_with(get, [1, 0, 0], 0, u64::MAX, usize::MAX, |_, _| count += 1);
+        assert_eq!(count, MAX_ITERATE_KEYS);
+        assert_eq!(next, Some(MAX_ITERATE_KEYS as u64));
+    }
+
+    #[test]
+    fn test_iterate_keys_bounds_ids_visited_in_sparse_ranges() {
+        let mut storage: HashMap<[u64; 4], Vec<u64>> = HashMap::new();
+        for id in [1u64, 1000] {
+            storage.insert([2, 10, 20, id], vec![id]);
+        }
+        let mut visited = 0;
+        let get = |key: &[u64; 4]| {
+            visited += 1;
+            storage.get(key).cloned().unwrap_or_default()
+        };
+
+        // Far fewer entries than max, yet the scan stops after MAX_ITERATE_KEYS ids
+        let mut seen = vec![];
+        let next = iterate_keys_with(get, [2, 10, 20], 1, 2000, 10, |id, _| seen.push(id));
+        assert_eq!(seen, vec![1]);
+        assert_eq!(visited, MAX_ITERATE_KEYS);
+        assert_eq!(next, Some(1 + MAX_ITERATE_KEYS as u64));
+
+        // Resuming walks the gap page by page until the next entry
+        let mut seen = vec![];
+        let mut next = next;
+        while let Some(start) = next {
+            let get = |key: &[u64; 4]| storage.get(key).cloned().unwrap_or_default();
+            next = iterate_keys_with(get, [2, 10, 20], start, 2000, 10, |id, _| seen.push(id));
+        }
+        assert_eq!(seen, vec![1000]);
+    }
+
     // Helper functions for creating test certificates
     fn create_test_certificate() -> Certificate {
         Certificate::new(
@@ -458,5 +1753,104 @@ mod tests {
     fn calculate_certificate_maturity(purchase_time: u64, duration_ticks: u64) -> u64 {
         purchase_time + duration_ticks
     }
-}
 
+    #[test]
+    fn test_list_active_products_uses_display_order() {
+        let mut storage: HashMap<[u64; 4], Vec<u64>> = HashMap::new();
+        // (id, display_order, is_active)
+        for (id, order, active) in [(1, 20, true), (2, 10, true), (3, 10, true), (4, 0, false), (5, 5, true)] {
+            let mut product = ProductType::new(id, 30 * TICKS_PER_DAY, 1200, 100);
+            product.display_order = order;
+            product.is_active = active;
+            let mut data = vec![];
+            product.to_data(&mut data);
+            storage.insert([1, 0, 0, id], data);
+        }
+
+        let page = ProductTypeManager::list_active_products_with(
+            |key| storage.get(key).cloned().unwrap_or_default(),
+            0,
+            6,
+            MAX_ITERATE_KEYS,
+        );
+        assert!(!page.has_more);
+        let ids: Vec<u64> = page.items.iter().map(|p| p.id).collect();
+        // Sorted by display_order, ties broken by id, inactive product 4 skipped
+        assert_eq!(ids, vec![5, 2, 3, 1]);
+    }
+
+    #[test]
+    fn test_product_type_display_order_defaults_for_legacy_data() {
+        // Records written before display_order existed have 5 words
+        let mut legacy = [7, 30 * TICKS_PER_DAY, 1200, 100, 1];
+        let product = ProductType::from_data(&mut legacy.iter_mut());
+        assert_eq!(product.display_order, 0);
+        assert!(product.is_active);
+    }
+
+    #[test]
+    fn test_product_type_options_from_params() {
+        assert_eq!(ProductTypeOptions::from_params(&[]).unwrap(), ProductTypeOptions::default());
+        let options = ProductTypeOptions::from_params(&[3, 1, 1]).unwrap();
+        assert_eq!(options.display_order, Some(3));
+        assert_eq!(options.compounding, Some(1));
+        assert_eq!(options.principal_currency, Some(1));
+        assert!(options.validate().is_ok());
+
+        assert_eq!(ProductTypeOptions::from_params(&[0, 256]), Err(ERROR_OVERFLOW));
+        assert_eq!(ProductTypeOptions::from_params(&[0, 0, 2]).unwrap().validate(), Err(ERROR_INVALID_PRINCIPAL_CURRENCY));
+        assert_eq!(ProductTypeOptions::from_params(&[0, 3]).unwrap().validate(), Err(ERROR_INVALID_COMPOUNDING));
+    }
+
+    #[test]
+    fn test_product_type_options_apy_steps() {
+        let year = 365 * TICKS_PER_DAY;
+        let apy_steps = ProductTypeOptions::apy_steps_from_params(&[year, 1200, 2 * year, 1500]).unwrap();
+        assert_eq!(apy_steps, vec![(year, 1200), (2 * year, 1500)]);
+        let options = ProductTypeOptions { apy_steps: Some(apy_steps), ..Default::default() };
+        assert!(options.validate().is_ok());
+        // Steps are no longer accepted after the scalar options
+        assert_eq!(ProductTypeOptions::from_params(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap().apy_steps, None);
+        assert_eq!(ProductTypeOptions::from_params(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, year, 1200]), Err(ERROR_INVALID_PARAM));
+
+        let mut product = ProductType::new(1, 3 * year, 800, 100);
+        options.apply(&mut product);
+        let mut data = vec![];
+        product.to_data(&mut data);
+        assert_eq!(ProductType::from_data(&mut data.iter_mut()).apy_steps, vec![(year, 1200), (2 * year, 1500)]);
+
+        // Unpaired, unordered, zero-threshold or out-of-range steps are rejected
+        assert_eq!(ProductTypeOptions::apy_steps_from_params(&[year]), Err(ERROR_INVALID_PARAM));
+        let invalid = [
+            vec![2 * year, 1200, year, 1500],
+            vec![0, 1200],
+            vec![year, crate::certificate::MAX_APY_BASIS_POINTS + 1],
+            vec![1, 1, 2, 2, 3, 3, 4, 4, 5, 5],
+        ];
+        for steps in invalid {
+            let apy_steps = ProductTypeOptions::apy_steps_from_params(&steps).unwrap();
+            let options = ProductTypeOptions { apy_steps: Some(apy_steps), ..Default::default() };
+            assert_eq!(options.validate(), Err(ERROR_INVALID_APY));
+        }
+    }
+
+    #[test]
+    fn test_large_catalog_returns_bounded_pages() {
+        let mut storage: HashMap<[u64; 4], Vec<u64>> = HashMap::new();
+        for id in 1..=100 {
+            let mut data = vec![];
+            ProductType::new(id, 30 * TICKS_PER_DAY, 1200, 100).to_data(&mut data);
+            storage.insert([1, 0, 0, id], data);
+        }
+        let get = |key: &[u64; 4]| storage.get(key).cloned().unwrap_or_default();
+
+        let first = ProductTypeManager::list_active_products_with(get, 0, 101, 64);
+        assert_eq!(first.items.len(), 64);
+        assert!(first.has_more);
+        assert_eq!(first.next, 65);
+
+        let rest = ProductTypeManager::list_active_products_with(get, first.next, 101, 64);
+        assert_eq!(rest.items.len(), 36);
+        assert!(!rest.has_more);
+    }
+}
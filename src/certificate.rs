@@ -1,5 +1,5 @@
-use crate::math_safe::{safe_add, safe_mul, safe_sub, safe_div};
-use crate::config::SECONDS_PER_TICK;
+use crate::math_safe::{safe_add, safe_mul, safe_sub, safe_div, safe_mul_div_u128, safe_mul_div_u128_nearest, safe_mul_fixed, safe_pow_fixed, to_u8_checked};
+use crate::config::{SECONDS_PER_TICK, ticks_per_day};
 use zkwasm_rest_abi::StorageData;
 use serde::{Deserialize, Serialize};
 
@@ -12,7 +12,126 @@ pub const BASIS_POINTS_DIVISOR: u64 = 10000; // For APY calculation (10000 = 100
 pub const MAX_CERTIFICATE_AMOUNT: u64 = 1_000_000_000; // 1B USDT max
 pub const MAX_APY_BASIS_POINTS: u64 = 50_000; // 500% maximum APY
 pub const MIN_CERTIFICATE_AMOUNT: u64 = 10; // 10 USDT minimum
+pub const MIN_INTEREST_CLAIM: u64 = 2; // Smallest direct claim, each claim costs a certificate write and events
 pub const MAX_CERTIFICATE_DURATION_TICKS: u64 = 3650 * 17280; // 10 years maximum duration (3650 days × 17280 ticks/day)
+pub const MAX_APY_STEPS: usize = 4; // Steps a product's APY schedule may add on top of the base APY
+pub const MAX_ACCRUAL_PAUSES: usize = 8; // Accrual pause windows a product may record
+pub const OPEN_ACCRUAL_PAUSE: u64 = u64::MAX; // End of the pause window of a product still frozen
+pub const NO_EARLY_WITHDRAWAL: u64 = BASIS_POINTS_DIVISOR; // Early withdrawal penalty forfeiting all interest disables the early exit
+
+// Interest compounding modes for ProductType::compounding
+pub const COMPOUNDING_SIMPLE: u8 = 0;
+pub const COMPOUNDING_DAILY: u8 = 1;
+pub const COMPOUNDING_MONTHLY: u8 = 2;
+pub const COMPOUNDING_MONTH_DAYS: u64 = 30; // A compounding month is 30 days
+pub const COMPOUND_GROWTH_SCALE: u128 = 1_000_000_000_000_000_000; // Fixed-point 1.0 for compounding growth factors
+
+// Currency a certificate's principal (and its interest) is denominated in
+pub const PRINCIPAL_CURRENCY_USDT: u8 = 0;   // Idle funds
+pub const PRINCIPAL_CURRENCY_POINTS: u8 = 1; // PlayerData::points
+
+/// How the simple interest formula rounds its full-precision (u128) result to whole units
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoundingMode {
+    Floor,   // Truncate, the protocol keeps the fraction
+    Nearest, // Half the divisor is added first, halves round up
+}
+
+impl RoundingMode {
+    pub const fn name(self) -> &'static str {
+        match self {
+            RoundingMode::Floor => "floor",
+            RoundingMode::Nearest => "nearest",
+        }
+    }
+
+    /// `a * b / c` rounded in this mode
+    pub fn mul_div(self, a: u64, b: u64, c: u64) -> Result<u64, u32> {
+        match self {
+            RoundingMode::Floor => safe_mul_div_u128(a, b, c),
+            RoundingMode::Nearest => safe_mul_div_u128_nearest(a, b, c),
+        }
+    }
+}
+
+// Rounding applied by calculate_total_simple_interest, once per APY segment
+pub const INTEREST_ROUNDING: RoundingMode = RoundingMode::Floor;
+pub const INTEREST_ROUNDING_MODE: &str = INTEREST_ROUNDING.name();
+
+// Interest withheld by that flooring is tracked in millionths of a unit
+pub const ROUNDING_SURPLUS_SCALE: u64 = 1_000_000;
+
+thread_local! {
+    // Last calculate_total_interest result as (Certificate::interest_cache_key, total)
+    // The key carries the owner, since ids are per owner, and every rate term: partial and early
+    // redemption change principal and maturity in place
+    static INTEREST_CACHE: std::cell::Cell<Option<([u64; 13], u64)>> = const { std::cell::Cell::new(None) };
+}
+
+/// Drop the memoized interest so nothing carries over between transactions
+pub fn clear_interest_cache() {
+    INTEREST_CACHE.with(|cache| cache.set(None));
+}
+
+/// Constants used by the on-chain interest formula, exposed so clients can reproduce it
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct InterestParams {
+    pub seconds_per_tick: u64,
+    pub ticks_per_day: u64,
+    pub seconds_per_year: u64,
+    pub basis_points_divisor: u64,
+    pub rounding_mode: &'static str,
+}
+
+/// `seconds_per_tick` is the current global value; each certificate reports the one it was bought under
+pub fn interest_params(seconds_per_tick: u64) -> InterestParams {
+    InterestParams {
+        seconds_per_tick,
+        ticks_per_day: ticks_per_day(seconds_per_tick),
+        seconds_per_year: SECONDS_PER_YEAR,
+        basis_points_divisor: BASIS_POINTS_DIVISOR,
+        rounding_mode: INTEREST_ROUNDING_MODE,
+    }
+}
+
+/// Simple interest earned from purchase_time to current_time, independent of storage
+/// This is the on-chain formula; callers clamp current_time to maturity themselves
+pub fn simulate_interest(
+    principal: u64,
+    apy: u64,
+    purchase_time: u64,
+    current_time: u64,
+    seconds_per_tick: u64,
+    seconds_per_year: u64
+) -> Result<u64, u32> {
+    simulate_interest_rounded(principal, apy, purchase_time, current_time, seconds_per_tick, seconds_per_year, INTEREST_ROUNDING)
+}
+
+/// `simulate_interest` under an explicit rounding mode
+///
+/// The total is recomputed from purchase on every claim and both modes are non-decreasing in
+/// time, so claimed + available == total holds either way
+pub fn simulate_interest_rounded(
+    principal: u64,
+    apy: u64,
+    purchase_time: u64,
+    current_time: u64,
+    seconds_per_tick: u64,
+    seconds_per_year: u64,
+    rounding: RoundingMode
+) -> Result<u64, u32> {
+    if current_time <= purchase_time {
+        return Ok(0);
+    }
+    
+    let total_time = safe_sub(current_time, purchase_time)?;
+    let total_time_seconds = safe_mul(total_time, seconds_per_tick)?;
+    
+    // Simple interest calculation: (principal * APY * time_seconds) / (BASIS_POINTS * seconds_per_year)
+    // The numerator is multiplied out in u128, so only the final result is rounded
+    let denominator = safe_mul(BASIS_POINTS_DIVISOR, seconds_per_year)?;
+    rounding.mul_div(safe_mul(principal, apy)?, total_time_seconds, denominator)
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum CertificateStatus {
@@ -38,6 +157,16 @@ impl CertificateStatus {
             _ => CertificateStatus::Active, // Default fallback
         }
     }
+
+    /// Strict variant of `from_u64` that rejects unknown or truncated status values
+    pub fn try_from_u64(value: u64) -> Result<Self, u32> {
+        match value {
+            0 => Ok(CertificateStatus::Active),
+            1 => Ok(CertificateStatus::Matured),
+            2 => Ok(CertificateStatus::Redeemed),
+            _ => Err(crate::error::ERROR_INVALID_CERTIFICATE_STATUS),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -47,23 +176,74 @@ pub struct ProductType {
     pub apy: u64,                   // Annual percentage yield in basis points (1000 = 10%)
     pub min_amount: u64,            // Minimum investment amount in USDT
     pub is_active: bool,            // Whether open for purchase
+    pub display_order: u64,         // Front-end display hint, lower first (no effect on math)
+    pub compounding: u8,            // Interest mode: 0 = simple, 1 = daily, 2 = monthly
+    pub principal_currency: u8,     // 0 = USDT idle funds, 1 = points
+    pub whitelist_only: bool,       // Only whitelisted users may purchase
+    pub max_total_principal: u64,   // Cap on outstanding principal sold, 0 = unlimited
+    pub current_total_principal: u64, // Outstanding (unredeemed) principal sold
+    pub apy_steps: Vec<(u64, u64)>, // (ticks after purchase, apy) steps replacing `apy` from that point on
+    pub interest_currency: u8,      // Currency interest is paid in, PRINCIPAL_CURRENCY_* values
+    pub early_withdrawal_penalty_bps: u64, // Share of accrued interest forfeited by an early exit, 10000 = no early exit
+    pub max_certificates: u64,      // Cap on certificates ever sold, 0 = unlimited
+    pub certificate_count: u64,     // Certificates sold so far, redemptions do not free a slot
+    pub grace_ticks: u64,           // Ticks past maturity that interest keeps accruing before redemption opens
+    pub accrual_pauses: Vec<(u64, u64)>, // (from, until) windows its certificates earned nothing, the last may be open
+    pub min_claim_interval_ticks: u64, // Ticks a certificate must wait between interest claims, 0 = no limit
 }
 
-impl StorageData for ProductType {
-    fn from_data(u64data: &mut std::slice::IterMut<u64>) -> Self {
+impl ProductType {
+    /// Checked decode of a stored product type, rejecting a narrow field out of range
+    pub fn try_from_data(u64data: &mut std::slice::IterMut<u64>) -> Result<Self, u32> {
         let id = *u64data.next().unwrap();
         let duration_ticks = *u64data.next().unwrap();
         let apy = *u64data.next().unwrap();
         let min_amount = *u64data.next().unwrap();
         let is_active = *u64data.next().unwrap() != 0;
+        let display_order = u64data.next().copied().unwrap_or(0);
+        let compounding = to_u8_checked(u64data.next().copied().unwrap_or(0))?;
+        let principal_currency = to_u8_checked(u64data.next().copied().unwrap_or(0))?;
+        let whitelist_only = u64data.next().copied().unwrap_or(0) != 0;
+        let max_total_principal = u64data.next().copied().unwrap_or(0);
+        let current_total_principal = u64data.next().copied().unwrap_or(0);
+        let apy_steps = read_tick_pairs(u64data);
+        // Products stored before interest_currency existed pay interest in their principal currency
+        let interest_currency = u64data.next().map_or(Ok(principal_currency), |v| to_u8_checked(*v))?;
+        let early_withdrawal_penalty_bps = u64data.next().copied().unwrap_or(NO_EARLY_WITHDRAWAL);
+        let max_certificates = u64data.next().copied().unwrap_or(0);
+        let certificate_count = u64data.next().copied().unwrap_or(0);
+        let grace_ticks = u64data.next().copied().unwrap_or(0);
+        let accrual_pauses = read_tick_pairs(u64data);
+        let min_claim_interval_ticks = u64data.next().copied().unwrap_or(0);
         
-        ProductType {
+        Ok(ProductType {
             id,
             duration_ticks,
             apy,
             min_amount,
             is_active,
-        }
+            display_order,
+            compounding,
+            principal_currency,
+            whitelist_only,
+            max_total_principal,
+            current_total_principal,
+            apy_steps,
+            interest_currency,
+            early_withdrawal_penalty_bps,
+            max_certificates,
+            certificate_count,
+            grace_ticks,
+            accrual_pauses,
+            min_claim_interval_ticks,
+        })
+    }
+}
+
+impl StorageData for ProductType {
+    /// Only this contract writes product types, storage reads go through `try_from_data`
+    fn from_data(u64data: &mut std::slice::IterMut<u64>) -> Self {
+        Self::try_from_data(u64data).expect("malformed stored product type")
     }
     
     fn to_data(&self, data: &mut Vec<u64>) {
@@ -72,6 +252,34 @@ impl StorageData for ProductType {
         data.push(self.apy);
         data.push(self.min_amount);
         data.push(if self.is_active { 1 } else { 0 });
+        data.push(self.display_order);
+        data.push(self.compounding as u64);
+        data.push(self.principal_currency as u64);
+        data.push(if self.whitelist_only { 1 } else { 0 });
+        data.push(self.max_total_principal);
+        data.push(self.current_total_principal);
+        write_tick_pairs(&self.apy_steps, data);
+        data.push(self.interest_currency as u64);
+        data.push(self.early_withdrawal_penalty_bps);
+        data.push(self.max_certificates);
+        data.push(self.certificate_count);
+        data.push(self.grace_ticks);
+        write_tick_pairs(&self.accrual_pauses, data);
+        data.push(self.min_claim_interval_ticks);
+    }
+}
+
+/// APY steps and accrual pauses are stored as a count followed by their pairs; absent means none
+fn read_tick_pairs(u64data: &mut std::slice::IterMut<u64>) -> Vec<(u64, u64)> {
+    let count = u64data.next().copied().unwrap_or(0);
+    (0..count).map(|_| (*u64data.next().unwrap(), *u64data.next().unwrap())).collect()
+}
+
+fn write_tick_pairs(pairs: &[(u64, u64)], data: &mut Vec<u64>) {
+    data.push(pairs.len() as u64);
+    for (first, second) in pairs {
+        data.push(*first);
+        data.push(*second);
     }
 }
 
@@ -83,6 +291,20 @@ impl ProductType {
             apy,
             min_amount,
             is_active: true,
+            display_order: 0,
+            compounding: COMPOUNDING_SIMPLE,
+            principal_currency: PRINCIPAL_CURRENCY_USDT,
+            whitelist_only: false,
+            max_total_principal: 0,
+            current_total_principal: 0,
+            apy_steps: vec![],
+            interest_currency: PRINCIPAL_CURRENCY_USDT,
+            early_withdrawal_penalty_bps: NO_EARLY_WITHDRAWAL,
+            max_certificates: 0,
+            certificate_count: 0,
+            grace_ticks: 0,
+            accrual_pauses: vec![],
+            min_claim_interval_ticks: 0,
         }
     }
     
@@ -90,6 +312,49 @@ impl ProductType {
         // Duration is already in ticks, directly add to purchase_time
         safe_add(purchase_time, self.duration_ticks)
     }
+
+    /// Return redeemed principal to the issuance capacity
+    /// Saturates so certificates sold before the counter existed cannot underflow it
+    pub fn release_principal(&mut self, amount: u64) {
+        self.current_total_principal = self.current_total_principal.saturating_sub(amount);
+    }
+
+    /// Tick since which the product's certificates have stopped accruing, if they have
+    pub fn frozen_since(&self) -> Option<u64> {
+        self.accrual_pauses.last().filter(|(_, until)| *until == OPEN_ACCRUAL_PAUSE).map(|(from, _)| *from)
+    }
+    
+    /// Stop its certificates accruing from `current_time` until resume_accrual, a no-op while already frozen
+    pub fn pause_accrual(&mut self, current_time: u64) -> Result<(), u32> {
+        if self.frozen_since().is_some() {
+            return Ok(());
+        }
+        if self.accrual_pauses.len() >= MAX_ACCRUAL_PAUSES {
+            return Err(crate::error::ERROR_ACCRUAL_PAUSE_LIMIT);
+        }
+        self.accrual_pauses.push((current_time, OPEN_ACCRUAL_PAUSE));
+        Ok(())
+    }
+    
+    /// Close an open pause window at `current_time`; one that never lasted a tick is dropped
+    pub fn resume_accrual(&mut self, current_time: u64) {
+        if let Some(from) = self.frozen_since() {
+            self.accrual_pauses.pop();
+            if current_time > from {
+                self.accrual_pauses.push((from, current_time));
+            }
+        }
+    }
+
+    /// USDT principal may pay interest in either currency; points principal only pays points
+    pub fn validate_currencies(&self) -> Result<(), u32> {
+        if self.interest_currency > PRINCIPAL_CURRENCY_POINTS
+            || (self.principal_currency == PRINCIPAL_CURRENCY_POINTS && self.interest_currency != PRINCIPAL_CURRENCY_POINTS)
+        {
+            return Err(crate::error::ERROR_INVALID_INTEREST_CURRENCY);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -103,10 +368,23 @@ pub struct Certificate {
     pub locked_apy: u64,           // Locked APY at purchase (basis points)
     pub total_interest_claimed: u64, // Total interest claimed so far
     pub status: CertificateStatus,  // Certificate status
+    pub compounding: u8,            // Compounding mode locked from the product at purchase
+    pub principal_currency: u8,     // Principal currency locked from the product at purchase
+    pub rounding_drift: u64,        // Floored-away interest at the last claim (ROUNDING_SURPLUS_SCALE units)
+    pub apy_steps: Vec<(u64, u64)>, // APY schedule locked from the product at purchase, locked_apy is the base step
+    pub interest_currency: u8,      // Interest payout currency locked from the product at purchase
+    pub early_withdrawal_penalty_bps: u64, // Early exit penalty locked from the product at purchase
+    pub grace_ticks: u64,           // Grace window after maturity_time locked from the product at purchase
+    pub auto_claim: bool,           // Opted in at purchase to have interest claimed by the tick pass
+    pub last_claim_counter: u64,    // Counter of the last interest claim (0 = never claimed)
+    pub seconds_per_tick: u64,      // Tick length in force at purchase, used for all of its interest
+    #[serde(skip)]
+    pub accrual_pauses: Vec<(u64, u64)>, // The product's pause windows, attached when loaded and never stored
 }
 
-impl StorageData for Certificate {
-    fn from_data(u64data: &mut std::slice::IterMut<u64>) -> Self {
+impl Certificate {
+    /// Checked decode of a stored certificate, rejecting an unknown status or a narrow field out of range
+    pub fn try_from_data(u64data: &mut std::slice::IterMut<u64>) -> Result<Self, u32> {
         let id = *u64data.next().unwrap();
         let owner = [*u64data.next().unwrap(), *u64data.next().unwrap()];
         let product_type_id = *u64data.next().unwrap();
@@ -115,9 +393,19 @@ impl StorageData for Certificate {
         let maturity_time = *u64data.next().unwrap();
         let locked_apy = *u64data.next().unwrap();
         let total_interest_claimed = *u64data.next().unwrap();
-        let status = CertificateStatus::from_u64(*u64data.next().unwrap());
+        let status = CertificateStatus::try_from_u64(*u64data.next().unwrap())?;
+        let compounding = to_u8_checked(u64data.next().copied().unwrap_or(0))?;
+        let principal_currency = to_u8_checked(u64data.next().copied().unwrap_or(0))?;
+        let rounding_drift = u64data.next().copied().unwrap_or(0);
+        let apy_steps = read_tick_pairs(u64data);
+        let interest_currency = u64data.next().map_or(Ok(principal_currency), |v| to_u8_checked(*v))?;
+        let early_withdrawal_penalty_bps = u64data.next().copied().unwrap_or(NO_EARLY_WITHDRAWAL);
+        let grace_ticks = u64data.next().copied().unwrap_or(0);
+        let auto_claim = u64data.next().copied().unwrap_or(0) != 0;
+        let last_claim_counter = u64data.next().copied().unwrap_or(0);
+        let seconds_per_tick = u64data.next().copied().unwrap_or(SECONDS_PER_TICK);
         
-        Certificate {
+        Ok(Certificate {
             id,
             owner,
             product_type_id,
@@ -127,7 +415,25 @@ impl StorageData for Certificate {
             locked_apy,
             total_interest_claimed,
             status,
-        }
+            compounding,
+            principal_currency,
+            rounding_drift,
+            apy_steps,
+            interest_currency,
+            early_withdrawal_penalty_bps,
+            grace_ticks,
+            auto_claim,
+            last_claim_counter,
+            seconds_per_tick,
+            accrual_pauses: vec![],
+        })
+    }
+}
+
+impl StorageData for Certificate {
+    /// Only this contract writes certificates, storage reads go through `try_from_data`
+    fn from_data(u64data: &mut std::slice::IterMut<u64>) -> Self {
+        Self::try_from_data(u64data).expect("malformed stored certificate")
     }
     
     fn to_data(&self, data: &mut Vec<u64>) {
@@ -141,6 +447,16 @@ impl StorageData for Certificate {
         data.push(self.locked_apy);
         data.push(self.total_interest_claimed);
         data.push(self.status.to_u64());
+        data.push(self.compounding as u64);
+        data.push(self.principal_currency as u64);
+        data.push(self.rounding_drift);
+        write_tick_pairs(&self.apy_steps, data);
+        data.push(self.interest_currency as u64);
+        data.push(self.early_withdrawal_penalty_bps);
+        data.push(self.grace_ticks);
+        data.push(self.auto_claim as u64);
+        data.push(self.last_claim_counter);
+        data.push(self.seconds_per_tick);
     }
 }
 
@@ -164,6 +480,17 @@ impl Certificate {
             locked_apy,
             total_interest_claimed: 0, // Start with no interest claimed
             status: CertificateStatus::Active,
+            compounding: COMPOUNDING_SIMPLE,
+            principal_currency: PRINCIPAL_CURRENCY_USDT,
+            rounding_drift: 0,
+            apy_steps: vec![],
+            interest_currency: PRINCIPAL_CURRENCY_USDT,
+            early_withdrawal_penalty_bps: NO_EARLY_WITHDRAWAL,
+            grace_ticks: 0,
+            auto_claim: false,
+            last_claim_counter: 0,
+            seconds_per_tick: SECONDS_PER_TICK,
+            accrual_pauses: vec![],
         }
     }
     
@@ -171,7 +498,7 @@ impl Certificate {
     /// Returns total earned interest minus what has already been claimed
     pub fn calculate_available_interest(&self, current_time: u64) -> Result<u64, u32> {
         // Calculate total interest from purchase time to current time
-        let total_earned = self.calculate_total_simple_interest(current_time)?;
+        let total_earned = self.calculate_total_interest(current_time)?;
         
         // Return the difference between total earned and already claimed
         if total_earned >= self.total_interest_claimed {
@@ -181,29 +508,193 @@ impl Certificate {
         }
     }
     
-    /// Calculate total simple interest from purchase to current time
-    pub fn calculate_total_simple_interest(&self, current_time: u64) -> Result<u64, u32> {
-        if current_time <= self.purchase_time {
+    /// Total interest earned so far under the certificate's compounding mode
+    /// Memoized per (certificate, current_time) so a claim's repeated reads cost one computation
+    pub fn calculate_total_interest(&self, current_time: u64) -> Result<u64, u32> {
+        let key = self.interest_cache_key(current_time);
+        if let Some((cached_key, total)) = INTEREST_CACHE.with(|cache| cache.get()) {
+            if cached_key == key {
+                return Ok(total);
+            }
+        }
+        let total = match self.compounding {
+            COMPOUNDING_SIMPLE => self.calculate_total_simple_interest(current_time),
+            _ => self.calculate_total_compound_interest(current_time),
+        }?;
+        INTEREST_CACHE.with(|cache| cache.set(Some((key, total))));
+        Ok(total)
+    }
+
+    /// Everything calculate_total_interest depends on, with the APY schedule folded into one word
+    fn interest_cache_key(&self, current_time: u64) -> [u64; 13] {
+        let steps = self.apy_steps.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &(threshold, apy)| {
+            ((hash ^ threshold).wrapping_mul(0x0100_0000_01b3) ^ apy).wrapping_mul(0x0100_0000_01b3)
+        });
+        [
+            self.id, self.owner[0], self.owner[1], current_time, self.principal, self.purchase_time, self.maturity_time,
+            self.grace_ticks, self.locked_apy, self.compounding as u64, self.paused_ticks(self.purchase_time, current_time),
+            self.seconds_per_tick, steps,
+        ]
+    }
+    
+    /// Calculate total compound interest over whole periods elapsed since purchase, paused ticks not counted
+    /// Each period grows the balance by the period's share of the APY in force at its start; a partial
+    /// period earns nothing yet. Periods are days at the certificate's locked tick length
+    ///
+    /// Growth is a fixed-point product raised per APY step by squaring, floored once on the balance
+    pub fn calculate_total_compound_interest(&self, current_time: u64) -> Result<u64, u32> {
+        let day_ticks = ticks_per_day(self.seconds_per_tick);
+        let period_ticks = match self.compounding {
+            COMPOUNDING_DAILY => day_ticks,
+            COMPOUNDING_MONTHLY => safe_mul(COMPOUNDING_MONTH_DAYS, day_ticks)?,
+            _ => return Err(crate::error::ERROR_INVALID_COMPOUNDING),
+        };
+        let effective_time = current_time.min(self.accrual_end());
+        if effective_time <= self.purchase_time {
             return Ok(0);
         }
         
-        let total_time = safe_sub(current_time, self.purchase_time)?;
-        let total_time_seconds = safe_mul(total_time, SECONDS_PER_TICK)?;
+        let periods = safe_div(self.accruing_ticks(self.purchase_time, effective_time)?, period_ticks)?;
+        let period_seconds = safe_mul(period_ticks, self.seconds_per_tick)?;
         
-        // Simple interest calculation: (principal * APY * time_seconds) / (BASIS_POINTS * seconds_per_year)
-        // Avoid overflow by rearranging: (principal * APY) / BASIS_POINTS * time_seconds / seconds_per_year
-        // This separates percentage calculation from time scaling
+        // Growth compounds quickly, every product is overflow checked
+        let mut growth = COMPOUND_GROWTH_SCALE;
+        let mut period = 0;
+        while period < periods {
+            let offset = safe_mul(period, period_ticks)?;
+            let apy = self.apy_at(offset);
+            // The first period starting at or after the next step threshold switches APY
+            let run_end = self.apy_steps
+                .iter()
+                .find(|(threshold, _)| *threshold > offset)
+                .map_or(periods, |(threshold, _)| threshold.div_ceil(period_ticks).min(periods));
+            let rate = COMPOUND_GROWTH_SCALE
+                .checked_mul(safe_mul(apy, period_seconds)? as u128)
+                .ok_or(crate::error::ERROR_OVERFLOW)?
+                / (BASIS_POINTS_DIVISOR as u128 * SECONDS_PER_YEAR as u128);
+            let factor = COMPOUND_GROWTH_SCALE + rate;
+            growth = safe_mul_fixed(growth, safe_pow_fixed(factor, run_end - period, COMPOUND_GROWTH_SCALE)?, COMPOUND_GROWTH_SCALE)?;
+            period = run_end;
+        }
+        let balance = safe_mul_fixed(self.principal as u128, growth, COMPOUND_GROWTH_SCALE)?;
+        safe_sub(u64::try_from(balance).map_err(|_| crate::error::ERROR_OVERFLOW)?, self.principal)
+    }
+
+    /// Calculate total simple interest from purchase to current time
+    /// Accrual stops at maturity (after any grace window): the APY is priced for the lock duration only
+    /// With an APY schedule each step's segment is computed (and rounded) on its own and summed,
+    /// over the ticks of the segment the product was not paused for
+    pub fn calculate_total_simple_interest(&self, current_time: u64) -> Result<u64, u32> {
+        let mut total = 0;
+        for (apy, start, end) in self.apy_segments(current_time.min(self.accrual_end()))? {
+            let ticks = self.accruing_ticks(start, end)?;
+            let segment = simulate_interest(self.principal, apy, 0, ticks, self.seconds_per_tick, SECONDS_PER_YEAR)?;
+            total = safe_add(total, segment)?;
+        }
+        Ok(total)
+    }
+
+    /// Whether any step of the locked APY schedule pays interest
+    pub fn is_interest_bearing(&self) -> bool {
+        self.locked_apy > 0 || self.apy_steps.iter().any(|(_, apy)| *apy > 0)
+    }
+
+    /// APY in force `offset` ticks after purchase
+    pub fn apy_at(&self, offset: u64) -> u64 {
+        self.apy_steps
+            .iter()
+            .take_while(|(threshold, _)| *threshold <= offset)
+            .last()
+            .map_or(self.locked_apy, |(_, apy)| *apy)
+    }
+
+    /// Ticks of [start, end) that fall inside the product's accrual pauses
+    fn paused_ticks(&self, start: u64, end: u64) -> u64 {
+        self.accrual_pauses
+            .iter()
+            .map(|(from, until)| end.min(*until).saturating_sub(start.max(*from)))
+            .sum()
+    }
+    
+    /// Ticks of [start, end) that earn interest
+    fn accruing_ticks(&self, start: u64, end: u64) -> Result<u64, u32> {
+        safe_sub(end.saturating_sub(start), self.paused_ticks(start, end))
+    }
+    
+    /// (apy, start, end) segments covering purchase_time to effective_time
+    fn apy_segments(&self, effective_time: u64) -> Result<Vec<(u64, u64, u64)>, u32> {
+        let mut segments = vec![];
+        let (mut apy, mut start) = (self.locked_apy, self.purchase_time);
+        for (threshold, step_apy) in &self.apy_steps {
+            let boundary = safe_add(self.purchase_time, *threshold)?;
+            if boundary >= effective_time {
+                break;
+            }
+            segments.push((apy, start, boundary));
+            (apy, start) = (*step_apy, boundary);
+        }
+        segments.push((apy, start, effective_time));
+        Ok(segments)
+    }
+    
+    /// Interest the rounded simple formula withholds, in ROUNDING_SURPLUS_SCALE units
+    /// The exact u128 interest minus what calculate_total_simple_interest pays; compounding certificates report 0,
+    /// and so does a certificate that RoundingMode::Nearest has paid slightly more than exact
+    pub fn calculate_rounding_drift(&self, current_time: u64) -> Result<u64, u32> {
+        use crate::error::ERROR_OVERFLOW;
         
-        // First calculate the annual interest rate: (principal * APY) / BASIS_POINTS
-        let annual_interest = safe_div(safe_mul(self.principal, self.locked_apy)?, BASIS_POINTS_DIVISOR)?;
+        if self.compounding != COMPOUNDING_SIMPLE {
+            return Ok(0);
+        }
+        let effective_time = current_time.min(self.accrual_end());
+        if effective_time <= self.purchase_time {
+            return Ok(0);
+        }
         
-        // Then scale by time: annual_interest * time_seconds / seconds_per_year
-        safe_div(safe_mul(annual_interest, total_time_seconds)?, SECONDS_PER_YEAR)
+        let mut exact_numerator: u128 = 0;
+        for (apy, start, end) in self.apy_segments(effective_time)? {
+            let segment_seconds = safe_mul(self.accruing_ticks(start, end)?, self.seconds_per_tick)?;
+            exact_numerator = (self.principal as u128)
+                .checked_mul(apy as u128)
+                .and_then(|v| v.checked_mul(segment_seconds as u128))
+                .and_then(|v| v.checked_add(exact_numerator))
+                .ok_or(ERROR_OVERFLOW)?;
+        }
+        let exact_scaled = exact_numerator
+            .checked_mul(ROUNDING_SURPLUS_SCALE as u128)
+            .ok_or(ERROR_OVERFLOW)?
+            / (BASIS_POINTS_DIVISOR as u128 * SECONDS_PER_YEAR as u128);
+        let paid_scaled = self.calculate_total_interest(current_time)? as u128 * ROUNDING_SURPLUS_SCALE as u128;
+        
+        let drift = match INTEREST_ROUNDING {
+            RoundingMode::Floor => exact_scaled.checked_sub(paid_scaled).ok_or(crate::error::ERROR_UNDERFLOW)?,
+            RoundingMode::Nearest => exact_scaled.saturating_sub(paid_scaled),
+        };
+        u64::try_from(drift).map_err(|_| ERROR_OVERFLOW)
+    }
+    
+    /// Term length in whole days under `ticks_per_day`, for display only
+    /// Stored times stay in ticks, so a change to the tick params only moves this figure
+    pub fn duration_days(&self, ticks_per_day: u64) -> u64 {
+        self.maturity_time.saturating_sub(self.purchase_time).checked_div(ticks_per_day).unwrap_or(0)
+    }
+    
+    /// Bring maturity forward to `current_time`, so interest stops accruing and the principal is redeemable
+    /// A grace window still running is closed as well
+    pub fn force_mature(&mut self, current_time: u64) {
+        self.maturity_time = self.accrual_end().min(current_time);
+        self.grace_ticks = 0;
+        self.update_status(current_time);
     }
     
-    /// Check if certificate has matured
+    /// End of interest accrual and start of redemption: maturity_time plus the grace window
+    pub fn accrual_end(&self) -> u64 {
+        self.maturity_time.saturating_add(self.grace_ticks)
+    }
+    
+    /// Check if certificate has matured, including any grace window
     pub fn is_matured(&self, current_time: u64) -> bool {
-        current_time >= self.maturity_time
+        current_time >= self.accrual_end()
     }
     
     /// Update status based on current time and conditions
@@ -216,6 +707,14 @@ impl Certificate {
         }
     }
     
+    /// Reject a claim less than `min_interval` ticks after the previous one; the first claim is never limited
+    pub fn check_claim_interval(&self, min_interval: u64, current_time: u64) -> Result<(), u32> {
+        if min_interval > 0 && self.last_claim_counter > 0 && current_time < self.last_claim_counter.saturating_add(min_interval) {
+            return Err(crate::error::ERROR_CLAIM_TOO_SOON);
+        }
+        Ok(())
+    }
+    
     /// Record interest claim (add claimed amount to total)
     pub fn claim_interest(&mut self, claimed_amount: u64) -> Result<(), u32> {
         self.total_interest_claimed = safe_add(self.total_interest_claimed, claimed_amount)?;
@@ -232,6 +731,34 @@ impl Certificate {
         Ok(())
     }
     
+    /// Close the certificate before maturity, returning the share of `accrued` interest kept
+    /// after the early withdrawal penalty; the forfeited share is never paid
+    pub fn redeem_early(&mut self, accrued: u64) -> Result<u64, u32> {
+        if self.early_withdrawal_penalty_bps >= NO_EARLY_WITHDRAWAL {
+            return Err(crate::error::ERROR_EARLY_WITHDRAWAL_DISABLED);
+        }
+        
+        let kept = safe_mul_div_u128(accrued, NO_EARLY_WITHDRAWAL - self.early_withdrawal_penalty_bps, BASIS_POINTS_DIVISOR)?;
+        self.claim_interest(kept)?;
+        self.status = CertificateStatus::Redeemed;
+        Ok(kept)
+    }
+    
+    /// Return part of a matured certificate's principal, redeemed once none is left
+    pub fn redeem_principal_partial(&mut self, current_time: u64, amount: u64) -> Result<(), u32> {
+        if !self.is_matured(current_time) {
+            return Err(crate::error::ERROR_CERTIFICATE_NOT_MATURED);
+        }
+        if amount == 0 || amount > self.principal {
+            return Err(crate::error::ERROR_INVALID_PRINCIPAL_AMOUNT);
+        }
+        
+        self.principal = safe_sub(self.principal, amount)?;
+        if self.principal == 0 {
+            self.status = CertificateStatus::Redeemed;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -249,6 +776,32 @@ mod tests {
         assert_eq!(CertificateStatus::from_u64(1), CertificateStatus::Matured);
         assert_eq!(CertificateStatus::from_u64(2), CertificateStatus::Redeemed);
     }
+
+    #[test]
+    fn test_certificate_status_try_from_rejects_out_of_range() {
+        use crate::error::ERROR_INVALID_CERTIFICATE_STATUS;
+        assert_eq!(CertificateStatus::try_from_u64(1), Ok(CertificateStatus::Matured));
+        assert_eq!(CertificateStatus::try_from_u64(3), Err(ERROR_INVALID_CERTIFICATE_STATUS));
+        assert_eq!(CertificateStatus::try_from_u64(256), Err(ERROR_INVALID_CERTIFICATE_STATUS));
+        assert_eq!(CertificateStatus::try_from_u64(u64::MAX), Err(ERROR_INVALID_CERTIFICATE_STATUS));
+    }
+
+    #[test]
+    fn test_stored_certificate_with_a_bad_status_or_flag_is_rejected() {
+        use crate::error::{ERROR_INVALID_CERTIFICATE_STATUS, ERROR_OVERFLOW};
+        let mut data = vec![];
+        Certificate::new(1, [100, 200], 1, 100000, 0, 30 * TICKS_PER_DAY, 1200).to_data(&mut data);
+        assert!(Certificate::try_from_data(&mut data.clone().iter_mut()).is_ok());
+
+        // status sits after id, owner, product, principal, the two times, apy and interest claimed
+        let mut bad_status = data.clone();
+        bad_status[9] = 7;
+        assert_eq!(Certificate::try_from_data(&mut bad_status.iter_mut()).err(), Some(ERROR_INVALID_CERTIFICATE_STATUS));
+
+        let mut bad_compounding = data;
+        bad_compounding[10] = 256;
+        assert_eq!(Certificate::try_from_data(&mut bad_compounding.iter_mut()).err(), Some(ERROR_OVERFLOW));
+    }
     
     #[test]
     fn test_product_type_maturity_calculation() {
@@ -278,10 +831,8 @@ mod tests {
         let current_time = 30 * TICKS_PER_DAY; // 30 days in ticks
         let interest_30_days = cert.calculate_available_interest(current_time).unwrap();
         
-        // Due to integer division precision loss in simplified calculation, 
-        // small interest amounts may result in 0. This is acceptable trade-off.
-        // The result should be 0 or close to expected value due to precision loss
-        assert!(interest_30_days == 0 || interest_30_days > 0);
+        // 100,000 * 12% * 30/365 = 986.30, floored once
+        assert_eq!(interest_30_days, 986);
     }
     
     #[test]
@@ -304,10 +855,10 @@ mod tests {
         let maturity_time = 365 * TICKS_PER_DAY; // 1 year
         let mut cert = Certificate::new(1, [100, 200], 1, 100000, purchase_time, maturity_time, 1200);
         
-        // After 30 days, check available interest (may be 0 due to precision loss)
+        // After 30 days, check available interest
         let time_30_days = 30 * TICKS_PER_DAY;
         let available_interest = cert.calculate_available_interest(time_30_days).unwrap();
-        // Accept that interest might be 0 due to integer division precision loss
+        assert_eq!(available_interest, 986);
         
         // Withdraw half the available interest  
         let withdrawal_amount = available_interest / 2;
@@ -341,9 +892,8 @@ mod tests {
         let one_year = 365 * TICKS_PER_DAY;
         let interest_one_year = cert.calculate_available_interest(one_year).unwrap();
         
-        // Due to precision loss in integer arithmetic, result may be significantly lower
-        // This is acceptable trade-off for avoiding u128
-        assert!(interest_one_year >= 0); // At minimum should not error
+        // Exactly 10% of the principal
+        assert_eq!(interest_one_year, 100_000);
     }
 
     #[test]
@@ -450,4 +1000,335 @@ mod tests {
         
         println!("Maximum calculation test passed: 1B USDT × 500% APY × 10 years = {} USDT", interest);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_interest_params_reproduce_on_chain_interest() {
+        let params = interest_params(SECONDS_PER_TICK);
+        assert_eq!(params.seconds_per_tick, SECONDS_PER_TICK);
+        assert_eq!(params.ticks_per_day, TICKS_PER_DAY);
+        assert_eq!(params.seconds_per_year, SECONDS_PER_YEAR);
+        assert_eq!(params.basis_points_divisor, BASIS_POINTS_DIVISOR);
+        assert_eq!(params.rounding_mode, INTEREST_ROUNDING_MODE);
+
+        // A client following the published parameters gets the same amounts
+        let cert = Certificate::new(1, [1, 2], 1, 123_457, 100, 100 + 365 * TICKS_PER_DAY, 1337);
+        for elapsed in [1, 17, TICKS_PER_DAY, 45 * TICKS_PER_DAY + 3] {
+            let client = cert.principal * cert.locked_apy * elapsed * params.seconds_per_tick
+                / (params.basis_points_divisor * params.seconds_per_year);
+            assert_eq!(cert.calculate_total_simple_interest(100 + elapsed).unwrap(), client);
+        }
+    }
+
+    #[test]
+    fn test_interest_stops_accruing_at_maturity() {
+        let maturity_time = 1000 + 90 * TICKS_PER_DAY;
+        let mut cert = Certificate::new(1, [100, 200], 1, 100000, 1000, maturity_time, 1200);
+
+        let at_maturity = cert.calculate_total_simple_interest(maturity_time).unwrap();
+        assert!(at_maturity > 0);
+        let late = maturity_time + 30 * TICKS_PER_DAY;
+        assert_eq!(cert.calculate_total_simple_interest(late).unwrap(), at_maturity);
+        assert_eq!(cert.calculate_available_interest(late).unwrap(), at_maturity);
+
+        // Once the capped interest is claimed nothing more accrues
+        cert.claim_interest(at_maturity).unwrap();
+        assert_eq!(cert.calculate_available_interest(late + TICKS_PER_DAY).unwrap(), 0);
+
+        // Still zero at or before purchase
+        assert_eq!(cert.calculate_total_simple_interest(1000).unwrap(), 0);
+        assert_eq!(cert.calculate_total_simple_interest(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_simple_vs_daily_compound_interest_one_year() {
+        let one_year = 365 * TICKS_PER_DAY;
+        let simple = Certificate::new(1, [100, 200], 1, 100000, 0, one_year, 1200);
+        let mut daily = simple.clone();
+        daily.compounding = COMPOUNDING_DAILY;
+
+        assert_eq!(simple.calculate_available_interest(one_year).unwrap(), 12000);
+        // 100000 * ((1 + 0.12 / 365)^365 - 1) = 12747.46, floored once
+        let compound = daily.calculate_available_interest(one_year).unwrap();
+        assert_eq!(compound, 12747);
+        assert!(compound > 12000);
+
+        // Only whole periods count
+        assert_eq!(daily.calculate_total_compound_interest(TICKS_PER_DAY - 1).unwrap(), 0);
+        assert!(daily.calculate_total_compound_interest(TICKS_PER_DAY).unwrap() > 0);
+    }
+
+    #[test]
+    fn test_compounding_periods_follow_the_locked_tick_length() {
+        // At 10 seconds a tick a day is 8640 ticks, and a year of daily periods pays the same
+        let day = ticks_per_day(10);
+        assert_eq!(day, TICKS_PER_DAY / 2);
+        let mut daily = Certificate::new(1, [100, 200], 1, 100000, 0, 365 * day, 1200);
+        daily.compounding = COMPOUNDING_DAILY;
+        daily.seconds_per_tick = 10;
+        assert_eq!(daily.calculate_available_interest(365 * day).unwrap(), 12747);
+        assert_eq!(daily.calculate_total_compound_interest(day - 1).unwrap(), 0);
+        assert!(daily.calculate_total_compound_interest(day).unwrap() > 0);
+        assert_eq!(interest_params(10).ticks_per_day, day);
+    }
+
+    #[test]
+    fn test_monthly_compound_interest_and_persistence() {
+        let one_year = 365 * TICKS_PER_DAY;
+        let mut cert = Certificate::new(1, [100, 200], 1, 100000, 0, one_year, 1200);
+        cert.compounding = COMPOUNDING_MONTHLY;
+        // 12 full 30-day months fit in the year: 100000 * ((1 + 0.12 * 30 / 365)^12 - 1) = 12499.24
+        assert_eq!(cert.calculate_total_interest(one_year).unwrap(), 12499);
+
+        let mut data = vec![];
+        cert.to_data(&mut data);
+        let restored = Certificate::from_data(&mut data.iter_mut());
+        assert_eq!(restored.compounding, COMPOUNDING_MONTHLY);
+
+        // Certificates stored before compounding existed are simple interest
+        // (drop compounding, principal_currency, rounding_drift, the APY step count, interest_currency,
+        // early_withdrawal_penalty_bps, grace_ticks, auto_claim, last_claim_counter and seconds_per_tick)
+        data.truncate(data.len() - 10);
+        let legacy = Certificate::from_data(&mut data.iter_mut());
+        assert_eq!(legacy.compounding, COMPOUNDING_SIMPLE);
+        assert_eq!(legacy.seconds_per_tick, SECONDS_PER_TICK);
+    }
+
+    #[test]
+    fn test_compound_interest_follows_apy_steps_and_long_terms() {
+        let day = TICKS_PER_DAY;
+        let mut stepped = Certificate::new(1, [100, 200], 1, 100000, 0, 30 * day, 800);
+        stepped.compounding = COMPOUNDING_DAILY;
+        stepped.apy_steps = vec![(10 * day, 1200)];
+        // 10 days at 8% then 20 at 12%: 100000 * (1 + 0.08 / 365)^10 * (1 + 0.12 / 365)^20 - 100000 = 880.x
+        assert_eq!(stepped.calculate_total_compound_interest(30 * day).unwrap(), 880);
+        // A threshold inside a period switches from the next whole period
+        stepped.apy_steps = vec![(9 * day + 1, 1200)];
+        assert_eq!(stepped.calculate_total_compound_interest(30 * day).unwrap(), 880);
+
+        // Ten years of daily periods: 1000000 * ((1 + 0.12 / 365)^3650 - 1) = 2319462.x
+        let ten_years = MAX_CERTIFICATE_DURATION_TICKS;
+        let mut long = Certificate::new(1, [100, 200], 1, 1_000_000, 0, ten_years, 1200);
+        long.compounding = COMPOUNDING_DAILY;
+        assert_eq!(long.calculate_total_compound_interest(ten_years).unwrap(), 2319462);
+    }
+
+    #[test]
+    fn test_compound_interest_overflow_is_reported() {
+        let ten_years = MAX_CERTIFICATE_DURATION_TICKS;
+        let mut cert = Certificate::new(1, [100, 200], 1, MAX_CERTIFICATE_AMOUNT, 0, ten_years, MAX_APY_BASIS_POINTS);
+        cert.compounding = COMPOUNDING_DAILY;
+        assert_eq!(cert.calculate_total_interest(ten_years), Err(crate::error::ERROR_OVERFLOW));
+    }
+
+    #[test]
+    fn test_rounding_drift_against_exact_interest() {
+        let cert = Certificate::new(1, [100, 200], 1, 10_000, 0, 30 * TICKS_PER_DAY, 1000);
+        // Exact interest after one day is 2.739726..., the floored formula pays 2
+        assert_eq!(cert.calculate_total_interest(TICKS_PER_DAY).unwrap(), 2);
+        assert_eq!(cert.calculate_rounding_drift(TICKS_PER_DAY).unwrap(), 739_726);
+        // After two days: 5.479452... exact, 5 paid
+        assert_eq!(cert.calculate_rounding_drift(2 * TICKS_PER_DAY).unwrap(), 479_452);
+        assert_eq!(cert.calculate_rounding_drift(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_rounding_modes_on_half_unit_boundary() {
+        // 1_000 at 10% for 31_536 ticks (157_680 seconds) earns exactly 0.5, 3_000 earns 1.5
+        let ticks = 31_536;
+        let simulate = |principal, current, rounding| {
+            simulate_interest_rounded(principal, 1000, 0, current, SECONDS_PER_TICK, SECONDS_PER_YEAR, rounding).unwrap()
+        };
+        assert_eq!((simulate(1_000, ticks, RoundingMode::Floor), simulate(1_000, ticks, RoundingMode::Nearest)), (0, 1));
+        assert_eq!((simulate(3_000, ticks, RoundingMode::Floor), simulate(3_000, ticks, RoundingMode::Nearest)), (1, 2));
+        // Just below the boundary both modes agree
+        assert_eq!((simulate(1_000, ticks - 1, RoundingMode::Floor), simulate(1_000, ticks - 1, RoundingMode::Nearest)), (0, 0));
+
+        // Nearest never falls behind floor or by more than a unit, and never decreases over time,
+        // so claiming the difference between totals never goes negative
+        let mut previous = 0;
+        for current in (0..=20 * ticks).step_by(ticks as usize / 4) {
+            let (floor, nearest) = (simulate(3_000, current, RoundingMode::Floor), simulate(3_000, current, RoundingMode::Nearest));
+            assert!(nearest >= floor && nearest <= floor + 1);
+            assert!(nearest >= previous);
+            previous = nearest;
+        }
+        assert_eq!(INTEREST_ROUNDING_MODE, INTEREST_ROUNDING.name());
+    }
+
+    #[test]
+    fn test_simulate_interest_matrix() {
+        let one_year = SECONDS_PER_YEAR / SECONDS_PER_TICK;
+        let cases = [
+            // (principal, apy, purchase, current, expected)
+            (10_000, 1000, 0, one_year, 1000),
+            (10_000, 1000, 0, one_year / 2, 500),
+            (10_000, 1000, 100, 100, 0),
+            (10_000, 1000, 200, 100, 0),
+            (10_000, 0, 0, one_year, 0),
+            (1_000, 1000, 0, TICKS_PER_DAY, 0), // 0.27 floors to 0
+            (5_000, 3, 0, 10 * one_year, 15), // 1.5 a year is not floored to 1 before scaling
+            (MAX_CERTIFICATE_AMOUNT, MAX_APY_BASIS_POINTS, 0, one_year, 5_000_000_000),
+        ];
+        for (principal, apy, purchase, current, expected) in cases {
+            let simulated = simulate_interest(principal, apy, purchase, current, SECONDS_PER_TICK, SECONDS_PER_YEAR).unwrap();
+            assert_eq!(simulated, expected);
+
+            // The instance method delegates once current is within the term
+            let cert = Certificate::new(1, [100, 200], 1, principal, purchase, purchase.max(current), apy);
+            assert_eq!(cert.calculate_total_simple_interest(current).unwrap(), simulated);
+        }
+
+        // Custom time units and invalid ones
+        assert_eq!(simulate_interest(10_000, 1000, 0, 365, 1, 365).unwrap(), 1000);
+        assert_eq!(simulate_interest(10_000, 1000, 0, 365, 1, 0), Err(crate::error::ERROR_DIVISION_BY_ZERO));
+        assert_eq!(simulate_interest(u64::MAX, 2, 0, 1, 1, 1), Err(crate::error::ERROR_OVERFLOW));
+    }
+
+    #[test]
+    fn test_apy_schedule_sums_segment_interest() {
+        let one_year = SECONDS_PER_YEAR / SECONDS_PER_TICK;
+        let purchase = 1000;
+        let mut cert = Certificate::new(1, [100, 200], 1, 10_000, purchase, purchase + 2 * one_year, 800);
+        // 8% in year one, 12% in year two
+        cert.apy_steps = vec![(one_year, 1200)];
+        assert_eq!(cert.apy_at(0), 800);
+        assert_eq!(cert.apy_at(one_year), 1200);
+
+        let first = simulate_interest(10_000, 800, purchase, purchase + one_year, SECONDS_PER_TICK, SECONDS_PER_YEAR).unwrap();
+        let second = simulate_interest(10_000, 1200, purchase + one_year, purchase + 2 * one_year, SECONDS_PER_TICK, SECONDS_PER_YEAR).unwrap();
+        assert_eq!((first, second), (800, 1200));
+        assert_eq!(cert.calculate_total_simple_interest(purchase + 2 * one_year).unwrap(), first + second);
+        assert_eq!(cert.calculate_total_simple_interest(purchase + one_year).unwrap(), first);
+        assert_eq!(cert.calculate_total_simple_interest(purchase + one_year + one_year / 2).unwrap(), first + 600);
+        assert_eq!(cert.calculate_rounding_drift(purchase + 2 * one_year).unwrap(), 0);
+
+        // Without steps the base APY covers the whole term
+        let flat = Certificate::new(1, [100, 200], 1, 10_000, purchase, purchase + 2 * one_year, 800);
+        assert_eq!(flat.calculate_total_simple_interest(purchase + 2 * one_year).unwrap(), 1600);
+
+        let mut data = vec![];
+        cert.to_data(&mut data);
+        assert_eq!(Certificate::from_data(&mut data.iter_mut()).apy_steps, vec![(one_year, 1200)]);
+    }
+
+    #[test]
+    fn test_duration_days_follows_tick_params() {
+        let cert = Certificate::new(1, [1, 2], 1, 10_000, 5, 5 + 30 * TICKS_PER_DAY, 1000);
+        assert_eq!(cert.duration_days(TICKS_PER_DAY), 30);
+
+        // Halving the tick length doubles ticks per day, the same stored term reads as 15 days
+        assert_eq!(cert.duration_days(2 * TICKS_PER_DAY), 15);
+        assert_eq!(cert.duration_days(TICKS_PER_DAY / 2), 60);
+        assert_eq!(cert.duration_days(0), 0);
+    }
+
+    #[test]
+    fn test_total_interest_is_memoized_per_certificate_and_time() {
+        let maturity = 30 * TICKS_PER_DAY;
+        let mut cert = Certificate::new(5, [100, 200], 1, 10_000, 0, maturity, 1000);
+        clear_interest_cache();
+
+        // A cached read matches a fresh computation
+        let fresh = cert.calculate_total_simple_interest(15 * TICKS_PER_DAY).unwrap();
+        assert_eq!(cert.calculate_total_interest(15 * TICKS_PER_DAY).unwrap(), fresh);
+        let key = cert.interest_cache_key(15 * TICKS_PER_DAY);
+        assert_eq!(key[..5], [5, 100, 200, 15 * TICKS_PER_DAY, 10_000]);
+        assert_eq!(INTEREST_CACHE.with(|cache| cache.get()), Some((key, fresh)));
+
+        // Identical inputs are served from the cache
+        INTEREST_CACHE.with(|cache| cache.set(Some((key, 7))));
+        assert_eq!(cert.calculate_total_interest(15 * TICKS_PER_DAY).unwrap(), 7);
+        assert_eq!(cert.calculate_available_interest(15 * TICKS_PER_DAY).unwrap(), 7);
+
+        // Any change to the inputs recomputes, and clearing drops the entry
+        cert.principal = 5_000;
+        assert_eq!(cert.calculate_total_interest(15 * TICKS_PER_DAY).unwrap(), fresh / 2);
+        cert.principal = 10_000;
+        assert_eq!(cert.calculate_total_interest(15 * TICKS_PER_DAY).unwrap(), fresh);
+        INTEREST_CACHE.with(|cache| cache.set(Some((key, 7))));
+        clear_interest_cache();
+        assert_eq!(cert.calculate_total_interest(15 * TICKS_PER_DAY).unwrap(), fresh);
+    }
+
+    #[test]
+    fn test_interest_cache_separates_owners_sharing_an_id() {
+        let maturity = 30 * TICKS_PER_DAY;
+        let now = 20 * TICKS_PER_DAY;
+        // Ids are per owner: both hold certificate 5 on the same terms, bob bought it later
+        let alice = Certificate::new(5, [100, 200], 1, 10_000, 0, maturity, 1000);
+        let bob = Certificate::new(5, [300, 400], 1, 10_000, 10 * TICKS_PER_DAY, maturity, 1000);
+        clear_interest_cache();
+
+        let alice_interest = alice.calculate_total_interest(now).unwrap();
+        let bob_interest = bob.calculate_total_interest(now).unwrap();
+        assert_eq!(bob_interest, bob.calculate_total_simple_interest(now).unwrap());
+        assert_eq!(bob_interest, alice_interest / 2);
+
+        // A different APY schedule on otherwise identical terms is not served alice's result either
+        let mut stepped = alice.clone();
+        stepped.apy_steps = vec![(10 * TICKS_PER_DAY, 2000)];
+        alice.calculate_total_interest(now).unwrap();
+        assert!(stepped.calculate_total_interest(now).unwrap() > alice_interest);
+    }
+
+    #[test]
+    fn test_interest_cache_separates_grace_windows() {
+        let maturity = 30 * TICKS_PER_DAY;
+        let now = 40 * TICKS_PER_DAY;
+        // Accrual runs on through the grace window, so a longer one earns more past maturity
+        let cert = Certificate::new(5, [100, 200], 1, 10_000, 0, maturity, 1000);
+        let mut graced = cert.clone();
+        graced.grace_ticks = 10 * TICKS_PER_DAY;
+        clear_interest_cache();
+
+        let interest = cert.calculate_total_interest(now).unwrap();
+        let graced_interest = graced.calculate_total_interest(now).unwrap();
+        assert_eq!(graced_interest, graced.calculate_total_simple_interest(now).unwrap());
+        assert!(graced_interest > interest);
+    }
+
+    #[test]
+    fn test_accrual_pauses_exclude_frozen_ticks() {
+        let day = TICKS_PER_DAY;
+        let mut product = ProductType::new(1, 30 * day, 1000, 100);
+        product.pause_accrual(5 * day).unwrap();
+        product.pause_accrual(6 * day).unwrap(); // Already frozen, the window keeps its start
+        assert_eq!(product.frozen_since(), Some(5 * day));
+        product.resume_accrual(15 * day);
+        assert_eq!((product.frozen_since(), product.accrual_pauses.clone()), (None, vec![(5 * day, 15 * day)]));
+        product.pause_accrual(20 * day).unwrap();
+        product.resume_accrual(20 * day); // Never lasted a tick
+        assert_eq!(product.accrual_pauses, vec![(5 * day, 15 * day)]);
+
+        let mut data = vec![];
+        product.to_data(&mut data);
+        assert_eq!(ProductType::from_data(&mut data.iter_mut()).accrual_pauses, product.accrual_pauses);
+
+        // 10 paused days out of 30: the certificate earns 20 days at 10% on 10_000
+        let plain = Certificate::new(1, [1, 1], 1, 10_000, 0, 30 * day, 1000);
+        let mut paused = plain.clone();
+        paused.accrual_pauses = product.accrual_pauses.clone();
+        assert_eq!(paused.calculate_total_simple_interest(5 * day).unwrap(), plain.calculate_total_simple_interest(5 * day).unwrap());
+        assert_eq!(paused.calculate_total_simple_interest(12 * day).unwrap(), plain.calculate_total_simple_interest(5 * day).unwrap());
+        assert_eq!(paused.calculate_total_simple_interest(30 * day).unwrap(), plain.calculate_total_simple_interest(20 * day).unwrap());
+        assert_eq!(paused.calculate_rounding_drift(30 * day).unwrap(), plain.calculate_rounding_drift(20 * day).unwrap());
+
+        // An open window freezes accrual up to the current time
+        paused.accrual_pauses.push((25 * day, OPEN_ACCRUAL_PAUSE));
+        assert_eq!(paused.calculate_total_simple_interest(30 * day).unwrap(), plain.calculate_total_simple_interest(15 * day).unwrap());
+
+        // Compounding counts whole unpaused periods only
+        let mut daily = plain.clone();
+        daily.compounding = COMPOUNDING_DAILY;
+        let mut daily_paused = daily.clone();
+        daily_paused.accrual_pauses = product.accrual_pauses.clone();
+        assert_eq!(daily_paused.calculate_total_interest(30 * day).unwrap(), daily.calculate_total_interest(20 * day).unwrap());
+
+        let mut full = ProductType::new(2, 30 * day, 1000, 100);
+        for window in 0..MAX_ACCRUAL_PAUSES as u64 {
+            full.pause_accrual(2 * window + 1).unwrap();
+            full.resume_accrual(2 * window + 2);
+        }
+        assert_eq!(full.pause_accrual(100), Err(crate::error::ERROR_ACCRUAL_PAUSE_LIMIT));
+    }
+}
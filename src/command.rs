@@ -1,17 +1,22 @@
 use crate::error::*;
-use crate::state::GLOBAL_STATE;
-use crate::player::StakingPlayer;
-use crate::math_safe::{safe_add, safe_sub, safe_mul};
+use crate::state::{with_state, with_state_mut, GlobalState};
+use crate::player::{NonceCheck, PlayerData, StakingPlayer};
+use crate::math_safe::{safe_add, safe_sub, safe_mul, to_bool_checked, to_u32_checked};
 use zkwasm_rest_abi::WithdrawInfo;
 use crate::settlement::SettlementInfo;
 use crate::config::{POINTS_DIVISOR, MIN_POINTS_WITHDRAWAL};
-use crate::cert_manager::{ProductTypeManager, CertificateManager};
+use crate::cert_manager::{ProductTypeManager, CertificateManager, ProductTypeOptions, WhitelistManager, Payout, PreparedClaim, PreparedRedemption, Storage, MerkleStorage};
+use crate::certificate::{Certificate, ProductType, PRINCIPAL_CURRENCY_USDT, PRINCIPAL_CURRENCY_POINTS};
 use crate::event::{emit_product_type_indexed_object, emit_certificate_indexed_object,
                    emit_interest_claim_event, emit_principal_redemption_event,
                    emit_certificate_purchase_event, emit_deposit_event, emit_withdrawal_event,
                    emit_points_withdrawal_event, emit_admin_withdrawal_event,
                    emit_product_type_created_event, emit_product_type_modified_event,
-                   emit_reserve_ratio_change_event};
+                   emit_reserve_ratio_change_event, emit_payouts_paused_event,
+                   emit_global_param_changed_event, emit_booster_bonus_event,
+                   emit_reserve_pool_withdrawal_event, emit_rounding_surplus_withdrawal_event, emit_whitelist_batch_event,
+                   emit_certificate_transfer_event, emit_system_paused_event,
+                   emit_recharge_adjusted_event, emit_role_changed_event, emit_admin_repayment_event};
 
 #[derive(Clone)]
 pub enum Command {
@@ -19,6 +24,7 @@ pub enum Command {
     Withdraw(Withdraw),
     WithdrawPoints(WithdrawPoints),
     Deposit(Deposit),
+    DepositAndPurchase(DepositAndPurchase),
     // Standard player install and timer
     InstallPlayer,
     Tick,
@@ -31,12 +37,65 @@ pub enum Command {
     // Admin functions
     AdminWithdrawToMultisig(AdminWithdrawToMultisig),
     SetReserveRatio(SetReserveRatio),
+    SetPayoutsPaused(SetPayoutsPaused),
+    SetGlobalParam(SetGlobalParam),
+    WithdrawReservePool(WithdrawReservePool),
+    WithdrawRoundingSurplus(WithdrawRoundingSurplus),
+    SetWhitelistBatch(SetWhitelistBatch),
+    SetProductTypeStatus(SetProductTypeStatus),
+    SetApySteps(SetApySteps),
+    TransferCertificate(TransferCertificate),
+    RedeemPrincipalPartial(RedeemPrincipalPartial),
+    RedeemAll(RedeemAll),
+    RedeemEarly(RedeemEarly),
+    BatchPurchase(BatchPurchase),
+    RolloverCertificate(RolloverCertificate),
+    ClaimInterestToTarget(ClaimInterestToTarget),
+    RedeemPointsForFunds(RedeemPointsForFunds),
+    SetPaused(SetPaused),
+    AdminAdjustRecharge(AdminAdjustRecharge),
+    GrantRole(GrantRole),
+    RevokeRole(RevokeRole),
+    AdminForceWithdrawPlayer(AdminForceWithdrawPlayer),
+    AdminRepay(AdminRepay),
+    SweepDust(SweepDust),
 }
 
 pub trait CommandHandler {
     fn handle(&self, pid: &[u64; 2], nonce: u64, rand: &[u64; 4], counter: u64) -> Result<(), u32>;
 }
 
+/// The player behind an admin command, which fails with ERROR_ADMIN_NOT_INSTALLED if it was never installed
+fn load_admin(pid: &[u64; 2]) -> Result<StakingPlayer, u32> {
+    load_admin_with(StakingPlayer::get_from_pid, pid)
+}
+
+/// Storage-independent core of `load_admin`, the getter returns None for a missing player
+fn load_admin_with<G>(get: G, pid: &[u64; 2]) -> Result<StakingPlayer, u32>
+where
+    G: FnOnce(&[u64; 2]) -> Option<StakingPlayer>,
+{
+    get(pid).ok_or(ERROR_ADMIN_NOT_INSTALLED)
+}
+
+/// Reject a malformed destination in withdraw command data, where the low 32 bits
+/// of the first word carry the amount and the rest is the address
+fn check_withdraw_address(data: &[u64; 3]) -> Result<(), u32> {
+    if !crate::config::validate_address_parts([data[0] >> 32, data[1], data[2]]) {
+        return Err(ERROR_INVALID_WITHDRAW_ADDRESS);
+    }
+    Ok(())
+}
+
+/// Settlement paying `amount` to the multisig, the amount rides in the low 32 bits of the first word
+/// Built before any state changes so an amount that does not fit is rejected cleanly
+fn multisig_withdraw_info(amount: u64) -> Result<WithdrawInfo, u32> {
+    let amount = to_u32_checked(amount)? as u64;
+    // The pre-parsed first part holds only the leading 4 address bytes, see validate_address_parts
+    let (first, middle, last) = crate::config::get_multisig_address_parts();
+    Ok(WithdrawInfo::new(&[(first << 32) | amount, middle, last], 0))
+}
+
 #[derive(Clone)]
 pub struct Withdraw {
     pub data: [u64; 3],
@@ -45,35 +104,23 @@ pub struct Withdraw {
 impl CommandHandler for Withdraw {
     fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
         // Get txid from global state early
-        let txid = GLOBAL_STATE.0.borrow().txcounter;
+        let txid = with_state(|state| state.txcounter);
         
         let mut player = StakingPlayer::get_from_pid(pid);
         match player.as_mut() {
             None => Err(ERROR_PLAYER_NOT_EXIST),
             Some(player) => {
-                player.check_and_inc_nonce(nonce);
-                let amount = self.data[0] & 0xffffffff;
-
-                // Certificate system: Check if user has enough idle funds to withdraw
-                if player.data.idle_funds < amount {
-                    return Err(ERROR_INSUFFICIENT_BALANCE);
-                }
+                player.try_check_and_inc_nonce(nonce)?;
 
-                // Certificate system: Withdraw from idle funds (no time restrictions)
-                player.data.spend_idle_funds(amount)?;
-                
-                // Update global statistics
-                {
-                    let mut state = GLOBAL_STATE.0.borrow_mut();
-                    state.total_funds = safe_sub(state.total_funds, amount)?;
-                }
+                // The local player copy is dropped without store() on error, so a failure leaves no partial state
+                let amount = with_state_mut(|state| self.apply(state, &mut player.data, counter))?;
                 
-                // Emit withdrawal event
                 let address_parts = [self.data[0], self.data[1], self.data[2]];
-                emit_withdrawal_event(*pid, amount, address_parts, txid, counter);
-                
-                let withdrawinfo = WithdrawInfo::new(&[self.data[0], self.data[1], self.data[2]], 0);
+                let withdrawinfo = WithdrawInfo::new(&address_parts, 0);
                 SettlementInfo::append_settlement(withdrawinfo);
+                
+                // Emit withdrawal event once the settlement is queued
+                emit_withdrawal_event(*pid, amount, address_parts, txid, counter);
                 player.store();
 
                 Ok(())
@@ -82,6 +129,16 @@ impl CommandHandler for Withdraw {
     }
 }
 
+impl Withdraw {
+    /// Withdraw::handle's steps on the loaded player, returning the amount leaving the system
+    pub fn apply(&self, state: &mut GlobalState, data: &mut PlayerData, counter: u64) -> Result<u64, u32> {
+        let amount = self.data[0] & 0xffffffff;
+        check_withdraw_address(&self.data)?;
+        apply_withdrawal(state, data, amount, counter)?;
+        Ok(amount)
+    }
+}
+
 
 #[derive(Clone)]
 pub struct WithdrawPoints {
@@ -91,14 +148,15 @@ pub struct WithdrawPoints {
 impl CommandHandler for WithdrawPoints {
     fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
         // Get txid from global state early
-        let txid = GLOBAL_STATE.0.borrow().txcounter;
+        let txid = with_state(|state| state.txcounter);
         
         let mut player = StakingPlayer::get_from_pid(pid);
         match player.as_mut() {
             None => Err(ERROR_PLAYER_NOT_EXIST),
             Some(player) => {
-                player.check_and_inc_nonce(nonce);
+                player.try_check_and_inc_nonce(nonce)?;
                 let points_amount = self.data[0] & 0xffffffff;
+                check_withdraw_address(&self.data)?;
 
                 // Validate points amount
                 if points_amount == 0 {
@@ -140,8 +198,8 @@ impl CommandHandler for WithdrawPoints {
 
 impl WithdrawPoints {
     pub fn handle_admin(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], _counter: u64) -> Result<(), u32> {
-        let mut admin = StakingPlayer::get_from_pid(pid).unwrap();
-        admin.check_and_inc_nonce(nonce);
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
 
         let withdrawinfo = WithdrawInfo::new(&[self.data[0], self.data[1], self.data[2]], 2<<8);
         SettlementInfo::append_settlement(withdrawinfo);
@@ -151,6 +209,45 @@ impl WithdrawPoints {
     }
 }
 
+#[derive(Clone)]
+pub struct RedeemPointsForFunds {
+    pub points_amount: u64, // Points to exchange, only whole units of funds are converted
+}
+
+impl CommandHandler for RedeemPointsForFunds {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], _counter: u64) -> Result<(), u32> {
+        let mut player = StakingPlayer::get_from_pid(pid);
+        match player.as_mut() {
+            None => Err(ERROR_PLAYER_NOT_EXIST),
+            Some(player) => {
+                player.try_check_and_inc_nonce(nonce)?;
+                with_state_mut(|state| apply_points_conversion(state, &mut player.data, self.points_amount))?;
+                
+                player.store();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// RedeemPointsForFunds::handle's accounting: whole units of funds are bought at points_to_funds_rate
+pub fn apply_points_conversion(state: &mut GlobalState, data: &mut PlayerData, points_amount: u64) -> Result<(), u32> {
+    let rate = state.points_to_funds_rate;
+    if rate == 0 {
+        return Err(ERROR_POINTS_CONVERSION_DISABLED);
+    }
+    // Points short of a whole unit of funds stay with the player
+    let funds = points_amount / rate;
+    if funds == 0 {
+        return Err(ERROR_INVALID_POINTS_AMOUNT);
+    }
+    let points = safe_mul(funds, rate)?;
+    
+    data.spend_points(points)?;
+    data.add_idle_funds(funds)?;
+    state.record_points_conversion(points, funds)
+}
+
 #[derive(Clone)]
 pub struct Deposit {
     pub data: [u64; 3],
@@ -158,68 +255,242 @@ pub struct Deposit {
 
 impl CommandHandler for Deposit {
     fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
-        let amount = self.data[2];
-        
-        // Validate deposit amount
-        if amount == 0 {
-            return Err(ERROR_INVALID_STAKE_AMOUNT);
-        }
-        
-        // Update global statistics and get txid early
-        let txid = {
-            let mut state = GLOBAL_STATE.0.borrow_mut();
-            state.total_funds = safe_add(state.total_funds, amount)?;
-            state.txcounter
-        };
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+
+        // Get txid early
+        let txid = with_state(|state| state.txcounter);
         
-        let mut admin = StakingPlayer::get_from_pid(pid).unwrap();
-        admin.check_and_inc_nonce(nonce);
-        let mut player = StakingPlayer::get_from_pid(&[self.data[0], self.data[1]]);
+        let user_id = [self.data[0], self.data[1]];
+        let mut player = StakingPlayer::get_from_pid(&user_id);
         match player.as_mut() {
             None => Err(ERROR_PLAYER_NOT_EXIST),
             Some(player) => {
-                // Certificate system: Add to idle funds instead of staking
-                player.data.add_idle_funds(amount)?;
-                
-                // Emit deposit event
-                let user_id = [self.data[0], self.data[1]];
-                emit_deposit_event(*pid, user_id, amount, txid, counter);
+                let amount = with_state_mut(|state| self.apply(state, &mut player.data, counter))?;
                 
                 player.store();
                 admin.store();
+                
+                // Emit deposit event
+                emit_deposit_event(*pid, user_id, amount, txid, counter);
                 Ok(())
             }
         }
     }
 }
 
+impl Deposit {
+    /// Deposit::handle's steps on the loaded target player, returning the amount credited
+    pub fn apply(&self, state: &mut GlobalState, data: &mut PlayerData, counter: u64) -> Result<u64, u32> {
+        let amount = self.data[2];
+        
+        // Validate deposit amount
+        if amount == 0 {
+            return Err(ERROR_INVALID_STAKE_AMOUNT);
+        }
+        apply_deposit(state, data, amount, counter)?;
+        Ok(amount)
+    }
+}
+
+/// Withdraw::handle's accounting: idle funds and the fee leave the player, the global counters follow
+/// Storage-free so the conservation tests drive it directly
+pub fn apply_withdrawal(state: &mut GlobalState, data: &mut PlayerData, amount: u64, counter: u64) -> Result<(), u32> {
+    let fee = state.withdrawal_fee;
+    data.check_withdraw_cooldown(counter, state.withdraw_cooldown_ticks)?;
+
+    // Certificate system: Check if user has enough idle funds to withdraw and pay the fee
+    let charged = safe_add(amount, fee)?;
+    if data.idle_funds < charged {
+        return Err(ERROR_INSUFFICIENT_BALANCE);
+    }
+
+    // Certificate system: Withdraw from idle funds, the fee goes to the reserve pool
+    data.spend_idle_funds(charged)?;
+    let ticks_per_day = crate::config::ticks_per_day(state.seconds_per_tick);
+    data.record_daily_withdrawal(counter, amount, state.user_daily_withdraw_limit, ticks_per_day)?;
+    data.mark_withdraw(counter);
+    
+    // Update global statistics
+    // record_withdrawal_with_fee validates before mutating
+    state.record_withdrawal_with_fee(amount, fee)
+}
+
+/// Deposit::handle's accounting: the amount is credited to the player and to total_funds
+pub fn apply_deposit(state: &mut GlobalState, data: &mut PlayerData, amount: u64, counter: u64) -> Result<(), u32> {
+    // Certificate system: Add to idle funds instead of staking
+    data.add_idle_funds(amount)?;
+    data.lifetime_deposited = safe_add(data.lifetime_deposited, amount)?;
+    data.mark_deposit(counter, state.deposit_maturation_ticks)?;
+    
+    // Update global statistics once the target player is known to exist
+    state.record_deposit(amount)
+}
+
+/// PurchaseCertificate::handle's accounting: the principal leaves the player's balance
+/// Points are not part of the USDT fund accounting
+pub fn apply_purchase(state: &mut GlobalState, data: &mut PlayerData, product_type_id: u64, currency: u8, amount: u64) -> Result<(), u32> {
+    // Deduct the principal first
+    data.debit_principal(currency, amount)?;
+    
+    // Update global statistics (product 0 converts user funds into recharge funding)
+    // record_certificate_purchase validates before mutating, so an error leaves it untouched
+    if currency == PRINCIPAL_CURRENCY_USDT {
+        state.record_certificate_purchase(product_type_id, amount)?;
+    }
+    Ok(())
+}
+
+/// DepositAndPurchase::handle's accounting: the deposit is credited and locked in the same step
+pub fn apply_deposit_and_purchase(state: &mut GlobalState, data: &mut PlayerData, product_type_id: u64, amount: u64) -> Result<(), u32> {
+    // The funds are locked in the same transaction, so the deposit maturation delay does not apply
+    data.add_idle_funds(amount)?;
+    data.lifetime_deposited = safe_add(data.lifetime_deposited, amount)?;
+    state.record_deposit(amount)?;
+    apply_purchase(state, data, product_type_id, PRINCIPAL_CURRENCY_USDT, amount)
+}
+
+#[derive(Clone)]
+pub struct DepositAndPurchase {
+    pub data: [u64; 4], // [user_pid[0], user_pid[1], product_type_id, amount]
+}
+
+impl CommandHandler for DepositAndPurchase {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        let txid = with_state(|state| state.txcounter);
+        
+        let user_id = [self.data[0], self.data[1]];
+        let mut player = StakingPlayer::get_from_pid(&user_id).ok_or(ERROR_PLAYER_NOT_EXIST)?;
+        let certificate = with_state_mut(|state| self.apply(state, &mut MerkleStorage, &mut player.data))?;
+        
+        player.store();
+        admin.store();
+        
+        let amount = certificate.principal;
+        emit_deposit_event(*pid, user_id, amount, txid, counter);
+        emit_certificate_indexed_object(&certificate);
+        emit_certificate_purchase_event(user_id, certificate.id, certificate.product_type_id, amount, txid, counter);
+        Ok(())
+    }
+}
+
+impl DepositAndPurchase {
+    /// DepositAndPurchase::handle's steps on the loaded user, returning the new certificate
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, data: &mut PlayerData) -> Result<Certificate, u32> {
+        let user_id = [self.data[0], self.data[1]];
+        let product_type_id = self.data[2];
+        let amount = self.data[3];
+        if amount == 0 {
+            return Err(ERROR_INVALID_PRINCIPAL_AMOUNT);
+        }
+        
+        // The purchase is validated for the user before anything is credited
+        let prepared = CertificateManager::prepare_purchase(state, storage, &user_id, data, product_type_id, amount)?;
+        if prepared.0.principal_currency != PRINCIPAL_CURRENCY_USDT {
+            return Err(ERROR_INVALID_PRINCIPAL_CURRENCY);
+        }
+        
+        apply_deposit_and_purchase(state, data, product_type_id, amount)?;
+        CertificateManager::issue_certificate(state, storage, user_id, data, prepared, amount, false)
+    }
+}
+
 // Certificate system command structures
 
 #[derive(Clone)]
 pub struct CreateProductType {
     pub data: [u64; 4], // [duration_ticks, apy, min_amount, is_active]
+    pub options: Vec<u64>, // optional [display_order, compounding, principal_currency, whitelist_only, max_total_principal, interest_currency, early_withdrawal_penalty_bps, max_certificates, grace_ticks, min_claim_interval_ticks]
 }
 
 impl CommandHandler for CreateProductType {
     fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
         // Verify admin permissions (this should be checked in state.rs)
-        let mut player = StakingPlayer::get_from_pid(pid).unwrap();
-        player.check_and_inc_nonce(nonce);
+        let mut player = load_admin(pid)?;
+        player.try_check_and_inc_nonce(nonce)?;
         
         let duration_ticks = self.data[0];
         let apy = self.data[1];
         let min_amount = self.data[2];
-        let is_active = self.data[3] != 0; // 0 = false, 非0 = true
+        let is_active = to_bool_checked(self.data[3])?; // 0 = false, 1 = true
         
-        let product_type_id = ProductTypeManager::create_product_type(duration_ticks, apy, min_amount, is_active)?;
+        let options = ProductTypeOptions::from_params(&self.options)?;
+        
+        let product_type = with_state_mut(|state| {
+            ProductTypeManager::create_product_type(state, &mut MerkleStorage, duration_ticks, apy, min_amount, is_active, &options)
+        })?;
         
         // Emit IndexedObject event for the new product type
-        if let Some(product_type) = ProductTypeManager::get_product_type(product_type_id) {
-            emit_product_type_indexed_object(&product_type);
-        }
+        emit_product_type_indexed_object(&product_type);
         
         // Emit direct product type created event
-        emit_product_type_created_event(*pid, product_type_id, duration_ticks, apy, min_amount, is_active, counter);
+        emit_product_type_created_event(*pid, product_type.id, duration_ticks, apy, min_amount, is_active, counter);
+        
+        player.store();
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct SetProductTypeStatus {
+    pub product_type_id: u64,
+    pub is_active: bool,
+    pub freeze_accrual: bool, // Only read when closing
+}
+
+impl CommandHandler for SetProductTypeStatus {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Verify admin permissions (this should be checked in state.rs)
+        let mut player = load_admin(pid)?;
+        player.try_check_and_inc_nonce(nonce)?;
+        
+        let product_type = with_state_mut(|state| {
+            ProductTypeManager::set_product_type_status(state, &mut MerkleStorage, self.product_type_id, self.is_active, self.freeze_accrual)
+        })?;
+        
+        emit_product_type_indexed_object(&product_type);
+        emit_product_type_modified_event(
+            *pid,
+            product_type.id,
+            product_type.apy,
+            product_type.duration_ticks,
+            product_type.min_amount,
+            product_type.is_active,
+            counter
+        );
+        
+        player.store();
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct SetApySteps {
+    pub product_type_id: u64,
+    pub steps: Vec<u64>, // (threshold_ticks, apy) pairs, empty clears the schedule
+}
+
+impl CommandHandler for SetApySteps {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Verify admin permissions (this should be checked in state.rs)
+        let mut player = load_admin(pid)?;
+        player.try_check_and_inc_nonce(nonce)?;
+        
+        let apy_steps = ProductTypeOptions::apy_steps_from_params(&self.steps)?;
+        let product_type = ProductTypeManager::set_apy_steps(&mut MerkleStorage, self.product_type_id, apy_steps)?;
+        
+        emit_product_type_indexed_object(&product_type);
+        emit_product_type_modified_event(
+            *pid,
+            product_type.id,
+            product_type.apy,
+            product_type.duration_ticks,
+            product_type.min_amount,
+            product_type.is_active,
+            counter
+        );
         
         player.store();
         Ok(())
@@ -229,26 +500,30 @@ impl CommandHandler for CreateProductType {
 #[derive(Clone)]
 pub struct ModifyProductType {
     pub data: [u64; 5], // [product_type_id, new_apy, new_duration, new_min_amount, is_active]
+    pub options: Vec<u64>, // optional [display_order, compounding, principal_currency, whitelist_only, max_total_principal, interest_currency, early_withdrawal_penalty_bps, max_certificates, grace_ticks, min_claim_interval_ticks], absent values are kept
 }
 
 impl CommandHandler for ModifyProductType {
     fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
         // Verify admin permissions (this should be checked in state.rs)
-        let mut player = StakingPlayer::get_from_pid(pid).unwrap();
-        player.check_and_inc_nonce(nonce);
+        let mut player = load_admin(pid)?;
+        player.try_check_and_inc_nonce(nonce)?;
         
         let product_type_id = self.data[0];
         let new_apy = self.data[1];
         let new_duration = self.data[2];
         let new_min_amount = self.data[3];
-        let is_active = self.data[4] != 0; // 0 = false, 非0 = true
+        let is_active = to_bool_checked(self.data[4])?; // 0 = false, 1 = true
         
-        ProductTypeManager::modify_product_type(product_type_id, new_apy, new_duration, new_min_amount, is_active)?;
+        let options = ProductTypeOptions::from_params(&self.options)?;
+        
+        let terms = (new_apy, new_duration, new_min_amount);
+        let product_type = with_state_mut(|state| {
+            ProductTypeManager::modify_product_type(state, &mut MerkleStorage, product_type_id, terms, is_active, &options)
+        })?;
         
         // Emit IndexedObject event for the updated product type
-        if let Some(product_type) = ProductTypeManager::get_product_type(product_type_id) {
-            emit_product_type_indexed_object(&product_type);
-        }
+        emit_product_type_indexed_object(&product_type);
         
         // Emit direct product type modified event
         emit_product_type_modified_event(*pid, product_type_id, new_apy, new_duration, new_min_amount, is_active, counter);
@@ -260,61 +535,29 @@ impl CommandHandler for ModifyProductType {
 
 #[derive(Clone)]
 pub struct PurchaseCertificate {
-    pub data: [u64; 2], // [product_type_id, amount]
+    pub data: [u64; 4], // [product_type_id, amount, expected_apy, auto_claim]
 }
 
 impl CommandHandler for PurchaseCertificate {
     fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
         // Get txid from global state early
-        let txid = GLOBAL_STATE.0.borrow().txcounter;
+        let txid = with_state(|state| state.txcounter);
         
         let mut player = StakingPlayer::get_from_pid(pid);
         match player.as_mut() {
             None => Err(ERROR_PLAYER_NOT_EXIST),
             Some(player) => {
-                player.check_and_inc_nonce(nonce);
-                
-                let product_type_id = self.data[0];
-                let amount = self.data[1];
-                
-                // Validate amount
-                if amount == 0 {
-                    return Err(ERROR_INVALID_PRINCIPAL_AMOUNT);
-                }
-                
-                // Certificate system: Check user has sufficient idle funds
-                if player.data.idle_funds < amount {
-                    return Err(ERROR_INSUFFICIENT_BALANCE);
-                }
+                player.try_check_and_inc_nonce(nonce)?;
                 
-                // Create certificate
-                let cert_id = CertificateManager::purchase_certificate(*pid, product_type_id, amount)?;
+                // The player is a local copy that is only persisted by store()
+                let certificate = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
+                let cert_id = certificate.id;
                 
                 // Emit certificate indexed object event
-                if let Ok(certificate) = CertificateManager::validate_certificate_ownership(pid, cert_id) {
-                    emit_certificate_indexed_object(&certificate);
-                }
-                
-                // Deduct from idle funds first
-                player.data.spend_idle_funds(amount)?;
-                
-                // Update global statistics
-                {
-                    let mut state = GLOBAL_STATE.0.borrow_mut();
-                    
-                    if product_type_id == 0 {
-                        // Special handling for product type 0 (recharge product)
-                        // User's funds convert from "user principal" to "external recharge funding"
-                        state.total_funds = safe_sub(state.total_funds, amount)?; // 减少用户本金
-                        state.total_recharge_amount = safe_add(state.total_recharge_amount, amount)?; // 增加回充资金
-                    } else {
-                        // Normal certificate purchase - funds stay in system, no change to total_funds needed
-                        // (user idle_funds decreased, but money is still in the system as locked certificate)
-                    }
-                }
+                emit_certificate_indexed_object(&certificate);
                 
                 // Emit direct certificate purchase event
-                emit_certificate_purchase_event(*pid, cert_id, product_type_id, amount, txid, counter);
+                emit_certificate_purchase_event(*pid, cert_id, certificate.product_type_id, certificate.principal, txid, counter);
                 
                 player.store();
                 Ok(())
@@ -323,38 +566,91 @@ impl CommandHandler for PurchaseCertificate {
     }
 }
 
+impl PurchaseCertificate {
+    /// PurchaseCertificate::handle's steps on the loaded player, returning the new certificate
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, pid: &[u64; 2], data: &mut PlayerData) -> Result<Certificate, u32> {
+        let product_type_id = self.data[0];
+        let amount = self.data[1];
+        let expected_apy = self.data[2];
+        let auto_claim = to_bool_checked(self.data[3])?; // 0 = false, 1 = true
+        
+        let product_type = ProductTypeManager::get_product_type_with(|key| storage.get(key), product_type_id);
+        let principal_currency = check_purchase(product_type, data, amount, expected_apy, state.counter)?.principal_currency;
+        
+        // A failed transaction is still committed with its error code, so every
+        // fallible step must run before global state or storage is touched
+        let prepared = CertificateManager::prepare_purchase(state, storage, pid, data, product_type_id, amount)?;
+        apply_purchase(state, data, product_type_id, principal_currency, amount)?;
+        
+        // Create certificate (cannot fail once prepare_purchase has passed)
+        CertificateManager::issue_certificate(state, storage, *pid, data, prepared, amount, auto_claim)
+    }
+}
+
+/// PurchaseCertificate::handle's checks on the stored `product_type`, None when it does not exist
+/// Run ahead of `prepare_purchase`, so a bad amount is reported before the balance
+pub fn check_purchase(
+    product_type: Option<ProductType>,
+    data: &PlayerData,
+    amount: u64,
+    expected_apy: u64,
+    counter: u64
+) -> Result<ProductType, u32> {
+    // Validate amount
+    if amount == 0 {
+        return Err(ERROR_INVALID_PRINCIPAL_AMOUNT);
+    }
+    
+    let product_type = product_type.ok_or(ERROR_PRODUCT_TYPE_NOT_EXIST)?;
+    if !product_type.is_active {
+        return Err(ERROR_PRODUCT_TYPE_INACTIVE);
+    }
+    
+    // A non-zero expected APY protects the buyer from an APY change landing first
+    if expected_apy != 0 && product_type.apy != expected_apy {
+        return Err(ERROR_APY_CHANGED);
+    }
+    
+    if amount < product_type.min_amount {
+        return Err(ERROR_PRINCIPAL_AMOUNT_TOO_SMALL);
+    }
+    
+    // Products either lock idle funds or points
+    if product_type.principal_currency == PRINCIPAL_CURRENCY_POINTS {
+        if data.points < amount {
+            return Err(ERROR_INSUFFICIENT_POINTS);
+        }
+    } else {
+        // Certificate system: Check user has sufficient idle funds
+        if data.idle_funds < amount {
+            return Err(ERROR_INSUFFICIENT_BALANCE);
+        }
+        
+        // Freshly deposited funds must wait out the maturation delay
+        data.check_funds_matured(counter)?;
+    }
+    Ok(product_type)
+}
+
 #[derive(Clone)]
-pub struct ClaimInterest {
-    pub certificate_id: u64,
+pub struct SweepDust {
+    pub product_type_id: u64,
 }
 
-impl CommandHandler for ClaimInterest {
+impl CommandHandler for SweepDust {
     fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
-        // Get txid from global state early
-        let txid = GLOBAL_STATE.0.borrow().txcounter;
+        let txid = with_state(|state| state.txcounter);
         
         let mut player = StakingPlayer::get_from_pid(pid);
         match player.as_mut() {
             None => Err(ERROR_PLAYER_NOT_EXIST),
             Some(player) => {
-                player.check_and_inc_nonce(nonce);
-                
-                let cert_id = self.certificate_id;
-                
-                // Certificate system: Claim all available interest (no external claim)
-                let actual_amount = CertificateManager::claim_interest(pid, cert_id)?;
+                player.try_check_and_inc_nonce(nonce)?;
                 
-                // Add interest to user's idle funds
-                player.data.add_idle_funds(actual_amount)?;
-                
-                // Update global statistics - only track interest claimed, don't add to total_funds
-                {
-                    let mut state = GLOBAL_STATE.0.borrow_mut();
-                    state.interest_claimed = safe_add(state.interest_claimed, actual_amount)?;
-                }
+                let certificate = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
                 
-                // Emit interest claim event
-                emit_interest_claim_event(*pid, cert_id, actual_amount, txid, counter);
+                emit_certificate_indexed_object(&certificate);
+                emit_certificate_purchase_event(*pid, certificate.id, self.product_type_id, certificate.principal, txid, counter);
                 
                 player.store();
                 Ok(())
@@ -363,35 +659,44 @@ impl CommandHandler for ClaimInterest {
     }
 }
 
+impl SweepDust {
+    /// SweepDust::handle's steps on the loaded player, returning the new certificate
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, pid: &[u64; 2], data: &mut PlayerData) -> Result<Certificate, u32> {
+        // The whole idle balance is swept, and only while it is too small to buy a certificate
+        let product_type_id = self.product_type_id;
+        let dust = data.idle_funds;
+        if dust == 0 {
+            return Err(ERROR_INSUFFICIENT_BALANCE);
+        }
+        data.check_funds_matured(state.counter)?;
+        let prepared = CertificateManager::prepare_dust_sweep(state, storage, pid, data, product_type_id, dust)?;
+        
+        apply_purchase(state, data, product_type_id, PRINCIPAL_CURRENCY_USDT, dust)?;
+        CertificateManager::issue_certificate(state, storage, *pid, data, prepared, dust, false)
+    }
+}
+
 #[derive(Clone)]
-pub struct RedeemPrincipal {
-    pub certificate_id: u64,
+pub struct BatchPurchase {
+    pub items: Vec<(u64, u64)>, // (product_type_id, amount), at most MAX_BATCH_PURCHASE items
 }
 
-impl CommandHandler for RedeemPrincipal {
+impl CommandHandler for BatchPurchase {
     fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
         // Get txid from global state early
-        let txid = GLOBAL_STATE.0.borrow().txcounter;
-        
-        let cert_id = self.certificate_id;
-        
-        // Certificate system: Redeem principal to idle funds (no external withdrawal)
-        let principal_amount = CertificateManager::redeem_principal(pid, cert_id)?;
+        let txid = with_state(|state| state.txcounter);
         
         let mut player = StakingPlayer::get_from_pid(pid);
         match player.as_mut() {
             None => Err(ERROR_PLAYER_NOT_EXIST),
             Some(player) => {
-                player.check_and_inc_nonce(nonce);
+                player.try_check_and_inc_nonce(nonce)?;
                 
-                // Principal is returned to user's idle funds
-                // No changes to total_funds needed as money stays in system
-                
-                // Add principal to user's idle funds
-                player.data.add_idle_funds(principal_amount)?;
-                
-                // Emit principal redemption event
-                emit_principal_redemption_event(*pid, cert_id, principal_amount, txid, counter);
+                let certificates = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
+                for certificate in &certificates {
+                    emit_certificate_indexed_object(certificate);
+                    emit_certificate_purchase_event(*pid, certificate.id, certificate.product_type_id, certificate.principal, txid, counter);
+                }
                 
                 player.store();
                 Ok(())
@@ -400,109 +705,1090 @@ impl CommandHandler for RedeemPrincipal {
     }
 }
 
-#[derive(Clone)]
-pub struct AdminWithdrawToMultisig {
-    pub amount: u64, // Amount to withdraw to multisig address
+impl BatchPurchase {
+    /// BatchPurchase::handle's steps on the loaded player, returning the new certificates in item order
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, pid: &[u64; 2], data: &mut PlayerData) -> Result<Vec<Certificate>, u32> {
+        if self.items.is_empty() || self.items.len() > crate::config::MAX_BATCH_PURCHASE {
+            return Err(ERROR_INVALID_PARAM);
+        }
+        
+        // The whole batch is validated up front so a failing item leaves no certificate behind
+        let products = CertificateManager::prepare_batch_purchase(state, storage, pid, data, &self.items)?;
+        check_batch_funds(state, data, &products, &self.items, state.counter)?;
+        
+        let mut certificates = vec![];
+        for (product_type, &(product_type_id, amount)) in products.iter().zip(&self.items) {
+            apply_purchase(state, data, product_type_id, product_type.principal_currency, amount)?;
+            // Each item is prepared again, an earlier item may have stored the same product
+            certificates.push(CertificateManager::purchase_certificate(state, storage, *pid, data, product_type_id, amount, false)?);
+        }
+        Ok(certificates)
+    }
 }
 
-#[derive(Clone)]
-pub struct SetReserveRatio {
-    pub reserve_ratio: u64, // Reserve ratio in basis points (e.g., 1000 = 10%)
+/// BatchPurchase::handle's balance checks, the prepared `products` are paid for as a whole
+pub fn check_batch_funds(
+    state: &GlobalState,
+    data: &PlayerData,
+    products: &[ProductType],
+    items: &[(u64, u64)],
+    counter: u64
+) -> Result<(), u32> {
+    let (mut funds_total, mut points_total, mut recharge_total) = (0, 0, 0);
+    for (product_type, &(product_type_id, amount)) in products.iter().zip(items) {
+        if product_type.principal_currency == PRINCIPAL_CURRENCY_POINTS {
+            points_total = safe_add(points_total, amount)?;
+        } else {
+            funds_total = safe_add(funds_total, amount)?;
+            if product_type_id == 0 {
+                recharge_total = safe_add(recharge_total, amount)?;
+            }
+        }
+    }
+    if data.points < points_total {
+        return Err(ERROR_INSUFFICIENT_POINTS);
+    }
+    if funds_total > 0 {
+        if data.idle_funds < funds_total {
+            return Err(ERROR_INSUFFICIENT_BALANCE);
+        }
+        data.check_funds_matured(counter)?;
+    }
+    // Recharge purchases move user funds into recharge funding, all of them must fit
+    safe_sub(state.total_funds, recharge_total)?;
+    Ok(())
 }
 
-impl CommandHandler for AdminWithdrawToMultisig {
-    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
-        // Verify admin permissions (this should be checked in state.rs)
-        let mut admin = StakingPlayer::get_from_pid(pid).unwrap();
-        admin.check_and_inc_nonce(nonce);
-        
-        let amount = self.amount;
-        
-        // Validate amount is not zero
-        if amount == 0 {
-            return Err(ERROR_INVALID_STAKE_AMOUNT);
+/// What settling a certificate paid out, reported in its events once the transaction went through
+pub struct Settled {
+    pub interest: u64,
+    pub bonus: u64,
+    pub principal: Option<u64>, // Set when the certificate was redeemed
+}
+
+/// Emit the claim, booster and redemption events of a settled certificate, then re-index it
+fn emit_settled(pid: &[u64; 2], cert_id: u64, settled: &Settled, txid: u64, counter: u64) -> Result<(), u32> {
+    if settled.interest > 0 {
+        emit_interest_claim_event(*pid, cert_id, settled.interest, txid, counter);
+        if settled.bonus > 0 {
+            emit_booster_bonus_event(*pid, cert_id, settled.bonus, txid, counter);
         }
-        
-        // Calculate available funds, validate and update statistics early
-        let txid = {
-            let mut state = GLOBAL_STATE.0.borrow_mut();
-            let max_available = crate::config::calculate_available_funds(
-                state.total_funds,
-                state.cumulative_admin_withdrawals,
-                state.total_recharge_amount,
-                state.reserve_ratio
-            )?;
-            
-            // Check if requested amount exceeds maximum available with reserve ratio
-            if amount > max_available {
-                return Err(ERROR_INSUFFICIENT_BALANCE);
-            }
-            
-            // Update cumulative withdrawals
-            state.cumulative_admin_withdrawals = safe_add(state.cumulative_admin_withdrawals, amount)?;
-            state.txcounter
-        };
-        
-        // Emit admin withdrawal event
-        emit_admin_withdrawal_event(*pid, amount, txid, counter);
-        
-        // Get pre-parsed multisig address parts to avoid trace-expensive parsing
-        let (first, middle, last) = crate::config::get_multisig_address_parts();
-        
-        // Create withdrawal info to multisig address (token index 0 for USDT)
-        let withdrawinfo = WithdrawInfo::new(&[first, middle, last], 0);
-        SettlementInfo::append_settlement(withdrawinfo);
-        
-        admin.store();
-        Ok(())
     }
+    if let Some(principal) = settled.principal {
+        emit_principal_redemption_event(*pid, cert_id, principal, txid, counter);
+    }
+    // Keep the indexed certificate in step with the claimed total and status
+    emit_certificate_snapshot(pid, cert_id)
 }
 
-impl CommandHandler for SetReserveRatio {
+/// Interest crediting for claims and redemptions: the payout and its booster bonus are credited and recorded
+/// Returns the bonus; the player is only changed once the global counters accept the credit
+pub fn apply_interest_credit(state: &mut GlobalState, data: &mut PlayerData, payout: Payout, counter: u64) -> Result<u64, u32> {
+    let (credited, bonus) = interest_credit(data, state, payout, counter)?;
+    record_interest_credit(state, payout, bonus)?;
+    *data = credited;
+    Ok(bonus)
+}
+
+/// The global side of a credit worked out by `interest_credit`
+fn record_interest_credit(state: &mut GlobalState, (amount, currency): Payout, bonus: u64) -> Result<(), u32> {
+    if currency == PRINCIPAL_CURRENCY_POINTS {
+        // Interest paid in points is outside the fund accounting, only the issuance is tracked
+        state.record_points_interest(amount)
+    } else {
+        // Update global statistics - only track interest claimed, don't add to total_funds
+        state.record_interest_claim(amount, bonus)
+    }
+}
+
+/// A player's balances after crediting `payout` with the booster bonus it earns, and that bonus
+/// Worked out on a copy with the global counters checked too, so a credit that passes cannot fail when recorded
+fn interest_credit(data: &PlayerData, state: &GlobalState, (amount, currency): Payout, counter: u64) -> Result<(PlayerData, u64), u32> {
+    let mut credited = data.clone();
+    if currency == PRINCIPAL_CURRENCY_POINTS {
+        credited.credit_principal(currency, amount)?;
+        state.check_points_interest(amount)?;
+        return Ok((credited, 0));
+    }
+    // Promotional booster bonus on top of the earned interest
+    let bonus = state.booster_bonus(amount, counter)?;
+    credited.add_idle_funds(safe_add(amount, bonus)?)?;
+    credited.lifetime_interest_claimed = safe_add(credited.lifetime_interest_claimed, amount)?;
+    state.check_interest_claim(amount, bonus)?;
+    Ok((credited, bonus))
+}
+
+#[derive(Clone)]
+pub struct ClaimInterest {
+    pub certificate_id: u64,
+    pub amount: u64, // Interest to claim, 0 = all available
+}
+
+impl CommandHandler for ClaimInterest {
     fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
-        // Verify admin permissions (this should be checked in state.rs)
-        let mut admin = StakingPlayer::get_from_pid(pid).unwrap();
-        admin.check_and_inc_nonce(nonce);
-        
-        let reserve_ratio = self.reserve_ratio;
+        // Get txid from global state early
+        let txid = with_state(|state| state.txcounter);
         
-        // Validate reserve ratio
-        if !crate::config::validate_reserve_ratio(reserve_ratio) {
-            return Err(ERROR_INVALID_STAKE_AMOUNT); // Reuse existing error code
+        let mut player = StakingPlayer::get_from_pid(pid);
+        match player.as_mut() {
+            None => Err(ERROR_PLAYER_NOT_EXIST),
+            Some(player) => {
+                player.try_check_and_inc_nonce(nonce)?;
+                
+                let settled = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
+                emit_settled(pid, self.certificate_id, &settled, txid, counter)?;
+                
+                player.store();
+                Ok(())
+            }
         }
-        
-        // Update reserve ratio and get old ratio for event
-        let old_ratio = {
-            let mut state = GLOBAL_STATE.0.borrow_mut();
-            let old_ratio = state.reserve_ratio;
-            state.reserve_ratio = reserve_ratio;
-            old_ratio
-        };
-        
-        // Emit reserve ratio change event
-        emit_reserve_ratio_change_event(*pid, old_ratio, reserve_ratio, counter);
-        
-        admin.store();
-        Ok(())
     }
 }
 
-
+impl ClaimInterest {
+    /// ClaimInterest::handle's steps on the loaded player
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, pid: &[u64; 2], data: &mut PlayerData) -> Result<Settled, u32> {
+        // Certificate system: Claim the requested or all available interest (no external claim)
+        let claim = CertificateManager::prepare_claim(state, storage, pid, self.certificate_id, self.amount)?;
+        settle_claim(state, storage, data, &claim)
+    }
+}
+
+/// Record a prepared claim and credit it, optionally returning a matured certificate's
+/// principal in the same transaction
+/// A failed transaction is still committed, so the credits are worked out on a copy of the
+/// player before the claim is recorded
+fn settle_claim<S: Storage>(
+    state: &mut GlobalState,
+    storage: &mut S,
+    data: &mut PlayerData,
+    claim: &PreparedClaim
+) -> Result<Settled, u32> {
+    let (mut credited, bonus) = interest_credit(data, state, claim.payout, state.counter)?;
+    
+    // A principal the balance cannot take fails the claim as well, the certificate stays open
+    let closed = if state.auto_redeem_on_claim {
+        CertificateManager::prepare_auto_redeem(&claim.certificate, state)?
+    } else {
+        None
+    };
+    if let Some(cert) = &closed {
+        credited.credit_redeemed_principal(cert.principal_currency, cert.principal)?;
+    }
+    
+    let payout = CertificateManager::commit_claim(state, storage, claim)?;
+    record_interest_credit(state, payout, bonus)?;
+    if let Some(cert) = &closed {
+        CertificateManager::commit_auto_redeem(state, storage, cert);
+    }
+    *data = credited;
+    Ok(Settled { interest: payout.0, bonus, principal: closed.map(|cert| cert.principal) })
+}
+
+/// Tick maintenance: claim interest for up to MAX_AUTO_CLAIMS_PER_TICK opted-in certificates
+///
+/// One that cannot claim yet (claim minimum, tick budget, recharge reserve) or whose interest could
+/// not be credited waits for the next round; nothing is stored for it
+pub fn run_auto_claims() {
+    let (txid, counter) = with_state(|state| (state.txcounter, state.counter));
+    let (paid, players) = with_state_mut(|state| claim_due_interest(state, &mut MerkleStorage, StakingPlayer::get_from_pid));
+    for player in players {
+        player.store();
+    }
+    for (owner, cert_id, settled) in paid {
+        let _ = emit_settled(&owner, cert_id, &settled, txid, counter);
+    }
+}
+
+/// An auto-claim pass: what each paid (owner, certificate) settled, and the players to store
+pub type AutoClaims = (Vec<([u64; 2], u64, Settled)>, Vec<StakingPlayer>);
+
+/// run_auto_claims' steps, loading owners through `load_player` once per pass
+pub fn claim_due_interest<S, L>(state: &mut GlobalState, storage: &mut S, mut load_player: L) -> AutoClaims
+where
+    S: Storage,
+    L: FnMut(&[u64; 2]) -> Option<StakingPlayer>,
+{
+    let (mut paid, mut players): (Vec<_>, Vec<StakingPlayer>) = (vec![], vec![]);
+    // Users cannot claim while the system is paused, neither does the pass
+    if state.paused {
+        return (paid, players);
+    }
+    for (owner, cert_id) in CertificateManager::next_auto_claims(state, storage, crate::config::MAX_AUTO_CLAIMS_PER_TICK) {
+        // An owner with several due certificates is credited on the copy this pass already updated
+        let index = match players.iter().position(|player| player.player_id == owner) {
+            Some(index) => index,
+            None => match load_player(&owner) {
+                Some(player) => {
+                    players.push(player);
+                    players.len() - 1
+                }
+                None => continue,
+            },
+        };
+        let data = &mut players[index].data;
+        if let Ok(claim) = CertificateManager::prepare_claim(state, storage, &owner, cert_id, 0) {
+            // A claim whose credit or auto-redeem cannot go through stores nothing
+            if let Ok(settled) = settle_claim(state, storage, data, &claim) {
+                paid.push((owner, cert_id, settled));
+            }
+        }
+    }
+    (paid, players)
+}
+
+/// Re-emit a certificate's indexed object from storage after a claim, redemption or transfer,
+/// so indexers always hold the latest snapshot rather than the one taken at purchase
+fn emit_certificate_snapshot(owner: &[u64; 2], cert_id: u64) -> Result<(), u32> {
+    emit_certificate_indexed_object(&CertificateManager::validate_certificate_ownership(owner, cert_id)?);
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct ClaimInterestToTarget {
+    pub certificate_id: u64,
+    pub target_remaining: u64, // Available interest to leave unclaimed, 0 = claim all
+}
+
+impl CommandHandler for ClaimInterestToTarget {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Get txid from global state early
+        let txid = with_state(|state| state.txcounter);
+        
+        let mut player = StakingPlayer::get_from_pid(pid);
+        match player.as_mut() {
+            None => Err(ERROR_PLAYER_NOT_EXIST),
+            Some(player) => {
+                player.try_check_and_inc_nonce(nonce)?;
+                
+                let settled = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
+                emit_settled(pid, self.certificate_id, &settled, txid, counter)?;
+                
+                player.store();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ClaimInterestToTarget {
+    /// ClaimInterestToTarget::handle's steps on the loaded player
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, pid: &[u64; 2], data: &mut PlayerData) -> Result<Settled, u32> {
+        let claim = CertificateManager::prepare_claim_to_target(state, storage, pid, self.certificate_id, self.target_remaining)?;
+        settle_claim(state, storage, data, &claim)
+    }
+}
+
+/// Record a prepared redemption and credit what it pays out, the interest with its booster bonus
+/// and then the principal
+/// Both credits are worked out on a copy of the player first, so a payout the balance cannot
+/// take leaves the certificate open
+fn settle_redemption<S: Storage>(
+    state: &mut GlobalState,
+    storage: &mut S,
+    data: &mut PlayerData,
+    redemption: &PreparedRedemption
+) -> Result<Settled, u32> {
+    let (interest, (principal, currency)) = (redemption.interest, redemption.principal);
+    // Nothing left to claim is fine, the principal is still returned
+    let (mut credited, bonus) = if interest.0 > 0 {
+        interest_credit(data, state, interest, state.counter)?
+    } else {
+        (data.clone(), 0)
+    };
+    credited.credit_redeemed_principal(currency, principal)?;
+    
+    CertificateManager::commit_redemption(state, storage, redemption)?;
+    if interest.0 > 0 {
+        record_interest_credit(state, interest, bonus)?;
+    }
+    *data = credited;
+    Ok(Settled { interest: interest.0, bonus, principal: Some(principal) })
+}
+
+#[derive(Clone)]
+pub struct RedeemPrincipal {
+    pub certificate_id: u64,
+}
+
+impl CommandHandler for RedeemPrincipal {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Get txid from global state early
+        let txid = with_state(|state| state.txcounter);
+        
+        let mut player = StakingPlayer::get_from_pid(pid);
+        match player.as_mut() {
+            None => Err(ERROR_PLAYER_NOT_EXIST),
+            Some(player) => {
+                player.try_check_and_inc_nonce(nonce)?;
+                
+                let settled = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
+                
+                // Emit principal redemption event
+                emit_settled(pid, self.certificate_id, &settled, txid, counter)?;
+                
+                player.store();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl RedeemPrincipal {
+    /// RedeemPrincipal::handle's steps on the loaded player
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, pid: &[u64; 2], data: &mut PlayerData) -> Result<Settled, u32> {
+        // Certificate system: Redeem principal to idle funds (no external withdrawal)
+        // Interest accrued up to maturity and not yet claimed is paid out first,
+        // otherwise it would be lost once the certificate is redeemed
+        let redemption = CertificateManager::prepare_redeem_all(state, storage, pid, self.certificate_id)?;
+        
+        // Principal is returned to user's idle funds or points
+        // No changes to total_funds needed as money stays in system
+        settle_redemption(state, storage, data, &redemption)
+    }
+}
+
+#[derive(Clone)]
+pub struct RedeemAll {
+    pub certificate_id: u64,
+}
+
+impl CommandHandler for RedeemAll {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Get txid from global state early
+        let txid = with_state(|state| state.txcounter);
+        
+        let mut player = StakingPlayer::get_from_pid(pid);
+        match player.as_mut() {
+            None => Err(ERROR_PLAYER_NOT_EXIST),
+            Some(player) => {
+                player.try_check_and_inc_nonce(nonce)?;
+                
+                let settled = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
+                emit_settled(pid, self.certificate_id, &settled, txid, counter)?;
+                
+                player.store();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl RedeemAll {
+    /// RedeemAll::handle's steps on the loaded player
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, pid: &[u64; 2], data: &mut PlayerData) -> Result<Settled, u32> {
+        let redemption = CertificateManager::prepare_redeem_all(state, storage, pid, self.certificate_id)?;
+        settle_redemption(state, storage, data, &redemption)
+    }
+}
+
+#[derive(Clone)]
+pub struct RedeemEarly {
+    pub certificate_id: u64,
+}
+
+impl CommandHandler for RedeemEarly {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Get txid from global state early
+        let txid = with_state(|state| state.txcounter);
+        
+        let mut player = StakingPlayer::get_from_pid(pid);
+        match player.as_mut() {
+            None => Err(ERROR_PLAYER_NOT_EXIST),
+            Some(player) => {
+                player.try_check_and_inc_nonce(nonce)?;
+                
+                let settled = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
+                emit_settled(pid, self.certificate_id, &settled, txid, counter)?;
+                
+                player.store();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl RedeemEarly {
+    /// RedeemEarly::handle's steps on the loaded player
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, pid: &[u64; 2], data: &mut PlayerData) -> Result<Settled, u32> {
+        // Only the interest left after the penalty is credited
+        let redemption = CertificateManager::prepare_redeem_early(state, storage, pid, self.certificate_id)?;
+        settle_redemption(state, storage, data, &redemption)
+    }
+}
+
+#[derive(Clone)]
+pub struct RolloverCertificate {
+    pub certificate_id: u64,
+}
+
+impl CommandHandler for RolloverCertificate {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Get txid from global state early
+        let txid = with_state(|state| state.txcounter);
+        
+        let mut player = StakingPlayer::get_from_pid(pid);
+        match player.as_mut() {
+            None => Err(ERROR_PLAYER_NOT_EXIST),
+            Some(player) => {
+                player.try_check_and_inc_nonce(nonce)?;
+                
+                let (settled, certificate) = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
+                emit_settled(pid, self.certificate_id, &settled, txid, counter)?;
+                emit_certificate_indexed_object(&certificate);
+                emit_certificate_purchase_event(*pid, certificate.id, certificate.product_type_id, certificate.principal, txid, counter);
+                
+                player.store();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl RolloverCertificate {
+    /// RolloverCertificate::handle's steps on the loaded player, returning how the old
+    /// certificate settled and the new certificate
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, pid: &[u64; 2], data: &mut PlayerData) -> Result<(Settled, Certificate), u32> {
+        let cert_id = self.certificate_id;
+        
+        // The repurchase is validated before the old certificate is redeemed
+        let (product_type, amount) = CertificateManager::prepare_rollover(state, storage, pid, data, cert_id)?;
+        let product_type_id = product_type.id;
+        // Rolling into recharge moves the funds into recharge funding, they must fit
+        if product_type_id == 0 {
+            safe_sub(state.total_funds, amount)?;
+        }
+        
+        // The new certificate keeps the old one's auto-claim opt-in
+        let auto_claim = CertificateManager::validate_certificate_ownership_with(|key| storage.get(key), pid, cert_id)?.auto_claim;
+        
+        // Redeem as RedeemAll does, crediting any booster bonus to idle funds
+        let redemption = CertificateManager::prepare_redeem_all(state, storage, pid, cert_id)?;
+        let (_, currency) = redemption.principal;
+        let settled = settle_redemption(state, storage, data, &redemption)?;
+        
+        // Principal plus interest buys the new certificate at the product's current APY
+        // The funds come straight from a certificate, so the deposit maturation delay does not apply
+        // It is prepared again so the product counts the principal the redemption released
+        apply_purchase(state, data, product_type_id, currency, amount)?;
+        let certificate = CertificateManager::purchase_certificate(state, storage, *pid, data, product_type_id, amount, auto_claim)?;
+        Ok((settled, certificate))
+    }
+}
+
+#[derive(Clone)]
+pub struct RedeemPrincipalPartial {
+    pub certificate_id: u64,
+    pub amount: u64,
+}
+
+impl CommandHandler for RedeemPrincipalPartial {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Get txid from global state early
+        let txid = with_state(|state| state.txcounter);
+        
+        let mut player = StakingPlayer::get_from_pid(pid);
+        match player.as_mut() {
+            None => Err(ERROR_PLAYER_NOT_EXIST),
+            Some(player) => {
+                player.try_check_and_inc_nonce(nonce)?;
+                
+                let cert_id = self.certificate_id;
+                let amount = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
+                
+                // Emit principal redemption event for the redeemed part
+                emit_principal_redemption_event(*pid, cert_id, amount, txid, counter);
+                emit_certificate_snapshot(pid, cert_id)?;
+                
+                player.store();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl RedeemPrincipalPartial {
+    /// RedeemPrincipalPartial::handle's steps on the loaded player, returning the amount redeemed
+    pub fn apply<S: Storage>(&self, state: &mut GlobalState, storage: &mut S, pid: &[u64; 2], data: &mut PlayerData) -> Result<u64, u32> {
+        // Return the redeemed part to idle funds or points, checked before the certificate changes
+        let cert = CertificateManager::validate_certificate_ownership_with(|key| storage.get(key), pid, self.certificate_id)?;
+        let mut credited = data.clone();
+        credited.credit_redeemed_principal(cert.principal_currency, self.amount)?;
+        
+        let (amount, _) = CertificateManager::redeem_principal_partial(state, storage, pid, self.certificate_id, self.amount)?;
+        *data = credited;
+        Ok(amount)
+    }
+}
+
+#[derive(Clone)]
+pub struct TransferCertificate {
+    pub certificate_id: u64,
+    pub new_owner: [u64; 2],
+}
+
+impl CommandHandler for TransferCertificate {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Get txid from global state early
+        let txid = with_state(|state| state.txcounter);
+        
+        let mut player = StakingPlayer::get_from_pid(pid);
+        match player.as_mut() {
+            None => Err(ERROR_PLAYER_NOT_EXIST),
+            Some(player) => {
+                player.try_check_and_inc_nonce(nonce)?;
+                
+                // The recipient must be an installed player to act on the certificate later
+                let mut recipient = StakingPlayer::get_from_pid(&self.new_owner).ok_or(ERROR_PLAYER_NOT_EXIST)?;
+                let certificate = with_state_mut(|state| {
+                    self.apply(state, &mut MerkleStorage, pid, &mut player.data, &mut recipient.data)
+                })?;
+                
+                // Re-index under the new owner, then record the transfer itself
+                emit_certificate_indexed_object(&certificate);
+                emit_certificate_transfer_event(*pid, self.new_owner, self.certificate_id, certificate.id, txid, counter);
+                
+                recipient.store();
+                player.store();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl TransferCertificate {
+    /// TransferCertificate::handle's steps on the loaded sender and recipient, returning the
+    /// certificate as it is stored under the new owner
+    pub fn apply<S: Storage>(
+        &self,
+        state: &mut GlobalState,
+        storage: &mut S,
+        pid: &[u64; 2],
+        data: &mut PlayerData,
+        recipient: &mut PlayerData
+    ) -> Result<Certificate, u32> {
+        // The sender pays the transfer fee from idle funds into the reserve pool
+        let fee = state.transfer_fee;
+        data.spend_idle_funds(fee)?;
+        state.check_fee(fee)?;
+        
+        // The certificate takes the recipient's next id
+        let certificate = CertificateManager::transfer_certificate(state, storage, pid, self.certificate_id, self.new_owner, recipient)?;
+        state.record_fee(fee)?;
+        Ok(certificate)
+    }
+}
+
+#[derive(Clone)]
+pub struct AdminWithdrawToMultisig {
+    pub amount: u64, // Amount to withdraw to multisig address
+}
+
+#[derive(Clone)]
+pub struct SetReserveRatio {
+    pub reserve_ratio: u64, // Reserve ratio in basis points (e.g., 1000 = 10%)
+}
+
+impl CommandHandler for AdminWithdrawToMultisig {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Verify admin permissions (this should be checked in state.rs)
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let txid = with_state(|state| state.txcounter);
+        let withdrawinfo = with_state_mut(|state| self.apply(state))?;
+        
+        // Emit admin withdrawal event
+        emit_admin_withdrawal_event(*pid, self.amount, txid, counter);
+        SettlementInfo::append_settlement(withdrawinfo);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+impl AdminWithdrawToMultisig {
+    /// AdminWithdrawToMultisig::handle's accounting, returning the settlement to queue
+    pub fn apply(&self, state: &mut GlobalState) -> Result<WithdrawInfo, u32> {
+        let amount = self.amount;
+        
+        // Validate amount is not zero
+        if amount == 0 {
+            return Err(ERROR_INVALID_STAKE_AMOUNT);
+        }
+        
+        // Withdrawal info to the pre-parsed multisig address (token index 0 for USDT)
+        let withdrawinfo = multisig_withdraw_info(amount)?;
+        
+        // Rejects amounts above the reserve-ratio limit, then updates cumulative withdrawals
+        state.record_admin_withdrawal(amount)?;
+        Ok(withdrawinfo)
+    }
+}
+
+impl CommandHandler for SetReserveRatio {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Verify admin permissions (this should be checked in state.rs)
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let old_ratio = with_state_mut(|state| self.apply(state, counter))?;
+        
+        admin.store();
+        
+        // Emit reserve ratio change event
+        emit_reserve_ratio_change_event(*pid, old_ratio, self.reserve_ratio, counter);
+        Ok(())
+    }
+}
+
+impl SetReserveRatio {
+    /// SetReserveRatio::handle's update at `counter`, returning the old ratio
+    pub fn apply(&self, state: &mut GlobalState, counter: u64) -> Result<u64, u32> {
+        let reserve_ratio = self.reserve_ratio;
+        
+        // Validate reserve ratio
+        if !crate::config::validate_reserve_ratio(reserve_ratio) {
+            return Err(ERROR_INVALID_STAKE_AMOUNT); // Reuse existing error code
+        }
+        
+        // Update reserve ratio and get old ratio for event (the floor and cooldown are checked first)
+        state.set_reserve_ratio(reserve_ratio, counter)
+    }
+}
+
+#[derive(Clone)]
+pub struct SetPayoutsPaused {
+    pub paused: bool, // Whether interest payouts are paused
+}
+
+impl CommandHandler for SetPayoutsPaused {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Verify admin permissions (this should be checked in state.rs)
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let pause_start_tick = with_state_mut(|state| {
+            state.set_payouts_paused(self.paused);
+            state.pause_start_tick
+        });
+        
+        // Emit payouts paused event
+        emit_payouts_paused_event(*pid, self.paused, pause_start_tick, counter);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct SetPaused {
+    pub paused: bool, // Whether non-admin commands are halted
+}
+
+impl CommandHandler for SetPaused {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        with_state_mut(|state| state.paused = self.paused);
+        
+        emit_system_paused_event(*pid, self.paused, counter);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct AdminAdjustRecharge {
+    pub delta: i64, // Signed correction to total_recharge_amount
+}
+
+impl CommandHandler for AdminAdjustRecharge {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let (old_amount, new_amount) = with_state_mut(|state| state.adjust_recharge(self.delta))?;
+        
+        emit_recharge_adjusted_event(*pid, self.delta, old_amount, new_amount, counter);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct AdminRepay {
+    pub amount: u64, // Amount returned against cumulative_admin_withdrawals
+}
+
+impl CommandHandler for AdminRepay {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let (old_withdrawals, new_withdrawals) = with_state_mut(|state| self.apply(state))?;
+        
+        emit_admin_repayment_event(*pid, self.amount, old_withdrawals, new_withdrawals, counter);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+impl AdminRepay {
+    /// AdminRepay::handle's accounting, returning the borrowed amount before and after
+    pub fn apply(&self, state: &mut GlobalState) -> Result<(u64, u64), u32> {
+        if self.amount == 0 {
+            return Err(ERROR_INVALID_STAKE_AMOUNT);
+        }
+        Ok(state.record_admin_repayment(self.amount))
+    }
+}
+
+#[derive(Clone)]
+pub struct GrantRole {
+    pub admin: [u64; 2], // Pid receiving the roles
+    pub roles: u64,      // ROLE_* bitmask to add
+}
+
+impl CommandHandler for GrantRole {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let (old_roles, new_roles) = with_state_mut(|state| state.grant_roles(self.admin, self.roles))?;
+        
+        emit_role_changed_event(*pid, self.admin, old_roles, new_roles, counter);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct RevokeRole {
+    pub admin: [u64; 2], // Pid losing the roles
+    pub roles: u64,      // ROLE_* bitmask to remove
+}
+
+impl CommandHandler for RevokeRole {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let (old_roles, new_roles) = with_state_mut(|state| state.revoke_roles(self.admin, self.roles))?;
+        
+        emit_role_changed_event(*pid, self.admin, old_roles, new_roles, counter);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct SetGlobalParam {
+    pub param: u64, // Parameter id (see PARAM_* in config.rs)
+    pub value: u64, // New value
+}
+
+impl CommandHandler for SetGlobalParam {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Verify admin permissions (this should be checked in state.rs)
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let old_value = with_state_mut(|state| state.set_param(self.param, self.value))?;
+        
+        // Emit global param changed event
+        emit_global_param_changed_event(*pid, self.param, old_value, self.value, counter);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct WithdrawReservePool {
+    pub amount: u64, // Amount of protocol revenue to withdraw to multisig
+}
+
+impl CommandHandler for WithdrawReservePool {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Verify admin permissions (this should be checked in state.rs)
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let txid = with_state(|state| state.txcounter);
+        let withdrawinfo = with_state_mut(|state| self.apply(state))?;
+        
+        // Emit reserve pool withdrawal event
+        emit_reserve_pool_withdrawal_event(*pid, self.amount, txid, counter);
+        SettlementInfo::append_settlement(withdrawinfo);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+impl WithdrawReservePool {
+    /// WithdrawReservePool::handle's accounting, returning the settlement to queue
+    pub fn apply(&self, state: &mut GlobalState) -> Result<WithdrawInfo, u32> {
+        let amount = self.amount;
+        if amount == 0 {
+            return Err(ERROR_INVALID_STAKE_AMOUNT);
+        }
+        
+        // Revenue goes to the same multisig as admin withdrawals
+        let withdrawinfo = multisig_withdraw_info(amount)?;
+        state.record_reserve_pool_withdrawal(amount)?;
+        Ok(withdrawinfo)
+    }
+}
+
+#[derive(Clone)]
+pub struct WithdrawRoundingSurplus {
+    pub amount: u64, // Whole units of rounding surplus to withdraw to multisig
+}
+
+impl CommandHandler for WithdrawRoundingSurplus {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Verify admin permissions (this should be checked in state.rs)
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let txid = with_state(|state| state.txcounter);
+        let withdrawinfo = with_state_mut(|state| self.apply(state))?;
+        
+        emit_rounding_surplus_withdrawal_event(*pid, self.amount, txid, counter);
+        SettlementInfo::append_settlement(withdrawinfo);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+impl WithdrawRoundingSurplus {
+    /// WithdrawRoundingSurplus::handle's accounting, returning the settlement to queue
+    pub fn apply(&self, state: &mut GlobalState) -> Result<WithdrawInfo, u32> {
+        let amount = self.amount;
+        if amount == 0 {
+            return Err(ERROR_INVALID_STAKE_AMOUNT);
+        }
+        
+        let withdrawinfo = multisig_withdraw_info(amount)?;
+        
+        // Only interest the flooring withheld can leave, user balances are untouched
+        state.record_rounding_surplus_withdrawal(amount)?;
+        Ok(withdrawinfo)
+    }
+}
+
+#[derive(Clone)]
+pub struct AdminForceWithdrawPlayer {
+    pub pid: [u64; 2],       // Player whose position is swept
+    pub data: [u64; 3],      // Destination address packed as in Withdraw, the amount bits are filled in
+    pub force_mature: bool,  // Also close unmatured certificates, paying the interest accrued so far
+    pub start: u64,          // Certificate id the sweep resumes from, 0 = the player's first
+}
+
+impl CommandHandler for AdminForceWithdrawPlayer {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Verify admin permissions (this should be checked in state.rs)
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        let mut player = StakingPlayer::get_from_pid(&self.pid).ok_or(ERROR_PLAYER_NOT_EXIST)?;
+        let txid = with_state(|state| state.txcounter);
+        let (swept, amount) = with_state_mut(|state| self.apply(state, &mut MerkleStorage, pid, &mut player.data))?;
+        
+        for (cert_id, settled) in &swept {
+            emit_settled(&self.pid, *cert_id, settled, txid, counter)?;
+        }
+        if amount > 0 {
+            let address_parts = [(self.data[0] & !0xffffffff) | amount, self.data[1], self.data[2]];
+            let withdrawinfo = WithdrawInfo::new(&address_parts, 0);
+            SettlementInfo::append_settlement(withdrawinfo);
+            emit_withdrawal_event(self.pid, amount, address_parts, txid, counter);
+        }
+        
+        player.store();
+        admin.store();
+        Ok(())
+    }
+}
+
+impl AdminForceWithdrawPlayer {
+    /// AdminForceWithdrawPlayer::handle's steps for admin `pid` on the loaded target player
+    /// Returns how each swept certificate settled and the idle funds sent to the address
+    pub fn apply<S: Storage>(
+        &self,
+        state: &mut GlobalState,
+        storage: &mut S,
+        pid: &[u64; 2],
+        data: &mut PlayerData
+    ) -> Result<(Vec<(u64, Settled)>, u64), u32> {
+        // Both copies are stored at the end, an admin sweeping itself would lose the nonce update
+        if self.pid == *pid {
+            return Err(ERROR_INVALID_PARAM);
+        }
+        check_withdraw_address(&self.data)?;
+        let current_time = state.counter;
+        
+        // Work out everything the sweep pays in USDT and check it can leave the system before
+        // any certificate is redeemed, so a failing sweep leaves no partial state
+        // A position spanning more ids than one page is swept in several calls, each resuming
+        // MAX_ITERATE_KEYS ids further on; idle funds are sent out by every call
+        let id_range = data.certificate_id_range(state.certificate_counter);
+        let certificates: Vec<_> = CertificateManager::certificates_page_with(|key| storage.get(key), &self.pid, id_range, self.start)
+            .items
+            .into_iter()
+            .filter(|cert| !matches!(cert.status, crate::certificate::CertificateStatus::Redeemed))
+            .filter(|cert| self.force_mature || cert.is_matured(current_time))
+            .collect();
+        check_sweep(state, data, &certificates, current_time)?;
+        
+        let mut swept = vec![];
+        for cert in &certificates {
+            if !cert.is_matured(current_time) {
+                CertificateManager::force_mature(state, storage, &self.pid, cert.id)?;
+            }
+            let redemption = CertificateManager::prepare_redeem_all(state, storage, &self.pid, cert.id)?;
+            swept.push((cert.id, settle_redemption(state, storage, data, &redemption)?));
+        }
+        
+        // Points stay with the player, only idle funds are sent out
+        let amount = data.idle_funds;
+        if amount > 0 {
+            data.spend_idle_funds(amount)?;
+            state.record_withdrawal(amount)?;
+        }
+        Ok((swept, amount))
+    }
+}
+
+/// AdminForceWithdrawPlayer::handle's total, the idle funds plus everything the swept
+/// `certificates` pay in USDT, checked to be able to leave the system in one settlement
+///
+/// The interest it pays must be backed by the recharge reserve as well
+pub fn check_sweep(state: &GlobalState, data: &PlayerData, certificates: &[Certificate], counter: u64) -> Result<u64, u32> {
+    let claim_time = state.interest_claim_cutoff(state.counter);
+    let (mut total, mut interest_total, mut bonus_total) = (data.idle_funds, 0, 0);
+    for cert in certificates {
+        if cert.principal_currency == PRINCIPAL_CURRENCY_USDT {
+            total = safe_add(total, cert.principal)?;
+        }
+        if cert.interest_currency == PRINCIPAL_CURRENCY_USDT {
+            let interest = cert.calculate_available_interest(claim_time)?;
+            let bonus = state.booster_bonus(interest, counter)?;
+            interest_total = safe_add(interest_total, interest)?;
+            bonus_total = safe_add(bonus_total, bonus)?;
+            total = safe_add(total, safe_add(interest, bonus)?)?;
+        }
+    }
+    state.check_interest_reserve(interest_total, bonus_total)?;
+    state.check_withdrawal(total)?;
+    // The settlement carries the amount in the low 32 bits of the first address word
+    to_u32_checked(total)?;
+    Ok(total)
+}
+
+#[derive(Clone)]
+pub struct SetWhitelistBatch {
+    pub product_type_id: u64,
+    pub users: Vec<[u64; 2]>, // At most MAX_WHITELIST_BATCH users
+    pub add: bool,            // true = add to whitelist, false = remove
+}
+
+impl CommandHandler for SetWhitelistBatch {
+    fn handle(&self, pid: &[u64; 2], nonce: u64, _rand: &[u64; 4], counter: u64) -> Result<(), u32> {
+        // Verify admin permissions (this should be checked in state.rs)
+        let mut admin = load_admin(pid)?;
+        admin.try_check_and_inc_nonce(nonce)?;
+        
+        self.apply(&mut MerkleStorage)?;
+        
+        // One event summarizes the whole batch
+        let txid = with_state(|state| state.txcounter);
+        emit_whitelist_batch_event(*pid, self.product_type_id, self.add, self.users.len() as u64, txid, counter);
+        
+        admin.store();
+        Ok(())
+    }
+}
+
+impl SetWhitelistBatch {
+    /// SetWhitelistBatch::handle's checks and writes
+    pub fn apply<S: Storage>(&self, storage: &mut S) -> Result<(), u32> {
+        if self.users.is_empty() || self.users.len() > crate::config::MAX_WHITELIST_BATCH {
+            return Err(ERROR_INVALID_PARAM);
+        }
+        ProductTypeManager::get_product_type_with(|key| storage.get(key), self.product_type_id)
+            .ok_or(ERROR_PRODUCT_TYPE_NOT_EXIST)?;
+        
+        for user in &self.users {
+            WhitelistManager::set_whitelisted(storage, self.product_type_id, user, self.add);
+        }
+        Ok(())
+    }
+}
+
+/// Every error code a command can fail with, in code order
+pub const ERROR_CODES: &[u32] = &[
+    ERROR_PLAYER_NOT_EXIST, ERROR_PLAYER_ALREADY_EXIST, ERROR_INSUFFICIENT_BALANCE, ERROR_INVALID_NONCE,
+    ERROR_OVERFLOW, ERROR_DIVISION_BY_ZERO, ERROR_UNDERFLOW, ERROR_COUNTER_OVERFLOW,
+    ERROR_INSUFFICIENT_STAKE, ERROR_INVALID_STAKE_AMOUNT, ERROR_STAKE_TOO_SMALL, ERROR_STAKE_TOO_LARGE,
+    ERROR_NO_STAKE_TO_WITHDRAW, ERROR_WITHDRAW_TOO_EARLY, ERROR_USER_WITHDRAW_LIMIT,
+    ERROR_INSUFFICIENT_POINTS, ERROR_POINTS_CONVERSION_DISABLED, ERROR_INVALID_POINTS_AMOUNT, ERROR_POINTS_AMOUNT_TOO_SMALL,
+    ERROR_PRODUCT_TYPE_NOT_EXIST, ERROR_PRODUCT_TYPE_INACTIVE, ERROR_CERTIFICATE_NOT_EXIST,
+    ERROR_CERTIFICATE_NOT_OWNED, ERROR_CERTIFICATE_NOT_MATURED, ERROR_CERTIFICATE_ALREADY_REDEEMED,
+    ERROR_INSUFFICIENT_INTEREST, ERROR_INVALID_PRINCIPAL_AMOUNT, ERROR_PRINCIPAL_AMOUNT_TOO_SMALL,
+    ERROR_INVALID_APY, ERROR_INVALID_DURATION, ERROR_FUNDS_NOT_MATURED, ERROR_INVALID_COMPOUNDING,
+    ERROR_INVALID_PRINCIPAL_CURRENCY, ERROR_NOT_WHITELISTED, ERROR_CERTIFICATE_SAME_OWNER,
+    ERROR_UNCLAIMED_INTEREST, ERROR_APY_CHANGED, ERROR_PRODUCT_CAP_EXCEEDED, ERROR_INVALID_INTEREST_CURRENCY,
+    ERROR_INVALID_PARAM, ERROR_UNKNOWN_COMMAND, ERROR_ADMIN_ONLY, ERROR_ADMIN_NOT_INSTALLED,
+    ERROR_SYSTEM_PAUSED, ERROR_RECONCILE_MODE_DISABLED, ERROR_ADMIN_REGISTRY_FULL, ERROR_INVALID_ADDRESS,
+    ERROR_INVALID_WITHDRAW_ADDRESS, ERROR_EARLY_WITHDRAWAL_DISABLED, ERROR_CLAIM_TOO_SMALL,
+    ERROR_PRODUCT_NON_INTEREST_BEARING, ERROR_PRODUCT_CERT_LIMIT, ERROR_INVALID_CLAIM_TARGET,
+    ERROR_INTEREST_TICK_BUDGET, ERROR_RESERVE_RATIO_TOO_LOW, ERROR_RESERVE_RATIO_CHANGE_TOO_SOON,
+    ERROR_ACCRUAL_PAUSE_LIMIT, ERROR_IDLE_FUNDS_NOT_DUST, ERROR_CLAIM_TOO_SOON, ERROR_INVALID_CERTIFICATE_STATUS,
+];
+
+/// Error group derived from the code ranges in error.rs
+pub fn error_category(e: u32) -> &'static str {
+    match e {
+        1..=10 => "player",
+        11..=20 => "security",
+        21..=30 => "staking",
+        31..=50 => "points",
+        51..=70 => "certificate",
+        71..=90 => "admin",
+        91..=110 => "certificate",
+        _ => "unknown",
+    }
+}
+
 pub fn decode_error(e: u32) -> &'static str {
     match e {
         ERROR_PLAYER_NOT_EXIST => "PlayerNotExist",
         ERROR_PLAYER_ALREADY_EXIST => "PlayerAlreadyExist",
         ERROR_INSUFFICIENT_BALANCE => "InsufficientBalance",
+        ERROR_INVALID_NONCE => "InvalidNonce",
         ERROR_INSUFFICIENT_STAKE => "InsufficientStake",
         ERROR_INVALID_STAKE_AMOUNT => "InvalidStakeAmount",
         ERROR_STAKE_TOO_SMALL => "StakeTooSmall",
         ERROR_STAKE_TOO_LARGE => "StakeTooLarge",
         ERROR_NO_STAKE_TO_WITHDRAW => "NoStakeToWithdraw",
         ERROR_WITHDRAW_TOO_EARLY => "WithdrawTooEarly",
+        ERROR_USER_WITHDRAW_LIMIT => "UserWithdrawLimit",
         ERROR_OVERFLOW => "MathOverflow",
         ERROR_UNDERFLOW => "MathUnderflow",
+        ERROR_COUNTER_OVERFLOW => "CounterOverflow",
         ERROR_DIVISION_BY_ZERO => "DivisionByZero",
         ERROR_INSUFFICIENT_POINTS => "InsufficientPoints",
+        ERROR_POINTS_CONVERSION_DISABLED => "PointsConversionDisabled",
         ERROR_INVALID_POINTS_AMOUNT => "InvalidPointsAmount",
         ERROR_POINTS_AMOUNT_TOO_SMALL => "PointsAmountTooSmall",
         // Certificate system errors
@@ -517,6 +1803,1939 @@ pub fn decode_error(e: u32) -> &'static str {
         ERROR_PRINCIPAL_AMOUNT_TOO_SMALL => "PrincipalAmountTooSmall",
         ERROR_INVALID_APY => "InvalidApy",
         ERROR_INVALID_DURATION => "InvalidDuration",
+        ERROR_FUNDS_NOT_MATURED => "FundsNotMatured",
+        ERROR_INVALID_COMPOUNDING => "InvalidCompounding",
+        ERROR_INVALID_PRINCIPAL_CURRENCY => "InvalidPrincipalCurrency",
+        ERROR_NOT_WHITELISTED => "NotWhitelisted",
+        ERROR_CERTIFICATE_SAME_OWNER => "CertificateSameOwner",
+        ERROR_UNCLAIMED_INTEREST => "UnclaimedInterest",
+        ERROR_APY_CHANGED => "ApyChanged",
+        ERROR_PRODUCT_CAP_EXCEEDED => "ProductCapExceeded",
+        ERROR_INVALID_INTEREST_CURRENCY => "InvalidInterestCurrency",
+        // Admin and system errors
+        ERROR_INVALID_PARAM => "InvalidParam",
+        ERROR_UNKNOWN_COMMAND => "UnknownCommand",
+        ERROR_ADMIN_ONLY => "AdminOnly",
+        ERROR_ADMIN_NOT_INSTALLED => "AdminNotInstalled",
+        ERROR_SYSTEM_PAUSED => "SystemPaused",
+        ERROR_RECONCILE_MODE_DISABLED => "ReconcileModeDisabled",
+        ERROR_ADMIN_REGISTRY_FULL => "AdminRegistryFull",
+        ERROR_INVALID_ADDRESS => "InvalidAddress",
+        ERROR_INVALID_WITHDRAW_ADDRESS => "InvalidWithdrawAddress",
+        ERROR_EARLY_WITHDRAWAL_DISABLED => "EarlyWithdrawalDisabled",
+        ERROR_CLAIM_TOO_SMALL => "ClaimTooSmall",
+        ERROR_PRODUCT_NON_INTEREST_BEARING => "ProductNonInterestBearing",
+        ERROR_PRODUCT_CERT_LIMIT => "ProductCertLimit",
+        ERROR_INVALID_CLAIM_TARGET => "InvalidClaimTarget",
+        ERROR_INTEREST_TICK_BUDGET => "InterestTickBudget",
+        ERROR_RESERVE_RATIO_TOO_LOW => "ReserveRatioTooLow",
+        ERROR_RESERVE_RATIO_CHANGE_TOO_SOON => "ReserveRatioChangeTooSoon",
+        ERROR_ACCRUAL_PAUSE_LIMIT => "AccrualPauseLimit",
+        ERROR_IDLE_FUNDS_NOT_DUST => "IdleFundsNotDust",
+        ERROR_CLAIM_TOO_SOON => "ClaimTooSoon",
+        ERROR_INVALID_CERTIFICATE_STATUS => "InvalidCertificateStatus",
         _ => "Unknown",
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TICKS_PER_DAY;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_admin_not_installed_is_a_recoverable_error() {
+        // Admin handlers surface a missing admin player as an error code instead of panicking
+        let admin = [748, 1];
+        assert_eq!(load_admin_with(|_| None, &admin).err(), Some(ERROR_ADMIN_NOT_INSTALLED));
+        let found = load_admin_with(|pid| Some(StakingPlayer::new_from_pid(*pid)), &admin).unwrap();
+        assert_eq!(found.player_id, admin);
+        assert_eq!(decode_error(ERROR_ADMIN_NOT_INSTALLED), "AdminNotInstalled");
+    }
+
+    #[test]
+    fn test_error_table_is_named_and_categorized() {
+        assert_eq!(error_category(ERROR_NOT_WHITELISTED), "certificate");
+        assert_eq!(error_category(ERROR_OVERFLOW), "security");
+        assert_eq!(error_category(ERROR_PLAYER_NOT_EXIST), "player");
+        assert_eq!(error_category(ERROR_ADMIN_ONLY), "admin");
+        assert!(ERROR_CODES.windows(2).all(|w| w[0] < w[1]));
+        assert!(ERROR_CODES.iter().all(|&e| decode_error(e) != "Unknown" && error_category(e) != "unknown"));
+    }
+
+    #[test]
+    fn test_interest_credit_that_cannot_settle_is_rejected_up_front() {
+        let mut state = GlobalState::new();
+        let mut data = PlayerData::new();
+        data.idle_funds = u64::MAX - 5;
+
+        // Interest the recharge funding does not back is refused
+        assert_eq!(interest_credit(&data, &state, (5, PRINCIPAL_CURRENCY_USDT), 0).err(), Some(ERROR_INSUFFICIENT_INTEREST));
+        state.total_recharge_amount = 10;
+
+        // The tick pass checks this before storing the claim, an overflowing credit stores nothing
+        assert_eq!(interest_credit(&data, &state, (10, PRINCIPAL_CURRENCY_USDT), 0).err(), Some(ERROR_OVERFLOW));
+        let (credited, bonus) = interest_credit(&data, &state, (5, PRINCIPAL_CURRENCY_USDT), 0).unwrap();
+        assert_eq!((credited.idle_funds, credited.lifetime_interest_claimed, bonus), (u64::MAX, 5, 0));
+
+        // Global counters that cannot take the claim fail it the same way
+        data.idle_funds = 0;
+        state.interest_claimed = u64::MAX;
+        assert_eq!(interest_credit(&data, &state, (1, PRINCIPAL_CURRENCY_USDT), 0).err(), Some(ERROR_OVERFLOW));
+        state.total_points_issued = u64::MAX;
+        assert_eq!(interest_credit(&data, &state, (1, PRINCIPAL_CURRENCY_POINTS), 0).err(), Some(ERROR_OVERFLOW));
+        assert_eq!(data.points, 0);
+    }
+
+    #[test]
+    fn test_multisig_settlement_carries_the_amount() {
+        let mut bytes = vec![];
+        multisig_withdraw_info(1_234_567).unwrap().flush(&mut bytes);
+        assert_eq!(bytes.len(), 32);
+        // Settlement layout: feature, 20 address bytes, then the amount big endian
+        assert_eq!(u64::from_be_bytes(bytes[24..32].try_into().unwrap()), 1_234_567);
+        let (first, middle, last) = crate::config::get_multisig_address_parts();
+        let mut address = (first as u32).to_le_bytes().to_vec();
+        address.extend_from_slice(&middle.to_le_bytes());
+        address.extend_from_slice(&last.to_le_bytes());
+        assert_eq!(&bytes[4..24], address.as_slice());
+
+        // An amount that does not fit the 32 amount bits is rejected, not truncated
+        assert_eq!(multisig_withdraw_info(1 << 32).err(), Some(ERROR_OVERFLOW));
+    }
+
+    /// The handlers' steps run against in-memory storage, with `storage` standing in for MERKLE_MAP
+    /// Each method loads a player copy, calls the core its handler calls and writes the copy back once
+    /// it went through; global state and storage keep whatever a failing step changed, as on chain
+    #[derive(Default)]
+    struct Ledger {
+        state: GlobalState,
+        storage: HashMap<[u64; 4], Vec<u64>>,
+        players: HashMap<[u64; 2], PlayerData>,
+    }
+
+    impl Ledger {
+        fn install(&mut self, pid: [u64; 2], idle_funds: u64) {
+            let mut data = PlayerData::new();
+            data.add_idle_funds(idle_funds).unwrap();
+            self.players.insert(pid, data);
+        }
+
+        fn player(&self, pid: &[u64; 2]) -> Result<PlayerData, u32> {
+            self.players.get(pid).cloned().ok_or(ERROR_PLAYER_NOT_EXIST)
+        }
+
+        fn idle_funds(&self, pid: &[u64; 2]) -> u64 {
+            self.players[pid].idle_funds
+        }
+
+        fn get(&self, key: &[u64; 4]) -> Vec<u64> {
+            Storage::get(&self.storage, key)
+        }
+
+        fn product(&self, product_type_id: u64) -> Option<ProductType> {
+            ProductTypeManager::get_product_type_with(|key| self.get(key), product_type_id)
+        }
+
+        /// CertificateManager::validate_certificate_ownership
+        fn certificate(&self, owner: &[u64; 2], cert_id: u64) -> Result<Certificate, u32> {
+            CertificateManager::validate_certificate_ownership_with(|key| self.get(key), owner, cert_id)
+        }
+
+        /// CertificateManager::get_certificate_with_status
+        fn certificate_now(&self, owner: &[u64; 2], cert_id: u64) -> Result<Certificate, u32> {
+            Ok(CertificateManager::with_current_status(self.certificate(owner, cert_id)?, self.state.counter))
+        }
+
+        /// CertificateManager::list_certificates
+        fn certificates(&self, owner: &[u64; 2]) -> Vec<Certificate> {
+            let legacy_end = self.state.certificate_counter;
+            let id_range = self.players.get(owner).map_or((0, 0), |data| data.certificate_id_range(legacy_end));
+            CertificateManager::certificates_page_with(|key| self.get(key), owner, id_range, 0).items
+        }
+
+        fn certificate_ids(&self, owner: &[u64; 2]) -> Vec<u64> {
+            self.certificates(owner).iter().map(|cert| cert.id).collect()
+        }
+
+        /// GlobalState::get_certificate_views
+        fn views(&self, owner: &[u64; 2]) -> Vec<crate::state::CertificateView> {
+            self.state.certificate_views_from(self.certificates(owner))
+        }
+
+        /// Run a player's step on its copy, keeping the copy only when the step went through
+        fn with_player<T, F>(&mut self, pid: [u64; 2], step: F) -> Result<T, u32>
+        where
+            F: FnOnce(&mut GlobalState, &mut HashMap<[u64; 4], Vec<u64>>, &mut PlayerData) -> Result<T, u32>,
+        {
+            let mut player = self.player(&pid)?;
+            let result = step(&mut self.state, &mut self.storage, &mut player)?;
+            self.players.insert(pid, player);
+            Ok(result)
+        }
+
+        /// CreateProductType::handle
+        fn create_product(&mut self, duration_ticks: u64, apy: u64, min_amount: u64, is_active: bool, options: &ProductTypeOptions) -> Result<u64, u32> {
+            ProductTypeManager::create_product_type(&mut self.state, &mut self.storage, duration_ticks, apy, min_amount, is_active, options)
+                .map(|product_type| product_type.id)
+        }
+
+        /// An open 30 day product at 10% APY with a minimum of 100
+        fn standard_product(&mut self) -> u64 {
+            self.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &ProductTypeOptions::default()).unwrap()
+        }
+
+        /// ModifyProductType::handle
+        fn modify_product(&mut self, product_type_id: u64, new_apy: u64, new_duration: u64, new_min_amount: u64, is_active: bool, options: &ProductTypeOptions) -> Result<(), u32> {
+            let terms = (new_apy, new_duration, new_min_amount);
+            ProductTypeManager::modify_product_type(&mut self.state, &mut self.storage, product_type_id, terms, is_active, options).map(|_| ())
+        }
+
+        /// SetProductTypeStatus::handle
+        fn set_product_status(&mut self, product_type_id: u64, is_active: bool, freeze_accrual: bool) -> Result<(), u32> {
+            ProductTypeManager::set_product_type_status(&mut self.state, &mut self.storage, product_type_id, is_active, freeze_accrual).map(|_| ())
+        }
+
+        /// SetWhitelistBatch::handle
+        fn set_whitelisted(&mut self, product_type_id: u64, users: &[[u64; 2]], add: bool) -> Result<(), u32> {
+            SetWhitelistBatch { product_type_id, users: users.to_vec(), add }.apply(&mut self.storage)
+        }
+
+        /// PurchaseCertificate::handle
+        fn purchase(&mut self, pid: [u64; 2], data: [u64; 4]) -> Result<Certificate, u32> {
+            self.with_player(pid, |state, storage, player| PurchaseCertificate { data }.apply(state, storage, &pid, player))
+        }
+
+        /// A plain purchase that has to go through, returning the new certificate id
+        fn buy(&mut self, pid: [u64; 2], product_type_id: u64, amount: u64) -> u64 {
+            self.purchase(pid, [product_type_id, amount, 0, 0]).unwrap().id
+        }
+
+        /// Deposit::handle
+        fn deposit(&mut self, user: [u64; 2], amount: u64) -> Result<(), u32> {
+            let counter = self.state.counter;
+            self.with_player(user, |state, _, player| Deposit { data: [user[0], user[1], amount] }.apply(state, player, counter)).map(|_| ())
+        }
+
+        /// DepositAndPurchase::handle
+        fn deposit_and_purchase(&mut self, user: [u64; 2], product_type_id: u64, amount: u64) -> Result<Certificate, u32> {
+            let command = DepositAndPurchase { data: [user[0], user[1], product_type_id, amount] };
+            self.with_player(user, |state, storage, player| command.apply(state, storage, player))
+        }
+
+        /// Withdraw::handle at `counter`
+        fn withdraw(&mut self, pid: [u64; 2], data: [u64; 3], counter: u64) -> Result<(), u32> {
+            self.with_player(pid, |state, _, player| Withdraw { data }.apply(state, player, counter)).map(|_| ())
+        }
+
+        /// SweepDust::handle
+        fn sweep_dust(&mut self, pid: [u64; 2], product_type_id: u64) -> Result<Certificate, u32> {
+            self.with_player(pid, |state, storage, player| SweepDust { product_type_id }.apply(state, storage, &pid, player))
+        }
+
+        /// BatchPurchase::handle
+        fn batch_purchase(&mut self, pid: [u64; 2], items: &[(u64, u64)]) -> Result<(), u32> {
+            let command = BatchPurchase { items: items.to_vec() };
+            self.with_player(pid, |state, storage, player| command.apply(state, storage, &pid, player)).map(|_| ())
+        }
+
+        /// RedeemPointsForFunds::handle
+        fn redeem_points(&mut self, pid: [u64; 2], points_amount: u64) -> Result<(), u32> {
+            self.with_player(pid, |state, _, player| apply_points_conversion(state, player, points_amount))
+        }
+
+        /// ClaimInterest::handle
+        fn claim(&mut self, pid: [u64; 2], certificate_id: u64, amount: u64) -> Result<(), u32> {
+            let command = ClaimInterest { certificate_id, amount };
+            self.with_player(pid, |state, storage, player| command.apply(state, storage, &pid, player)).map(|_| ())
+        }
+
+        /// ClaimInterestToTarget::handle
+        fn claim_to_target(&mut self, pid: [u64; 2], certificate_id: u64, target_remaining: u64) -> Result<(), u32> {
+            let command = ClaimInterestToTarget { certificate_id, target_remaining };
+            self.with_player(pid, |state, storage, player| command.apply(state, storage, &pid, player)).map(|_| ())
+        }
+
+        /// RedeemAll::handle
+        fn redeem_all(&mut self, pid: [u64; 2], certificate_id: u64) -> Result<(), u32> {
+            let command = RedeemAll { certificate_id };
+            self.with_player(pid, |state, storage, player| command.apply(state, storage, &pid, player)).map(|_| ())
+        }
+
+        /// RedeemEarly::handle
+        fn redeem_early(&mut self, pid: [u64; 2], certificate_id: u64) -> Result<(), u32> {
+            let command = RedeemEarly { certificate_id };
+            self.with_player(pid, |state, storage, player| command.apply(state, storage, &pid, player)).map(|_| ())
+        }
+
+        /// RedeemPrincipalPartial::handle
+        fn redeem_partial(&mut self, pid: [u64; 2], certificate_id: u64, amount: u64) -> Result<(), u32> {
+            let command = RedeemPrincipalPartial { certificate_id, amount };
+            self.with_player(pid, |state, storage, player| command.apply(state, storage, &pid, player)).map(|_| ())
+        }
+
+        /// TransferCertificate::handle, returning the id under the new owner
+        fn transfer(&mut self, pid: [u64; 2], certificate_id: u64, new_owner: [u64; 2]) -> Result<u64, u32> {
+            let mut player = self.player(&pid)?;
+            let mut recipient = self.player(&new_owner)?;
+            let command = TransferCertificate { certificate_id, new_owner };
+            let moved = command.apply(&mut self.state, &mut self.storage, &pid, &mut player, &mut recipient)?;
+            self.players.insert(new_owner, recipient);
+            self.players.insert(pid, player);
+            Ok(moved.id)
+        }
+
+        /// RolloverCertificate::handle
+        fn rollover(&mut self, pid: [u64; 2], certificate_id: u64) -> Result<Certificate, u32> {
+            let command = RolloverCertificate { certificate_id };
+            self.with_player(pid, |state, storage, player| command.apply(state, storage, &pid, player))
+                .map(|(_, certificate)| certificate)
+        }
+
+        /// AdminForceWithdrawPlayer::handle over the first page of the target's certificates,
+        /// returning the amount sent to the address
+        fn force_withdraw(&mut self, admin: [u64; 2], target: [u64; 2], data: [u64; 3], force_mature: bool) -> Result<u64, u32> {
+            let command = AdminForceWithdrawPlayer { pid: target, data, force_mature, start: 0 };
+            self.with_player(target, |state, storage, player| command.apply(state, storage, &admin, player))
+                .map(|(_, amount)| amount)
+        }
+
+        /// SetReserveRatio::handle
+        fn set_reserve_ratio(&mut self, reserve_ratio: u64, counter: u64) -> Result<u64, u32> {
+            SetReserveRatio { reserve_ratio }.apply(&mut self.state, counter)
+        }
+
+        /// AdminWithdrawToMultisig::handle
+        fn admin_withdraw(&mut self, amount: u64) -> Result<(), u32> {
+            AdminWithdrawToMultisig { amount }.apply(&mut self.state).map(|_| ())
+        }
+
+        /// AdminRepay::handle, returning the borrowed amount and the surplus
+        fn admin_repay(&mut self, amount: u64) -> Result<(u64, u64), u32> {
+            AdminRepay { amount }.apply(&mut self.state)
+        }
+
+        /// WithdrawRoundingSurplus::handle, returning the settlement it queues
+        fn withdraw_rounding_surplus(&mut self, amount: u64) -> Result<WithdrawInfo, u32> {
+            WithdrawRoundingSurplus { amount }.apply(&mut self.state)
+        }
+
+        /// Transaction::tick: the counter moves on, then run_auto_claims pays opted-in certificates
+        fn tick(&mut self) {
+            self.state.counter += 1;
+            let players = &self.players;
+            let load_player = |pid: &[u64; 2]| {
+                players.get(pid).map(|data| {
+                    let mut player = StakingPlayer::new_from_pid(*pid);
+                    player.data = data.clone();
+                    player
+                })
+            };
+            let (_, updated) = claim_due_interest(&mut self.state, &mut self.storage, load_player);
+            for player in updated {
+                self.players.insert(player.player_id, player.data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_purchase_leaves_no_partial_state() {
+        let mut ledger = Ledger::default();
+        let pid = [753, 1];
+        // Idle funds not backed by total_funds make the recharge purchase underflow
+        ledger.install(pid, 1000);
+
+        assert_eq!(ledger.purchase(pid, [0, 500, 0, 0]).err(), Some(ERROR_UNDERFLOW));
+        assert_eq!(ledger.idle_funds(&pid), 1000);
+        assert_eq!(ledger.state.certificate_counter, GlobalState::new().certificate_counter);
+        assert_eq!(ledger.state.total_recharge_amount, 0);
+    }
+
+    #[test]
+    fn test_failed_withdraw_leaves_no_partial_state() {
+        let mut ledger = Ledger::default();
+        let pid = [753, 2];
+        ledger.install(pid, 1000);
+
+        assert_eq!(ledger.withdraw(pid, [500, 0x1111, 0], 0), Err(ERROR_INSUFFICIENT_BALANCE));
+        assert_eq!(ledger.idle_funds(&pid), 1000);
+        assert_eq!(ledger.state.total_funds, 0);
+    }
+
+    #[test]
+    fn test_deposit_credits_only_on_success() {
+        let mut ledger = Ledger::default();
+        let user = [754, 2];
+        ledger.install(user, 0);
+
+        assert_eq!(ledger.deposit(user, 0), Err(ERROR_INVALID_STAKE_AMOUNT));
+        assert_eq!(ledger.deposit([754, 3], 500), Err(ERROR_PLAYER_NOT_EXIST));
+        assert_eq!(ledger.state.total_funds, 0);
+
+        ledger.deposit(user, 500).unwrap();
+        assert_eq!(ledger.idle_funds(&user), 500);
+        assert_eq!(ledger.state.total_funds, 500);
+    }
+
+    #[test]
+    fn test_deposit_and_purchase_locks_funds_for_the_user() {
+        let mut ledger = Ledger::default();
+        let user = [803, 2];
+        ledger.install(user, 250);
+        let product_type_id = ledger.standard_product();
+        let closed = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, false, &ProductTypeOptions::default()).unwrap();
+
+        // Rejected purchases credit nothing
+        assert_eq!(ledger.deposit_and_purchase(user, closed, 10_000).err(), Some(ERROR_PRODUCT_TYPE_INACTIVE));
+        assert_eq!(ledger.deposit_and_purchase(user, product_type_id, 50).err(), Some(ERROR_PRINCIPAL_AMOUNT_TOO_SMALL));
+        assert_eq!(ledger.state.total_funds, 0);
+
+        ledger.deposit_and_purchase(user, product_type_id, 10_000).unwrap();
+        let data = ledger.player(&user).unwrap();
+        assert_eq!((data.idle_funds, data.lifetime_deposited), (250, 10_000));
+        let certificates = ledger.certificates(&user);
+        assert_eq!(certificates.len(), 1);
+        assert_eq!((certificates[0].product_type_id, certificates[0].principal), (product_type_id, 10_000));
+        assert_eq!(ledger.state.total_funds, 10_000);
+    }
+
+    #[test]
+    fn test_whitelist_batch_controls_purchase_access() {
+        let mut ledger = Ledger::default();
+        let (alice, bob) = ([755, 2], [755, 3]);
+        ledger.install(alice, 1000);
+        ledger.install(bob, 1000);
+
+        let options = ProductTypeOptions { whitelist_only: Some(true), ..Default::default() };
+        let product_type_id = ledger.create_product(17280, 1000, 100, true, &options).unwrap();
+        let purchase = [product_type_id, 100, 0, 0];
+        assert_eq!(ledger.purchase(alice, purchase).err(), Some(ERROR_NOT_WHITELISTED));
+
+        ledger.set_whitelisted(product_type_id, &[alice, bob], true).unwrap();
+        ledger.purchase(alice, purchase).unwrap();
+        ledger.purchase(bob, purchase).unwrap();
+
+        ledger.set_whitelisted(product_type_id, &[alice], false).unwrap();
+        assert_eq!(ledger.purchase(alice, purchase).err(), Some(ERROR_NOT_WHITELISTED));
+        ledger.purchase(bob, purchase).unwrap();
+
+        // Oversized batches and unknown products are rejected
+        let oversized = vec![alice; crate::config::MAX_WHITELIST_BATCH + 1];
+        assert_eq!(ledger.set_whitelisted(product_type_id, &oversized, true), Err(ERROR_INVALID_PARAM));
+        assert_eq!(ledger.set_whitelisted(999, &[alice], true), Err(ERROR_PRODUCT_TYPE_NOT_EXIST));
+    }
+
+    #[test]
+    fn test_withdraw_takes_amount_from_low_bits() {
+        let mut ledger = Ledger::default();
+        let pid = [755, 4];
+        ledger.install(pid, 1000);
+        ledger.state.record_deposit(1000).unwrap();
+
+        // The low 32 bits of the first word carry the amount, the rest is the address
+        let first = (0xabcd << 32) + 400;
+        ledger.withdraw(pid, [first, 0x1111, 0x2222], 7).unwrap();
+        assert_eq!(ledger.idle_funds(&pid), 600);
+        assert_eq!(ledger.state.total_funds, 600);
+    }
+
+    #[test]
+    fn test_claims_accumulate_rounding_surplus() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let (alice, bob) = ([756, 1], [756, 2]);
+        ledger.install(alice, 10_000);
+        ledger.install(bob, 10_000);
+        let product_type_id = ledger.standard_product();
+        let first_cert = ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(bob, product_type_id, 10_000);
+
+        // One day in each certificate has paid 2 and withheld 0.739726
+        ledger.state.counter = TICKS_PER_DAY;
+        ledger.claim(alice, first_cert, 0).unwrap();
+        ledger.claim(bob, first_cert, 0).unwrap();
+        assert_eq!(ledger.state.rounding_surplus, 2 * 739_726);
+
+        // A later claim replaces that certificate's earlier drift
+        ledger.state.counter = 2 * TICKS_PER_DAY;
+        ledger.claim(alice, first_cert, 0).unwrap();
+        assert_eq!(ledger.state.rounding_surplus, 739_726 + 479_452);
+    }
+
+    #[test]
+    fn test_purchase_reports_product_errors_before_balance() {
+        let mut ledger = Ledger::default();
+        let alice = [794, 1];
+        ledger.install(alice, 50);
+        let open = ledger.standard_product();
+        let closed = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, false, &ProductTypeOptions::default()).unwrap();
+        let purchase = |ledger: &mut Ledger, product_type_id, amount| ledger.purchase(alice, [product_type_id, amount, 0, 0]).err();
+
+        // alice holds 50 idle funds, too little for any of these
+        assert_eq!(purchase(&mut ledger, open, 0), Some(ERROR_INVALID_PRINCIPAL_AMOUNT));
+        assert_eq!(purchase(&mut ledger, 999, 500), Some(ERROR_PRODUCT_TYPE_NOT_EXIST));
+        assert_eq!(purchase(&mut ledger, closed, 500), Some(ERROR_PRODUCT_TYPE_INACTIVE));
+        assert_eq!(purchase(&mut ledger, closed, 10), Some(ERROR_PRODUCT_TYPE_INACTIVE));
+        assert_eq!(purchase(&mut ledger, open, 60), Some(ERROR_PRINCIPAL_AMOUNT_TOO_SMALL));
+        assert_eq!(purchase(&mut ledger, open, 500), Some(ERROR_INSUFFICIENT_BALANCE));
+        assert_eq!(ledger.idle_funds(&alice), 50);
+
+        // Once the funds are there the minimum is the only thing left to meet
+        ledger.install(alice, 1_000);
+        assert_eq!(purchase(&mut ledger, open, 60), Some(ERROR_PRINCIPAL_AMOUNT_TOO_SMALL));
+        assert_eq!(purchase(&mut ledger, open, 100), None);
+        assert_eq!(ledger.idle_funds(&alice), 900);
+    }
+
+    #[test]
+    fn test_closing_with_freeze_stops_accrual_until_reopened() {
+        let mut ledger = Ledger::default();
+        let alice = [796, 2];
+        ledger.install(alice, 20_000);
+        ledger.state.total_recharge_amount += 1_000_000;
+        let frozen = ledger.standard_product();
+        let closed = ledger.standard_product();
+        let first_cert = ledger.buy(alice, frozen, 10_000);
+        ledger.buy(alice, closed, 10_000);
+        let available = |ledger: &Ledger, cert_id, day| {
+            ledger.certificate_now(&alice, cert_id).unwrap().calculate_available_interest(day * TICKS_PER_DAY).unwrap()
+        };
+
+        // 10 days accrue, then the product is pulled with accrual frozen and the other one only closed
+        ledger.state.counter = 10 * TICKS_PER_DAY;
+        ledger.claim(alice, first_cert, 0).unwrap();
+        ledger.set_product_status(frozen, false, true).unwrap();
+        ledger.set_product_status(closed, false, false).unwrap();
+        assert_eq!(ledger.product(frozen).unwrap().frozen_since(), Some(10 * TICKS_PER_DAY));
+        assert_eq!(ledger.product(closed).unwrap().frozen_since(), None);
+
+        // Nothing accrues on the frozen product across the closed span
+        ledger.state.counter = 20 * TICKS_PER_DAY;
+        assert_eq!(available(&ledger, first_cert, 20), 0);
+        assert_eq!(available(&ledger, first_cert + 1, 20), 54);
+
+        // Reopened, accrual picks up where it stopped: 15 accruing days earn 41 in total
+        ledger.set_product_status(frozen, true, false).unwrap();
+        assert_eq!(ledger.product(frozen).unwrap().accrual_pauses, vec![(10 * TICKS_PER_DAY, 20 * TICKS_PER_DAY)]);
+        ledger.state.counter = 25 * TICKS_PER_DAY;
+        assert_eq!(available(&ledger, first_cert, 25), 14);
+        ledger.claim(alice, first_cert, 0).unwrap();
+        assert_eq!(ledger.certificate_now(&alice, first_cert).unwrap().total_interest_claimed, 41);
+    }
+
+    #[test]
+    fn test_active_certificate_count_follows_redemptions() {
+        let mut ledger = Ledger::default();
+        let (alice, bob) = ([798, 1], [798, 2]);
+        ledger.install(alice, 10_000);
+        ledger.install(bob, 0);
+        ledger.state.total_recharge_amount += 1_000_000;
+        let options = ProductTypeOptions { early_withdrawal_penalty_bps: Some(5000), ..Default::default() };
+        let product_type_id = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &options).unwrap();
+        let first_cert = GlobalState::new().certificate_counter;
+        let counts = |ledger: &Ledger| (ledger.state.certificates_issued, ledger.state.active_certificate_count);
+
+        for _ in 0..3 {
+            ledger.buy(alice, product_type_id, 1_000);
+        }
+        assert_eq!(counts(&ledger), (3, 3));
+
+        // A transfer keeps the certificate open, an early exit closes it
+        ledger.transfer(alice, first_cert + 2, bob).unwrap();
+        assert_eq!(counts(&ledger), (3, 3));
+        ledger.state.counter = 10 * TICKS_PER_DAY;
+        ledger.redeem_early(alice, first_cert + 1).unwrap();
+        assert_eq!(counts(&ledger), (3, 2));
+
+        // A partial redemption only closes the certificate once no principal is left
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        ledger.redeem_all(alice, first_cert).unwrap();
+        assert_eq!(counts(&ledger), (3, 1));
+        ledger.claim(bob, first_cert, 0).unwrap();
+        ledger.redeem_partial(bob, first_cert, 400).unwrap();
+        assert_eq!(counts(&ledger), (3, 1));
+        ledger.redeem_partial(bob, first_cert, 600).unwrap();
+        assert_eq!(counts(&ledger), (3, 0));
+        assert_eq!(ledger.redeem_all(alice, first_cert), Err(ERROR_CERTIFICATE_ALREADY_REDEEMED));
+        assert_eq!(counts(&ledger), (3, 0));
+    }
+
+    #[test]
+    fn test_sweep_dust_buys_a_certificate_below_the_minimum() {
+        let mut ledger = Ledger::default();
+        let (alice, bob) = ([799, 1], [799, 2]);
+        ledger.install(alice, 7);
+        ledger.install(bob, 10);
+        let points = ProductTypeOptions { principal_currency: Some(1), interest_currency: Some(1), ..Default::default() };
+        let open = ledger.standard_product();
+        let closed = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, false, &ProductTypeOptions::default()).unwrap();
+        let points_product = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &points).unwrap();
+
+        // Product existence, status and currency still apply
+        assert_eq!(ledger.sweep_dust(alice, 999).err(), Some(ERROR_PRODUCT_TYPE_NOT_EXIST));
+        assert_eq!(ledger.sweep_dust(alice, closed).err(), Some(ERROR_PRODUCT_TYPE_INACTIVE));
+        assert_eq!(ledger.sweep_dust(alice, points_product).err(), Some(ERROR_INVALID_PRINCIPAL_CURRENCY));
+        // A balance that can buy a certificate normally is not dust
+        assert_eq!(ledger.sweep_dust(bob, open).err(), Some(ERROR_IDLE_FUNDS_NOT_DUST));
+
+        // 7 units go in despite the product's 100 minimum
+        let cert = ledger.sweep_dust(alice, open).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 0);
+        assert_eq!((cert.product_type_id, cert.principal), (open, 7));
+        assert_eq!(ledger.certificate_ids(&alice), vec![GlobalState::new().certificate_counter]);
+        assert_eq!(ledger.product(open).unwrap().current_total_principal, 7);
+        assert_eq!(ledger.sweep_dust(alice, open).err(), Some(ERROR_INSUFFICIENT_BALANCE));
+    }
+
+    #[test]
+    fn test_claims_on_a_certificate_are_spaced_by_the_product_interval() {
+        let mut ledger = Ledger::default();
+        let alice = [801, 1];
+        ledger.install(alice, 100_000);
+        ledger.state.total_recharge_amount += 1_000_000;
+        let options = ProductTypeOptions { min_claim_interval_ticks: Some(TICKS_PER_DAY), ..Default::default() };
+        let product_type_id = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &options).unwrap();
+        let cert_id = ledger.buy(alice, product_type_id, 100_000);
+        let claim_at = |ledger: &mut Ledger, counter| {
+            ledger.state.counter = counter;
+            ledger.claim(alice, cert_id, 0)
+        };
+        let claimed = |ledger: &Ledger| ledger.certificate_now(&alice, cert_id).unwrap().total_interest_claimed;
+
+        // The first claim is never limited, an immediate second one is
+        claim_at(&mut ledger, TICKS_PER_DAY).unwrap();
+        assert_eq!(claimed(&ledger), 27);
+        assert_eq!(claim_at(&mut ledger, TICKS_PER_DAY + 100), Err(ERROR_CLAIM_TOO_SOON));
+        assert_eq!(claimed(&ledger), 27);
+
+        // A full interval later claiming is open again
+        claim_at(&mut ledger, 2 * TICKS_PER_DAY).unwrap();
+        assert_eq!(claimed(&ledger), 54);
+        assert_eq!(ledger.certificate_now(&alice, cert_id).unwrap().last_claim_counter, 2 * TICKS_PER_DAY);
+
+        // Redemption pays out right after a claim
+        claim_at(&mut ledger, 30 * TICKS_PER_DAY).unwrap();
+        ledger.redeem_all(alice, cert_id).unwrap();
+    }
+
+    #[test]
+    fn test_set_product_type_status_closes_purchases() {
+        let mut ledger = Ledger::default();
+        let alice = [784, 14];
+        ledger.install(alice, 1_000);
+        let product_type_id = ledger.standard_product();
+
+        ledger.set_product_status(product_type_id, false, false).unwrap();
+        let product_type = ledger.product(product_type_id).unwrap();
+        assert!(!product_type.is_active);
+        assert_eq!((product_type.apy, product_type.duration_ticks), (1000, 30 * TICKS_PER_DAY));
+        let purchase = [product_type_id, 500, 0, 0];
+        assert_eq!(ledger.purchase(alice, purchase).err(), Some(ERROR_PRODUCT_TYPE_INACTIVE));
+
+        // The recharge product stays open and unknown products are rejected
+        assert_eq!(ledger.set_product_status(0, false, false), Err(ERROR_INVALID_PARAM));
+        assert_eq!(ledger.set_product_status(999, true, false), Err(ERROR_PRODUCT_TYPE_NOT_EXIST));
+
+        ledger.set_product_status(product_type_id, true, false).unwrap();
+        ledger.purchase(alice, purchase).unwrap();
+    }
+
+    #[test]
+    fn test_interest_claims_limited_to_recharge_reserve() {
+        let mut ledger = Ledger::default();
+        let (alice, bob) = ([791, 1], [791, 2]);
+        ledger.install(alice, 10_000);
+        ledger.install(bob, 10_000);
+        ledger.state.total_recharge_amount += 50;
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(bob, product_type_id, 10_000);
+
+        // 41 each is available, the recharge funding only backs one of the claims
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        assert_eq!(ledger.state.interest_reserve(), 9);
+        assert_eq!(ledger.claim(bob, cert_id, 0), Err(ERROR_INSUFFICIENT_INTEREST));
+        assert_eq!(ledger.certificate(&bob, cert_id).unwrap().total_interest_claimed, 0);
+        assert_eq!(ledger.state.interest_claimed, 41);
+
+        // A claim the reserve covers still goes through, as does everything once it is topped up
+        ledger.claim(bob, cert_id, 9).unwrap();
+        ledger.state.total_recharge_amount += 32;
+        ledger.claim(bob, cert_id, 0).unwrap();
+        assert_eq!(ledger.idle_funds(&bob), 41);
+        assert_eq!(ledger.state.interest_reserve(), 0);
+    }
+
+    #[test]
+    fn test_certificate_ids_are_numbered_per_owner() {
+        let mut ledger = Ledger::default();
+        let (alice, bob) = ([788, 1], [788, 2]);
+        ledger.install(alice, 20_000);
+        ledger.install(bob, 10_000);
+        let product_type_id = ledger.standard_product();
+        ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(bob, product_type_id, 10_000);
+
+        // Both owners hold a certificate numbered 1 under their own key
+        let alice_cert = ledger.certificate(&alice, 1).unwrap();
+        let bob_cert = ledger.certificate(&bob, 1).unwrap();
+        assert_eq!((alice_cert.owner, bob_cert.owner), (alice, bob));
+        assert_eq!(ledger.state.certificate_counter, 1);
+
+        // A transfer renumbers the certificate from the recipient's counter
+        ledger.transfer(bob, 1, alice).unwrap();
+        assert_eq!(ledger.certificate_ids(&alice), vec![1, 2]);
+        assert!(ledger.certificates(&bob).is_empty());
+        ledger.buy(alice, product_type_id, 10_000);
+        assert_eq!(ledger.player(&alice).unwrap().certificate_nonce, 4);
+    }
+
+    #[test]
+    fn test_legacy_global_certificate_ids_stay_readable() {
+        let mut ledger = Ledger::default();
+        let alice = [788, 3];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+
+        // Globally numbered certificates 1 to 4 were issued before the switch, alice owns number 3
+        // Her player record predates first_certificate_id, which decodes as 1
+        ledger.state.certificate_counter = 5;
+        ledger.players.get_mut(&alice).unwrap().first_certificate_id = 1;
+        CertificateManager::store_certificate(&mut ledger.storage, &Certificate::new(3, alice, product_type_id, 500, 0, 30 * TICKS_PER_DAY, 1000));
+
+        ledger.buy(alice, product_type_id, 10_000);
+        assert_eq!(ledger.certificate_ids(&alice), vec![3, 5]);
+        assert_eq!(ledger.certificate(&alice, 3).unwrap().principal, 500);
+    }
+
+    #[test]
+    fn test_stored_certificate_follows_claims_and_redemption() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [784, 15];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        let cert = ledger.certificate(&alice, cert_id).unwrap();
+        assert_eq!((cert.total_interest_claimed, cert.status), (41, CertificateStatus::Active));
+
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        ledger.redeem_partial(alice, cert_id, 4_000).unwrap();
+        let cert = ledger.certificate(&alice, cert_id).unwrap();
+        assert_eq!((cert.principal, cert.total_interest_claimed), (6_000, 82));
+
+        ledger.redeem_all(alice, cert_id).unwrap();
+        assert_eq!(ledger.certificate(&alice, cert_id).unwrap().status, CertificateStatus::Redeemed);
+    }
+
+    #[test]
+    fn test_grace_period_delays_redemption_while_interest_accrues() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [784, 12];
+        ledger.install(alice, 10_000);
+        let options = ProductTypeOptions { grace_ticks: Some(5 * TICKS_PER_DAY), ..Default::default() };
+        let product_type_id = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &options).unwrap();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+        let cert = ledger.certificate(&alice, cert_id).unwrap();
+        assert_eq!((cert.maturity_time, cert.grace_ticks), (30 * TICKS_PER_DAY, 5 * TICKS_PER_DAY));
+
+        // At nominal maturity the grace window keeps the principal locked
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        assert!(!cert.is_matured(30 * TICKS_PER_DAY));
+        assert_eq!(ledger.redeem_all(alice, cert_id), Err(ERROR_CERTIFICATE_NOT_MATURED));
+
+        // Interest accrues through the window and stops at its end
+        assert_eq!(cert.calculate_available_interest(30 * TICKS_PER_DAY).unwrap(), 82);
+        assert_eq!(cert.calculate_available_interest(35 * TICKS_PER_DAY).unwrap(), 95);
+        assert_eq!(cert.calculate_available_interest(40 * TICKS_PER_DAY).unwrap(), 95);
+
+        ledger.state.counter = 35 * TICKS_PER_DAY;
+        ledger.redeem_all(alice, cert_id).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 10_095);
+    }
+
+    #[test]
+    fn test_interest_claims_share_a_per_tick_budget() {
+        use crate::config::{PARAM_MAX_INTEREST_PER_TICK, PARAM_INTEREST_TICK_PARTIAL};
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let (alice, bob) = ([784, 10], [784, 11]);
+        ledger.install(alice, 10_000);
+        ledger.install(bob, 10_000);
+        let product_type_id = ledger.standard_product();
+        let first_cert = ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(bob, product_type_id, 10_000);
+
+        // Each certificate has 41 available, the tick allows 50 in total
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+        ledger.state.set_param(PARAM_MAX_INTEREST_PER_TICK, 50).unwrap();
+        ledger.claim(alice, first_cert, 0).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 41);
+        assert_eq!(ledger.claim(bob, first_cert, 0), Err(ERROR_INTEREST_TICK_BUDGET));
+
+        // With partial payouts the claim takes what is left of the budget
+        ledger.state.set_param(PARAM_INTEREST_TICK_PARTIAL, 1).unwrap();
+        ledger.claim(bob, first_cert, 0).unwrap();
+        assert_eq!(ledger.idle_funds(&bob), 9);
+        assert_eq!(ledger.claim(bob, first_cert, 0), Err(ERROR_INTEREST_TICK_BUDGET));
+
+        // The budget resets on the next tick
+        ledger.state.counter += 1;
+        ledger.claim(bob, first_cert, 0).unwrap();
+        assert_eq!(ledger.idle_funds(&bob), 41);
+        assert_eq!((ledger.state.interest_paid_this_tick, ledger.state.last_interest_tick), (32, 15 * TICKS_PER_DAY + 1));
+    }
+
+    #[test]
+    fn test_reserve_ratio_change_returns_old_ratio() {
+        let mut ledger = Ledger::default();
+
+        // Rejected ratios change nothing
+        assert_eq!(ledger.set_reserve_ratio(crate::config::MAX_RESERVE_RATIO + 1, 5), Err(ERROR_INVALID_STAKE_AMOUNT));
+        assert_eq!(ledger.state.reserve_ratio, 1000);
+
+        assert_eq!(ledger.set_reserve_ratio(2000, 5), Ok(1000));
+        assert_eq!(ledger.state.reserve_ratio, 2000);
+    }
+
+    #[test]
+    fn test_reserve_ratio_floor_and_change_cooldown() {
+        use crate::config::{MIN_RESERVE_RATIO, RESERVE_RATIO_CHANGE_COOLDOWN_TICKS};
+
+        let mut ledger = Ledger::default();
+        let start = 100;
+
+        assert_eq!(ledger.set_reserve_ratio(MIN_RESERVE_RATIO - 1, start), Err(ERROR_RESERVE_RATIO_TOO_LOW));
+        ledger.set_reserve_ratio(MIN_RESERVE_RATIO, start).unwrap();
+        assert_eq!(ledger.state.last_reserve_change_counter, start);
+
+        // The next change has to wait out the cooldown, a rejected one does not restart it
+        let next = start + RESERVE_RATIO_CHANGE_COOLDOWN_TICKS;
+        assert_eq!(ledger.set_reserve_ratio(2000, next - 1), Err(ERROR_RESERVE_RATIO_CHANGE_TOO_SOON));
+        assert_eq!(ledger.state.reserve_ratio, MIN_RESERVE_RATIO);
+        ledger.set_reserve_ratio(2000, next).unwrap();
+        assert_eq!(ledger.state.reserve_ratio, 2000);
+    }
+
+    #[test]
+    fn test_auto_redeem_on_claim_for_matured_certificates() {
+        use crate::certificate::CertificateStatus;
+        use crate::config::PARAM_AUTO_REDEEM_ON_CLAIM;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let (alice, bob) = ([757, 1], [757, 2]);
+        ledger.install(alice, 10_000);
+        ledger.install(bob, 10_000);
+        let product_type_id = ledger.standard_product();
+        let first_cert = ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(bob, product_type_id, 10_000);
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+
+        // Without the flag only the interest is paid, the certificate stays unredeemed
+        ledger.claim(alice, first_cert, 0).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 82);
+        assert_eq!(ledger.certificate(&alice, first_cert).unwrap().status, CertificateStatus::Matured);
+
+        // With it the principal comes back too
+        assert_eq!(ledger.state.set_param(PARAM_AUTO_REDEEM_ON_CLAIM, 2), Err(ERROR_INVALID_PARAM));
+        ledger.state.set_param(PARAM_AUTO_REDEEM_ON_CLAIM, 1).unwrap();
+        ledger.claim(bob, first_cert, 0).unwrap();
+        assert_eq!(ledger.idle_funds(&bob), 10_082);
+        assert_eq!(ledger.certificate(&bob, first_cert).unwrap().status, CertificateStatus::Redeemed);
+    }
+
+    #[test]
+    fn test_auto_redeem_that_cannot_be_credited_fails_the_claim() {
+        use crate::certificate::CertificateStatus;
+        use crate::config::PARAM_AUTO_REDEEM_ON_CLAIM;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [779, 1];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+        ledger.state.set_param(PARAM_AUTO_REDEEM_ON_CLAIM, 1).unwrap();
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+
+        // The 82 of interest would fit the balance but the principal does not, neither is recorded
+        ledger.players.get_mut(&alice).unwrap().idle_funds = u64::MAX - 100;
+        assert_eq!(ledger.claim(alice, cert_id, 0), Err(ERROR_OVERFLOW));
+        let cert = ledger.certificate(&alice, cert_id).unwrap();
+        assert_eq!((cert.status, cert.total_interest_claimed), (CertificateStatus::Active, 0));
+        assert_eq!((ledger.state.interest_claimed, ledger.state.active_certificate_count), (0, 1));
+
+        ledger.players.get_mut(&alice).unwrap().idle_funds = 0;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 10_082);
+        assert_eq!(ledger.certificate(&alice, cert_id).unwrap().status, CertificateStatus::Redeemed);
+        assert_eq!((ledger.state.interest_claimed, ledger.state.active_certificate_count), (82, 0));
+    }
+
+    #[test]
+    fn test_redemption_that_cannot_be_credited_leaves_the_certificate_open() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [759, 1];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+
+        // The balance takes the interest but not the principal, so neither the claim nor the close is recorded
+        ledger.players.get_mut(&alice).unwrap().idle_funds = u64::MAX - 100;
+        assert_eq!(ledger.redeem_all(alice, cert_id), Err(ERROR_OVERFLOW));
+        let cert = ledger.certificate(&alice, cert_id).unwrap();
+        assert_eq!((cert.status, cert.total_interest_claimed), (CertificateStatus::Active, 0));
+        assert_eq!((ledger.state.interest_claimed, ledger.state.active_certificate_count), (0, 1));
+
+        ledger.players.get_mut(&alice).unwrap().idle_funds = 0;
+        ledger.redeem_all(alice, cert_id).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 10_082);
+        assert_eq!((ledger.state.interest_claimed, ledger.state.active_certificate_count), (82, 0));
+    }
+
+    #[test]
+    fn test_transfer_certificate_rekeys_ownership() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let (alice, bob) = ([758, 1], [758, 2]);
+        ledger.install(alice, 10_000);
+        ledger.install(bob, 0);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+
+        assert_eq!(ledger.transfer(alice, cert_id, alice), Err(ERROR_CERTIFICATE_SAME_OWNER));
+        assert_eq!(ledger.transfer(alice, cert_id, [758, 3]), Err(ERROR_PLAYER_NOT_EXIST));
+
+        // Bob holds no certificates yet, so it keeps the same number under bob's key
+        assert_eq!(ledger.transfer(alice, cert_id, bob), Ok(cert_id));
+        assert_eq!(ledger.certificate(&alice, cert_id).unwrap_err(), ERROR_CERTIFICATE_NOT_OWNED);
+        assert_eq!(ledger.certificate(&bob, cert_id).unwrap().owner, bob);
+        assert_eq!(ledger.transfer(alice, cert_id, alice), Err(ERROR_CERTIFICATE_NOT_OWNED));
+
+        // The new owner can redeem it; redeemed certificates cannot move
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        ledger.redeem_all(bob, cert_id).unwrap();
+        assert_eq!(ledger.idle_funds(&bob), 10_082);
+        assert_eq!(ledger.transfer(bob, cert_id, alice), Err(ERROR_CERTIFICATE_ALREADY_REDEEMED));
+    }
+
+    #[test]
+    fn test_partial_then_full_principal_redemption() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [758, 4];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+
+        assert_eq!(ledger.redeem_partial(alice, cert_id, 5_000), Err(ERROR_CERTIFICATE_NOT_MATURED));
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        assert_eq!(ledger.redeem_partial(alice, cert_id, 5_000), Err(ERROR_UNCLAIMED_INTEREST));
+
+        ledger.claim(alice, cert_id, 0).unwrap();
+        assert_eq!(ledger.redeem_partial(alice, cert_id, 10_001), Err(ERROR_INVALID_PRINCIPAL_AMOUNT));
+        ledger.redeem_partial(alice, cert_id, 5_000).unwrap();
+
+        let cert = ledger.certificate(&alice, cert_id).unwrap();
+        assert_eq!(cert.principal, 5_000);
+        assert_eq!(cert.total_interest_claimed, 82);
+        assert_eq!(cert.status, CertificateStatus::Matured);
+        assert_eq!(ledger.idle_funds(&alice), 5_082);
+
+        // The remaining half comes back through a full redemption
+        ledger.redeem_all(alice, cert_id).unwrap();
+        assert_eq!(ledger.certificate(&alice, cert_id).unwrap().status, CertificateStatus::Redeemed);
+        assert_eq!(ledger.idle_funds(&alice), 10_082);
+        assert_eq!(ledger.redeem_partial(alice, cert_id, 5_000), Err(ERROR_CERTIFICATE_ALREADY_REDEEMED));
+    }
+
+    #[test]
+    fn test_redeem_all_returns_principal_and_interest() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [759, 1];
+        ledger.install(alice, 20_000);
+        let product_type_id = ledger.standard_product();
+        let first_cert = ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(alice, product_type_id, 10_000);
+
+        assert_eq!(ledger.redeem_all(alice, first_cert), Err(ERROR_CERTIFICATE_NOT_MATURED));
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+
+        ledger.redeem_all(alice, first_cert).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 10_082);
+        assert_eq!(ledger.certificate(&alice, first_cert).unwrap().status, CertificateStatus::Redeemed);
+        assert_eq!(ledger.state.interest_claimed, 82);
+
+        // Interest already claimed: the principal is still redeemed
+        ledger.claim(alice, first_cert + 1, 0).unwrap();
+        ledger.redeem_all(alice, first_cert + 1).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 20_164);
+        assert_eq!(ledger.redeem_all(alice, first_cert + 1), Err(ERROR_CERTIFICATE_ALREADY_REDEEMED));
+    }
+
+    #[test]
+    fn test_redeem_principal_pays_unclaimed_interest() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [760, 1];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+
+        // Never claimed, redeemed well after maturity
+        ledger.state.counter = 40 * TICKS_PER_DAY;
+        ledger.redeem_all(alice, cert_id).unwrap();
+
+        assert_eq!(ledger.idle_funds(&alice), 10_082);
+        let cert = ledger.certificate(&alice, cert_id).unwrap();
+        assert_eq!(cert.status, CertificateStatus::Redeemed);
+        assert_eq!(cert.total_interest_claimed, 82);
+        assert_eq!(ledger.state.interest_claimed, 82);
+    }
+
+    #[test]
+    fn test_list_certificates_returns_owned_certificates() {
+        let mut ledger = Ledger::default();
+        let (alice, bob) = ([761, 1], [761, 2]);
+        ledger.install(alice, 30_000);
+        ledger.install(bob, 10_000);
+        assert!(ledger.certificates(&alice).is_empty());
+        assert!(ledger.views(&alice).is_empty());
+
+        let product_type_id = ledger.standard_product();
+        ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(bob, product_type_id, 10_000);
+        ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(alice, product_type_id, 10_000);
+
+        let first_cert = GlobalState::new().certificate_counter;
+        assert_eq!(ledger.certificate_ids(&alice), vec![first_cert, first_cert + 1, first_cert + 2]);
+        assert_eq!(ledger.certificate_ids(&bob), vec![first_cert]);
+
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        let views = ledger.views(&alice);
+        assert_eq!(views.len(), 3);
+        assert!(views.iter().all(|v| v.is_matured && v.available_interest == 82 && v.duration_days == 30));
+        assert_eq!(views[0].certificate.id, first_cert);
+    }
+
+    #[test]
+    fn test_certificate_views_list_next_to_mature_first() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [802, 1];
+        ledger.install(alice, 40_000);
+        for days in [30, 10, 20, 5] {
+            let product_type_id = ledger.create_product(days * TICKS_PER_DAY, 1000, 100, true, &ProductTypeOptions::default()).unwrap();
+            ledger.buy(alice, product_type_id, 10_000);
+        }
+        let first_cert = GlobalState::new().certificate_counter;
+
+        ledger.state.counter = 5 * TICKS_PER_DAY;
+        ledger.redeem_all(alice, first_cert + 3).unwrap();
+
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+        let listed: Vec<(u64, u64, bool)> = ledger
+            .views(&alice)
+            .iter()
+            .map(|v| (v.certificate.id, v.time_to_maturity, v.is_matured))
+            .collect();
+        assert_eq!(listed, vec![
+            (first_cert + 1, 0, true),
+            (first_cert + 2, 5 * TICKS_PER_DAY, false),
+            (first_cert, 15 * TICKS_PER_DAY, false),
+        ]);
+    }
+
+    #[test]
+    fn test_tick_length_changes_price_new_certificates_only() {
+        use crate::config::{PARAM_SECONDS_PER_TICK, SECONDS_PER_TICK};
+
+        let mut ledger = Ledger::default();
+        let alice = [804, 1];
+        ledger.install(alice, 20_000);
+        let product_type_id = ledger.standard_product();
+        ledger.buy(alice, product_type_id, 10_000);
+
+        assert_eq!(ledger.state.set_param(PARAM_SECONDS_PER_TICK, 0), Err(ERROR_INVALID_PARAM));
+        assert_eq!(ledger.state.set_param(PARAM_SECONDS_PER_TICK, 2 * SECONDS_PER_TICK), Ok(SECONDS_PER_TICK));
+        ledger.buy(alice, product_type_id, 10_000);
+
+        // Twice the seconds per tick earns twice the interest over the same ticks
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        let priced: Vec<(u64, u64)> = ledger
+            .views(&alice)
+            .iter()
+            .map(|v| (v.certificate.seconds_per_tick, v.available_interest))
+            .collect();
+        assert_eq!(priced, vec![(SECONDS_PER_TICK, 82), (2 * SECONDS_PER_TICK, 164)]);
+    }
+
+    #[test]
+    fn test_certificate_queries_refresh_matured_status() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        let alice = [784, 8];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+
+        let matured = |cert: &Certificate| matches!(cert.status, CertificateStatus::Matured);
+        assert!(!matured(&ledger.certificate_now(&alice, cert_id).unwrap()));
+
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        assert!(matured(&ledger.certificate_now(&alice, cert_id).unwrap()));
+        assert!(matured(&ledger.views(&alice)[0].certificate));
+        // Queries never write the refreshed status back
+        let stored = ledger.certificate(&alice, cert_id).unwrap();
+        assert!(matches!(stored.status, CertificateStatus::Active));
+        assert_eq!(ledger.certificate_now(&alice, cert_id + 1).err(), Some(ERROR_CERTIFICATE_NOT_OWNED));
+    }
+
+    #[test]
+    fn test_purchase_rejects_stale_expected_apy() {
+        let mut ledger = Ledger::default();
+        let alice = [762, 1];
+        ledger.install(alice, 20_000);
+        let product_type_id = ledger.standard_product();
+        ledger.modify_product(product_type_id, 800, 30 * TICKS_PER_DAY, 100, true, &ProductTypeOptions::default()).unwrap();
+
+        assert_eq!(ledger.purchase(alice, [product_type_id, 10_000, 1000, 0]).err(), Some(ERROR_APY_CHANGED));
+        assert_eq!(ledger.state.certificate_counter, GlobalState::new().certificate_counter);
+        assert_eq!(ledger.idle_funds(&alice), 20_000);
+
+        ledger.purchase(alice, [product_type_id, 10_000, 800, 0]).unwrap();
+        ledger.purchase(alice, [product_type_id, 10_000, 0, 0]).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 0);
+    }
+
+    #[test]
+    fn test_list_product_types_includes_recharge_and_inactive() {
+        let mut ledger = Ledger::default();
+        let open = ledger.standard_product();
+        let closed = ledger.create_product(90 * TICKS_PER_DAY, 1500, 100, false, &ProductTypeOptions::default()).unwrap();
+
+        let products = ProductTypeManager::list_product_types_with(|key| ledger.get(key), ledger.state.product_type_counter);
+        let listed: Vec<(u64, bool)> = products.iter().map(|p| (p.id, p.is_active)).collect();
+        assert_eq!(listed, vec![(0, true), (open, true), (closed, false)]);
+        assert_eq!(products[0].apy, 0);
+    }
+
+    #[test]
+    fn test_admin_withdrawal_is_limited_by_reserve() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_funds = 10_000;
+
+        // The default 10% reserve leaves 9_000 withdrawable
+        assert_eq!(ledger.admin_withdraw(9_001), Err(ERROR_INSUFFICIENT_BALANCE));
+        assert_eq!(ledger.state.cumulative_admin_withdrawals, 0);
+
+        ledger.admin_withdraw(4_000).unwrap();
+        assert_eq!(ledger.state.cumulative_admin_withdrawals, 4_000);
+    }
+
+    #[test]
+    fn test_admin_repay_restores_available_funds() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_funds = 10_000;
+        let available = ledger.state.available_admin_funds().unwrap();
+        assert_eq!(available, 9_000);
+
+        // The 10% reserve now applies to the 4_000 left in the system
+        ledger.admin_withdraw(6_000).unwrap();
+        assert_eq!(ledger.state.available_admin_funds().unwrap(), 3_600);
+        assert_eq!(ledger.admin_repay(0), Err(ERROR_INVALID_STAKE_AMOUNT));
+
+        assert_eq!(ledger.admin_repay(6_000), Ok((6_000, 0)));
+        assert_eq!(ledger.state.available_admin_funds().unwrap(), available);
+
+        // Repaying more than was withdrawn floors the counter at zero
+        ledger.admin_withdraw(1_000).unwrap();
+        ledger.admin_repay(5_000).unwrap();
+        assert_eq!(ledger.state.cumulative_admin_withdrawals, 0);
+    }
+
+    #[test]
+    fn test_user_withdrawals_keep_admin_borrowed_funds_covered() {
+        let mut ledger = Ledger::default();
+        let (alice, bob) = ([784, 6], [784, 7]);
+        ledger.install(alice, 6_000);
+        ledger.install(bob, 4_000);
+        ledger.state.total_funds = 10_000;
+
+        // The admin borrows 8_000, leaving 2_000 in the treasury
+        ledger.state.reserve_ratio = 0;
+        ledger.admin_withdraw(8_000).unwrap();
+
+        assert_eq!(ledger.withdraw(alice, [2_001, 0x1111, 0], 0), Err(ERROR_INSUFFICIENT_BALANCE));
+        ledger.withdraw(alice, [2_000, 0x1111, 0], 0).unwrap();
+        assert_eq!(ledger.withdraw(bob, [1, 0x1111, 0], 0), Err(ERROR_INSUFFICIENT_BALANCE));
+        assert_eq!(ledger.idle_funds(&bob), 4_000);
+
+        // Repayment restores what users can take out
+        ledger.admin_repay(3_000).unwrap();
+        ledger.withdraw(bob, [3_000, 0x1111, 0], 0).unwrap();
+        assert_eq!(ledger.state.total_funds, 5_000);
+    }
+
+    #[test]
+    fn test_withdraw_rounding_surplus_is_capped_and_reset() {
+        use crate::certificate::ROUNDING_SURPLUS_SCALE;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_funds = 10_000;
+        ledger.state.rounding_surplus = 2 * ROUNDING_SURPLUS_SCALE + 500_000;
+        assert_eq!(ledger.state.withdrawable_rounding_surplus(), 2);
+
+        // Only whole tracked units can leave
+        assert_eq!(ledger.withdraw_rounding_surplus(3).err(), Some(ERROR_INSUFFICIENT_BALANCE));
+
+        // The settlement pays the withdrawn units, big endian in its last 8 bytes
+        let mut settlement = vec![];
+        ledger.withdraw_rounding_surplus(2).unwrap().flush(&mut settlement);
+        assert_eq!(settlement.len(), 32);
+        assert_eq!(u64::from_be_bytes(settlement[24..32].try_into().unwrap()), 2);
+        assert_eq!((ledger.state.withdrawable_rounding_surplus(), ledger.state.rounding_surplus_withdrawn), (0, 2));
+        // Live drift and user funds are untouched
+        assert_eq!((ledger.state.rounding_surplus, ledger.state.total_funds), (2 * ROUNDING_SURPLUS_SCALE + 500_000, 10_000));
+        assert_eq!(ledger.withdraw_rounding_surplus(1).err(), Some(ERROR_INSUFFICIENT_BALANCE));
+    }
+
+    #[test]
+    fn test_product_cap_limits_outstanding_principal() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [764, 2];
+        ledger.install(alice, 50_000);
+        let options = ProductTypeOptions { max_total_principal: Some(20_000), ..Default::default() };
+        let product_type_id = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &options).unwrap();
+
+        ledger.buy(alice, product_type_id, 12_000);
+        let second_cert = ledger.buy(alice, product_type_id, 8_000);
+        assert_eq!(ledger.product(product_type_id).unwrap().current_total_principal, 20_000);
+
+        let over = [product_type_id, 100, 0, 0];
+        assert_eq!(ledger.purchase(alice, over).err(), Some(ERROR_PRODUCT_CAP_EXCEEDED));
+        assert_eq!(ledger.idle_funds(&alice), 30_000);
+
+        // Redemption frees capacity again
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        ledger.redeem_all(alice, second_cert).unwrap();
+        assert_eq!(ledger.product(product_type_id).unwrap().current_total_principal, 12_000);
+        ledger.purchase(alice, over).unwrap();
+    }
+
+    #[test]
+    fn test_booster_applies_to_claims_and_auto_redemption() {
+        use crate::config::{PARAM_AUTO_REDEEM_ON_CLAIM, PARAM_BOOSTER_BPS, PARAM_BOOSTER_END_TICK};
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [765, 1];
+        ledger.install(alice, 20_000);
+        ledger.state.set_param(PARAM_BOOSTER_BPS, 500).unwrap();
+        ledger.state.set_param(PARAM_BOOSTER_END_TICK, 60 * TICKS_PER_DAY).unwrap();
+        ledger.state.set_param(PARAM_AUTO_REDEEM_ON_CLAIM, 1).unwrap();
+        let product_type_id = ledger.standard_product();
+        let first_cert = ledger.buy(alice, product_type_id, 10_000);
+        ledger.purchase(alice, [product_type_id, 10_000, 1000, 0]).unwrap();
+
+        // The claim redeems its matured certificate, the second one is redeemed directly
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        ledger.claim(alice, first_cert, 0).unwrap();
+        ledger.redeem_all(alice, first_cert + 1).unwrap();
+
+        // Both certificates paid 82 interest plus a 5% booster and returned their principal
+        assert_eq!(ledger.idle_funds(&alice), 20_000 + 2 * (82 + 4));
+    }
+
+    #[test]
+    fn test_claim_lookback_caps_dormant_backlog() {
+        use crate::config::PARAM_MAX_CLAIM_LOOKBACK_TICKS;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [766, 1];
+        ledger.install(alice, 20_000);
+        let product_type_id = ledger.create_product(365 * TICKS_PER_DAY, 1000, 100, true, &ProductTypeOptions::default()).unwrap();
+        let first_cert = ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(alice, product_type_id, 10_000);
+
+        // Untouched for the full year: 1000 accrued, a claim pays at most 30 days' worth (82)
+        ledger.state.set_param(PARAM_MAX_CLAIM_LOOKBACK_TICKS, 30 * TICKS_PER_DAY).unwrap();
+        ledger.state.counter = 365 * TICKS_PER_DAY;
+        ledger.claim(alice, first_cert, 0).unwrap();
+        assert_eq!(ledger.certificate(&alice, first_cert).unwrap().total_interest_claimed, 82);
+
+        // Later claims drain the rest, 82 at a time
+        let mut claims = 0;
+        while ledger.claim(alice, first_cert, 0).is_ok() {
+            claims += 1;
+        }
+        assert_eq!(claims, 12);
+        assert_eq!(ledger.certificate(&alice, first_cert).unwrap().total_interest_claimed, 1000);
+
+        // Redemption settles whatever a capped claim left behind
+        ledger.claim(alice, first_cert + 1, 0).unwrap();
+        ledger.redeem_all(alice, first_cert + 1).unwrap();
+        assert_eq!(ledger.certificate(&alice, first_cert + 1).unwrap().total_interest_claimed, 1000);
+        assert_eq!(ledger.idle_funds(&alice), 2 * 1000 + 10_000);
+    }
+
+    #[test]
+    fn test_withdraw_cooldown_spaces_withdrawals() {
+        use crate::config::PARAM_WITHDRAW_COOLDOWN_TICKS;
+
+        let mut ledger = Ledger::default();
+        let pid = [766, 3];
+        ledger.install(pid, 1000);
+        ledger.state.record_deposit(1000).unwrap();
+        ledger.state.set_param(PARAM_WITHDRAW_COOLDOWN_TICKS, 100).unwrap();
+        let withdraw = [100, 0x1111, 0];
+
+        // The first withdrawal is never held back
+        ledger.withdraw(pid, withdraw, 50).unwrap();
+        assert_eq!(ledger.withdraw(pid, withdraw, 149), Err(ERROR_WITHDRAW_TOO_EARLY));
+        assert_eq!(ledger.idle_funds(&pid), 900);
+
+        ledger.withdraw(pid, withdraw, 150).unwrap();
+        let player = ledger.player(&pid).unwrap();
+        assert_eq!(player.idle_funds, 800);
+        assert_eq!(player.last_withdraw_counter, 150);
+    }
+
+    #[test]
+    fn test_admin_adjust_recharge_corrections() {
+        use crate::config::PARAM_RECONCILE_MODE;
+
+        let mut state = GlobalState::new();
+        state.total_recharge_amount = 1000;
+
+        // Refused outside reconcile mode
+        assert_eq!(state.adjust_recharge(500), Err(ERROR_RECONCILE_MODE_DISABLED));
+        state.set_param(PARAM_RECONCILE_MODE, 1).unwrap();
+
+        assert_eq!(state.adjust_recharge(500), Ok((1000, 1500)));
+        assert_eq!(state.adjust_recharge(-1200), Ok((1500, 300)));
+
+        // Cannot take the total below zero
+        assert_eq!(state.adjust_recharge(-301), Err(ERROR_UNDERFLOW));
+        assert_eq!(state.total_recharge_amount, 300);
+        assert_eq!(state.adjust_recharge(i64::MIN), Err(ERROR_UNDERFLOW));
+    }
+
+    #[test]
+    fn test_daily_withdraw_limit_caps_same_day_withdrawals() {
+        use crate::config::PARAM_USER_DAILY_WITHDRAW_LIMIT;
+
+        let mut ledger = Ledger::default();
+        let pid = [768, 1];
+        ledger.install(pid, 3000);
+        ledger.state.record_deposit(3000).unwrap();
+        ledger.state.set_param(PARAM_USER_DAILY_WITHDRAW_LIMIT, 1000).unwrap();
+        let address = |amount| [amount, 0x1111, 0];
+
+        ledger.withdraw(pid, address(700), 10).unwrap();
+        assert_eq!(ledger.withdraw(pid, address(301), 20), Err(ERROR_USER_WITHDRAW_LIMIT));
+        ledger.withdraw(pid, address(300), 20).unwrap();
+        assert_eq!(ledger.withdraw(pid, address(1), TICKS_PER_DAY - 1), Err(ERROR_USER_WITHDRAW_LIMIT));
+
+        // The limit applies afresh the next day
+        ledger.withdraw(pid, address(1000), TICKS_PER_DAY).unwrap();
+        assert_eq!(ledger.idle_funds(&pid), 1000);
+    }
+
+    #[test]
+    fn test_product_counts_track_catalog_and_live_offerings() {
+        let mut ledger = Ledger::default();
+        let options = ProductTypeOptions::default();
+        let first = ledger.standard_product();
+        ledger.create_product(60 * TICKS_PER_DAY, 1200, 100, true, &options).unwrap();
+        ledger.create_product(90 * TICKS_PER_DAY, 1500, 100, false, &options).unwrap();
+        let counts = |ledger: &Ledger| (ledger.state.total_products_created(), ledger.state.active_products);
+        assert_eq!(counts(&ledger), (3, 2));
+
+        // Deactivating shrinks the live count only, repeated updates do not double count
+        ledger.modify_product(first, 1000, 30 * TICKS_PER_DAY, 100, false, &options).unwrap();
+        ledger.modify_product(first, 900, 30 * TICKS_PER_DAY, 100, false, &options).unwrap();
+        assert_eq!(counts(&ledger), (3, 1));
+        ledger.modify_product(first, 900, 30 * TICKS_PER_DAY, 100, true, &options).unwrap();
+        assert_eq!(counts(&ledger), (3, 2));
+
+        // A rejected modification leaves both figures alone
+        assert!(ledger.modify_product(first, 900, 0, 100, false, &options).is_err());
+        assert_eq!(counts(&ledger), (3, 2));
+    }
+
+    #[test]
+    fn test_points_interest_product_pays_points_not_funds() {
+        use crate::config::PARAM_INTEREST_POINTS_RATE;
+
+        let mut ledger = Ledger::default();
+        let alice = [770, 1];
+        ledger.install(alice, 10_000);
+        ledger.state.set_param(PARAM_INTEREST_POINTS_RATE, 10).unwrap();
+        let options = ProductTypeOptions { interest_currency: Some(PRINCIPAL_CURRENCY_POINTS), ..Default::default() };
+        let product_type_id = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &options).unwrap();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+
+        // One day of interest (2) is credited as points at the configured rate
+        ledger.state.counter = TICKS_PER_DAY;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        let player = ledger.player(&alice).unwrap();
+        assert_eq!((player.points, player.idle_funds), (20, 0));
+        let state = &ledger.state;
+        assert_eq!((state.total_points_issued, state.interest_claimed, state.rounding_surplus), (20, 0, 0));
+
+        // Points principal cannot pay USDT interest
+        let invalid = ProductTypeOptions {
+            principal_currency: Some(PRINCIPAL_CURRENCY_POINTS),
+            interest_currency: Some(PRINCIPAL_CURRENCY_USDT),
+            ..Default::default()
+        };
+        let counter = ledger.state.product_type_counter;
+        assert_eq!(ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &invalid), Err(ERROR_INVALID_INTEREST_CURRENCY));
+        assert_eq!(ledger.state.product_type_counter, counter);
+    }
+
+    #[test]
+    fn test_redeem_early_forfeits_penalty_share_of_interest() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [770, 2];
+        ledger.install(alice, 20_000);
+        let options = ProductTypeOptions { early_withdrawal_penalty_bps: Some(5000), ..Default::default() };
+        let product_type_id = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &options).unwrap();
+        let locked_id = ledger.standard_product();
+        let first_cert = ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(alice, locked_id, 10_000);
+
+        // Halfway through, 41 has accrued and half of it is forfeited
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+        ledger.redeem_early(alice, first_cert).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 10_020);
+        let cert = ledger.certificate(&alice, first_cert).unwrap();
+        assert_eq!((cert.status, cert.total_interest_claimed), (CertificateStatus::Redeemed, 20));
+        assert_eq!(ledger.product(product_type_id).unwrap().current_total_principal, 0);
+        assert_eq!(ledger.redeem_early(alice, first_cert), Err(ERROR_CERTIFICATE_ALREADY_REDEEMED));
+
+        // Products default to no early exit
+        assert_eq!(ledger.redeem_early(alice, first_cert + 1), Err(ERROR_EARLY_WITHDRAWAL_DISABLED));
+    }
+
+    #[test]
+    fn test_batch_purchase_is_all_or_nothing() {
+        let mut ledger = Ledger::default();
+        let alice = [771, 2];
+        ledger.install(alice, 10_000);
+        let options = ProductTypeOptions { max_total_principal: Some(5_000), ..Default::default() };
+        let short = ledger.standard_product();
+        let long = ledger.create_product(90 * TICKS_PER_DAY, 1200, 1_000, true, &options).unwrap();
+        let first_cert = GlobalState::new().certificate_counter;
+
+        // The second item is below its product's minimum: nothing is bought
+        assert_eq!(ledger.batch_purchase(alice, &[(short, 1_000), (long, 500), (short, 1_000)]), Err(ERROR_PRINCIPAL_AMOUNT_TOO_SMALL));
+        // Items sharing a capped product must fit the cap together
+        assert_eq!(ledger.batch_purchase(alice, &[(long, 3_000), (long, 3_000)]), Err(ERROR_PRODUCT_CAP_EXCEEDED));
+        assert_eq!(ledger.batch_purchase(alice, &[(short, 6_000), (long, 5_000)]), Err(ERROR_INSUFFICIENT_BALANCE));
+        assert_eq!(ledger.idle_funds(&alice), 10_000);
+        assert_eq!(ledger.state.certificate_counter, first_cert);
+        assert_eq!(ledger.product(long).unwrap().current_total_principal, 0);
+
+        ledger.batch_purchase(alice, &[(short, 1_000), (long, 2_000), (short, 3_000)]).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 4_000);
+        let owned: Vec<(u64, u64, u64)> = ledger
+            .certificates(&alice)
+            .iter()
+            .map(|c| (c.id, c.product_type_id, c.principal))
+            .collect();
+        assert_eq!(owned, vec![(first_cert, short, 1_000), (first_cert + 1, long, 2_000), (first_cert + 2, short, 3_000)]);
+        assert_eq!(ledger.product(long).unwrap().current_total_principal, 2_000);
+
+        let oversized = vec![(short, 100); crate::config::MAX_BATCH_PURCHASE + 1];
+        assert_eq!(ledger.batch_purchase(alice, &oversized), Err(ERROR_INVALID_PARAM));
+    }
+
+    #[test]
+    fn test_admin_force_withdraw_sweeps_player_position() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let (admin, alice) = ([772, 1], [772, 2]);
+        ledger.install(alice, 25_000);
+        ledger.state.record_deposit(25_000).unwrap();
+        let short = ledger.standard_product();
+        let long = ledger.create_product(90 * TICKS_PER_DAY, 1200, 100, true, &ProductTypeOptions::default()).unwrap();
+        let first_cert = ledger.buy(alice, short, 10_000);
+        ledger.buy(alice, long, 10_000);
+        let status = |ledger: &Ledger, id| ledger.certificate(&alice, id).unwrap().status;
+
+        // Idle funds plus the matured certificate with its interest; the active one is left alone
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        let address = [0xabcd << 32, 0x1111, 0x2222];
+        assert_eq!(ledger.force_withdraw(admin, alice, address, false), Ok(15_082));
+        assert_eq!(ledger.idle_funds(&alice), 0);
+        assert_eq!((status(&ledger, first_cert), status(&ledger, first_cert + 1)), (CertificateStatus::Redeemed, CertificateStatus::Active));
+        assert_eq!(ledger.state.total_funds, 25_000 - 15_082);
+
+        // Forcing maturity pays 30 of 90 days' interest, but the sweep must fit total_funds first
+        assert_eq!(ledger.force_withdraw(admin, alice, address, true), Err(ERROR_UNDERFLOW));
+        assert_eq!(status(&ledger, first_cert + 1), CertificateStatus::Active);
+        ledger.state.record_deposit(1_000).unwrap();
+        ledger.force_withdraw(admin, alice, address, true).unwrap();
+        let cert = ledger.certificate(&alice, first_cert + 1).unwrap();
+        assert_eq!((cert.status, cert.maturity_time, cert.total_interest_claimed), (CertificateStatus::Redeemed, 30 * TICKS_PER_DAY, 98));
+        assert_eq!(ledger.state.total_funds, 1_000 - 180);
+
+        // The admin is an installed player too, it still cannot sweep itself
+        ledger.install(admin, 0);
+        assert_eq!(ledger.force_withdraw(admin, admin, address, true), Err(ERROR_INVALID_PARAM));
+    }
+
+    #[test]
+    fn test_liability_aggregates_follow_certificate_lifecycle() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [772, 3];
+        ledger.install(alice, 20_000);
+        let product_type_id = ledger.standard_product();
+        let options = ProductTypeOptions { early_withdrawal_penalty_bps: Some(5000), ..Default::default() };
+        let exit_id = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &options).unwrap();
+        let liabilities = |ledger: &Ledger| (ledger.state.total_certificate_principal, ledger.state.total_interest_owed);
+
+        // Purchases book the principal and the 82 each certificate earns by maturity
+        let first_cert = ledger.buy(alice, product_type_id, 10_000);
+        ledger.buy(alice, exit_id, 10_000);
+        assert_eq!(liabilities(&ledger), (20_000, 164));
+
+        // Claims pay interest down, an early exit drops the rest of its schedule
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+        ledger.claim(alice, first_cert, 0).unwrap();
+        assert_eq!(liabilities(&ledger), (20_000, 123));
+        ledger.redeem_early(alice, first_cert + 1).unwrap();
+        assert_eq!(liabilities(&ledger), (10_000, 41));
+
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        ledger.redeem_all(alice, first_cert).unwrap();
+        assert_eq!(liabilities(&ledger), (0, 0));
+    }
+
+    #[test]
+    fn test_interest_claims_below_minimum_are_rejected() {
+        use crate::certificate::MIN_INTEREST_CLAIM;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [773, 2];
+        ledger.install(alice, 10_000);
+        // 10,000 at 10% earns 2.74 a day
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+
+        // Nothing and a single unit are both below the minimum
+        assert_eq!(ledger.claim(alice, cert_id, 0), Err(ERROR_CLAIM_TOO_SMALL));
+        ledger.state.counter = TICKS_PER_DAY / 2;
+        assert_eq!(ledger.claim(alice, cert_id, 0), Err(ERROR_CLAIM_TOO_SMALL));
+
+        ledger.state.counter = TICKS_PER_DAY;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), MIN_INTEREST_CLAIM);
+
+        // The last unit at maturity cannot be claimed directly, redemption still pays it
+        ledger.state.counter = 30 * TICKS_PER_DAY - TICKS_PER_DAY / 4;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 81);
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        assert_eq!(ledger.claim(alice, cert_id, 0), Err(ERROR_CLAIM_TOO_SMALL));
+        ledger.redeem_all(alice, cert_id).unwrap();
+        assert_eq!(ledger.idle_funds(&alice), 10_000 + 82);
+    }
+
+    #[test]
+    fn test_closed_certificates_hold_history_only() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [774, 1];
+        ledger.install(alice, 30_000);
+        let short = ledger.standard_product();
+        let long = ledger.create_product(90 * TICKS_PER_DAY, 1000, 100, true, &ProductTypeOptions::default()).unwrap();
+        ledger.batch_purchase(alice, &[(short, 10_000), (long, 10_000), (short, 10_000)]).unwrap();
+        let first_cert = GlobalState::new().certificate_counter;
+        let closed = |ledger: &Ledger, max| CertificateManager::closed_certificates_from(ledger.certificates(&alice), max);
+        assert!(closed(&ledger, 10).is_empty());
+
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        ledger.redeem_all(alice, first_cert).unwrap();
+        ledger.redeem_all(alice, first_cert + 2).unwrap();
+
+        let fields: Vec<_> = closed(&ledger, 10).iter().map(|c| (c.id, c.status.clone(), c.total_interest_claimed)).collect();
+        assert_eq!(fields, vec![
+            (first_cert, CertificateStatus::Redeemed, 82),
+            (first_cert + 2, CertificateStatus::Redeemed, 82),
+        ]);
+        assert_eq!(closed(&ledger, 1).len(), 1);
+
+        // Live positions no longer list them
+        let open: Vec<u64> = ledger.views(&alice).iter().map(|v| v.certificate.id).collect();
+        assert_eq!(open, vec![first_cert + 1]);
+    }
+
+    #[test]
+    fn test_claim_interest_partial_amounts() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [774, 2];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+        let claimed = |ledger: &Ledger| ledger.certificate(&alice, cert_id).unwrap().total_interest_claimed;
+
+        // 82 is available at maturity: two lots of 30, then the remaining 22 and no more
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        ledger.claim(alice, cert_id, 30).unwrap();
+        ledger.claim(alice, cert_id, 30).unwrap();
+        assert_eq!((claimed(&ledger), ledger.idle_funds(&alice)), (60, 60));
+        assert_eq!(ledger.claim(alice, cert_id, 23), Err(ERROR_INSUFFICIENT_INTEREST));
+        assert_eq!(ledger.claim(alice, cert_id, 1), Err(ERROR_CLAIM_TOO_SMALL));
+        ledger.claim(alice, cert_id, 22).unwrap();
+        assert_eq!(claimed(&ledger), 82);
+        assert_eq!(ledger.claim(alice, cert_id, 0), Err(ERROR_CLAIM_TOO_SMALL));
+        assert_eq!(ledger.state.interest_claimed, 82);
+    }
+
+    #[test]
+    fn test_claim_after_maturity_marks_certificate_matured() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [775, 1];
+        ledger.install(alice, 20_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+        let status = |ledger: &Ledger| ledger.certificate(&alice, cert_id).unwrap().status;
+
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        assert_eq!(status(&ledger), CertificateStatus::Active);
+
+        // The stored certificate carries the new status
+        ledger.state.counter = 31 * TICKS_PER_DAY;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        assert_eq!(status(&ledger), CertificateStatus::Matured);
+    }
+
+    #[test]
+    fn test_claim_on_recharge_certificate_is_non_interest_bearing() {
+        let mut ledger = Ledger::default();
+        let alice = [776, 1];
+        ledger.install(alice, 1000);
+        ledger.state.total_funds = 1000;
+
+        let cert_id = ledger.buy(alice, 0, 500);
+
+        ledger.state.counter += 30 * 17280;
+        assert_eq!(ledger.claim(alice, cert_id, 0), Err(ERROR_PRODUCT_NON_INTEREST_BEARING));
+        assert_eq!(decode_error(ERROR_PRODUCT_NON_INTEREST_BEARING), "ProductNonInterestBearing");
+        assert_eq!(ledger.idle_funds(&alice), 500);
+    }
+
+    #[test]
+    fn test_product_certificate_count_cap() {
+        let mut ledger = Ledger::default();
+        let alice = [777, 1];
+        ledger.install(alice, 10_000);
+        let options = ProductTypeOptions { max_certificates: Some(3), ..Default::default() };
+        let product_type_id = ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &options).unwrap();
+
+        // A batch that would overshoot the count is rejected as a whole
+        assert_eq!(ledger.batch_purchase(alice, &[(product_type_id, 100); 4]), Err(ERROR_PRODUCT_CERT_LIMIT));
+        assert_eq!(ledger.product(product_type_id).unwrap().certificate_count, 0);
+
+        let first_cert = ledger.buy(alice, product_type_id, 100);
+        ledger.buy(alice, product_type_id, 100);
+        ledger.buy(alice, product_type_id, 100);
+        assert_eq!(ledger.product(product_type_id).unwrap().certificate_count, 3);
+        let over = [product_type_id, 100, 0, 0];
+        assert_eq!(ledger.purchase(alice, over).err(), Some(ERROR_PRODUCT_CERT_LIMIT));
+        assert_eq!(ledger.idle_funds(&alice), 9_700);
+
+        // Redemption does not free a slot, raising the cap does
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        ledger.redeem_all(alice, first_cert).unwrap();
+        assert_eq!(ledger.purchase(alice, over).err(), Some(ERROR_PRODUCT_CERT_LIMIT));
+        let raise = ProductTypeOptions { max_certificates: Some(4), ..Default::default() };
+        ledger.modify_product(product_type_id, 1000, 30 * TICKS_PER_DAY, 100, true, &raise).unwrap();
+        ledger.purchase(alice, over).unwrap();
+    }
+
+    #[test]
+    fn test_player_lifetime_statistics() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [778, 2];
+        ledger.install(alice, 0);
+        let product_type_id = ledger.standard_product();
+
+        ledger.deposit(alice, 10_000).unwrap();
+        ledger.deposit(alice, 5_000).unwrap();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        ledger.redeem_all(alice, cert_id).unwrap();
+
+        // Redemption pays the remaining interest before returning the principal
+        let data = ledger.player(&alice).unwrap();
+        assert_eq!(data.lifetime_deposited, 15_000);
+        assert_eq!(data.lifetime_interest_claimed, 82);
+        assert_eq!(data.lifetime_principal_redeemed, 10_000);
+        assert_eq!(data.idle_funds, 15_082);
+    }
+
+    #[test]
+    fn test_rollover_certificate_repurchases_principal_plus_interest() {
+        use crate::certificate::CertificateStatus;
+
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [779, 1];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let first_cert = ledger.buy(alice, product_type_id, 10_000);
+
+        ledger.state.counter = 29 * TICKS_PER_DAY;
+        assert_eq!(ledger.rollover(alice, first_cert).err(), Some(ERROR_CERTIFICATE_NOT_MATURED));
+
+        // An inactive product cannot be rolled into, the old certificate is left as it was
+        ledger.state.counter = 30 * TICKS_PER_DAY;
+        let options = ProductTypeOptions::default();
+        ledger.modify_product(product_type_id, 1200, 30 * TICKS_PER_DAY, 100, false, &options).unwrap();
+        assert_eq!(ledger.rollover(alice, first_cert).err(), Some(ERROR_PRODUCT_TYPE_INACTIVE));
+        assert_eq!(ledger.certificate(&alice, first_cert).unwrap().total_interest_claimed, 0);
+
+        ledger.modify_product(product_type_id, 1200, 30 * TICKS_PER_DAY, 100, true, &options).unwrap();
+        ledger.rollover(alice, first_cert).unwrap();
+        let old = ledger.certificate(&alice, first_cert).unwrap();
+        assert_eq!(old.status, CertificateStatus::Redeemed);
+        assert_eq!(old.total_interest_claimed, 82);
+
+        let new = ledger.certificate(&alice, first_cert + 1).unwrap();
+        assert_eq!(new.principal, 10_000 + 82);
+        assert_eq!(new.locked_apy, 1200);
+        assert_eq!(new.purchase_time, 30 * TICKS_PER_DAY);
+        assert_eq!(new.maturity_time, 60 * TICKS_PER_DAY);
+        assert_eq!(ledger.idle_funds(&alice), 0);
+        assert_eq!(ledger.product(product_type_id).unwrap().current_total_principal, 10_082);
+        assert_eq!(ledger.rollover(alice, first_cert).err(), Some(ERROR_CERTIFICATE_ALREADY_REDEEMED));
+    }
+
+    #[test]
+    fn test_withdraw_rejects_malformed_address() {
+        use crate::config::validate_address_parts;
+
+        assert!(validate_address_parts([0xabcd, 0x1111, 0x2222]));
+        assert!(validate_address_parts([0, 0, 1]));
+        assert!(validate_address_parts([u32::MAX as u64, u64::MAX, u64::MAX]));
+        assert!(!validate_address_parts([0, 0, 0]));
+        assert!(!validate_address_parts([1 << 32, 0x1111, 0x2222]));
+        assert!(!validate_address_parts([u64::MAX, 0, 0]));
+
+        let mut ledger = Ledger::default();
+        let pid = [780, 1];
+        ledger.install(pid, 1000);
+        ledger.state.record_deposit(1000).unwrap();
+
+        // Only the amount and no address, for funds and for points alike
+        assert_eq!(ledger.withdraw(pid, [400, 0, 0], 0), Err(ERROR_INVALID_WITHDRAW_ADDRESS));
+        assert_eq!(check_withdraw_address(&[1, 0, 0]), Err(ERROR_INVALID_WITHDRAW_ADDRESS));
+        assert_eq!(ledger.idle_funds(&pid), 1000);
+
+        ledger.withdraw(pid, [(0xabcd << 32) + 400, 0, 0], 0).unwrap();
+        assert_eq!(ledger.idle_funds(&pid), 600);
+    }
+
+    #[test]
+    fn test_exhausted_counters_are_rejected() {
+        let mut ledger = Ledger::default();
+        let options = ProductTypeOptions::default();
+        ledger.state.product_type_counter = u64::MAX;
+        assert_eq!(ledger.create_product(30 * TICKS_PER_DAY, 1000, 100, true, &options), Err(ERROR_COUNTER_OVERFLOW));
+        assert_eq!(ledger.state.product_type_counter, u64::MAX);
+
+        // The last id before the counter is exhausted is still handed out
+        ledger.state.product_type_counter = u64::MAX - 1;
+        let product_type_id = ledger.standard_product();
+        assert_eq!(product_type_id, u64::MAX - 1);
+
+        let alice = [781, 1];
+        ledger.install(alice, 1000);
+        ledger.state.certificate_counter = u64::MAX;
+        assert_eq!(ledger.purchase(alice, [product_type_id, 500, 0, 0]).err(), Some(ERROR_COUNTER_OVERFLOW));
+        assert_eq!(ledger.idle_funds(&alice), 1000);
+        assert_eq!(ledger.product(product_type_id).unwrap().current_total_principal, 0);
+    }
+
+    #[test]
+    fn test_claim_that_cannot_be_credited_is_not_recorded() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [792, 1];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+
+        // A balance that cannot take the interest fails the claim before the certificate is updated
+        ledger.players.get_mut(&alice).unwrap().idle_funds = u64::MAX;
+        assert_eq!(ledger.claim(alice, cert_id, 0), Err(ERROR_OVERFLOW));
+        assert_eq!(ledger.claim_to_target(alice, cert_id, 11), Err(ERROR_OVERFLOW));
+        assert_eq!(ledger.certificate(&alice, cert_id).unwrap().total_interest_claimed, 0);
+        assert_eq!(ledger.state.interest_claimed, 0);
+
+        ledger.players.get_mut(&alice).unwrap().idle_funds = 0;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        assert_eq!(ledger.certificate(&alice, cert_id).unwrap().total_interest_claimed, 41);
+        assert_eq!((ledger.idle_funds(&alice), ledger.state.interest_claimed), (41, 41));
+    }
+
+    #[test]
+    fn test_claim_interest_to_target_leaves_remaining_balance() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [782, 1];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+        let available = |ledger: &Ledger| {
+            let cert = ledger.certificate(&alice, cert_id).unwrap();
+            cert.calculate_available_interest(ledger.state.counter).unwrap()
+        };
+
+        // 41 is available after 15 days
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+        assert_eq!(ledger.claim_to_target(alice, cert_id, 42), Err(ERROR_INVALID_CLAIM_TARGET));
+        assert_eq!(ledger.claim_to_target(alice, cert_id, 41), Err(ERROR_INSUFFICIENT_INTEREST));
+
+        ledger.claim_to_target(alice, cert_id, 11).unwrap();
+        assert_eq!(available(&ledger), 11);
+        assert_eq!(ledger.idle_funds(&alice), 30);
+
+        ledger.claim_to_target(alice, cert_id, 0).unwrap();
+        assert_eq!(available(&ledger), 0);
+        assert_eq!(ledger.idle_funds(&alice), 41);
+        assert_eq!(ledger.claim_to_target(alice, cert_id, 0), Err(ERROR_INSUFFICIENT_INTEREST));
+    }
+
+    #[test]
+    fn test_project_interest_until_maturity() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let alice = [783, 1];
+        ledger.install(alice, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.buy(alice, product_type_id, 10_000);
+        let project = |ledger: &Ledger, at_time| {
+            CertificateManager::project_interest_with(&ledger.certificate(&alice, cert_id)?, &ledger.state, at_time)
+        };
+
+        assert_eq!(project(&ledger, 0), Ok(0));
+        assert_eq!(project(&ledger, 15 * TICKS_PER_DAY), Ok(41));
+        assert_eq!(project(&ledger, 30 * TICKS_PER_DAY), Ok(82));
+        assert_eq!(project(&ledger, 365 * TICKS_PER_DAY), Ok(82));
+
+        // Projections are net of claims and cannot look into the past
+        ledger.state.counter = 15 * TICKS_PER_DAY;
+        ledger.claim(alice, cert_id, 0).unwrap();
+        assert_eq!(project(&ledger, 30 * TICKS_PER_DAY), Ok(41));
+        assert_eq!(project(&ledger, 15 * TICKS_PER_DAY - 1), Err(ERROR_INVALID_PARAM));
+        assert_eq!(ledger.certificate(&[783, 2], cert_id).err(), Some(ERROR_CERTIFICATE_NOT_OWNED));
+    }
+
+    #[test]
+    fn test_redeem_points_for_funds_at_configured_rate() {
+        use crate::config::PARAM_POINTS_TO_FUNDS_RATE;
+
+        let mut ledger = Ledger::default();
+        let alice = [784, 1];
+        ledger.install(alice, 0);
+        ledger.players.get_mut(&alice).unwrap().points = 1_050;
+
+        // Disabled until a rate is configured
+        assert_eq!(ledger.redeem_points(alice, 1_050), Err(ERROR_POINTS_CONVERSION_DISABLED));
+
+        ledger.state.set_param(PARAM_POINTS_TO_FUNDS_RATE, 100).unwrap();
+        assert_eq!(ledger.redeem_points(alice, 99), Err(ERROR_INVALID_POINTS_AMOUNT));
+        assert_eq!(ledger.redeem_points(alice, 1_100), Err(ERROR_INSUFFICIENT_POINTS));
+
+        // Only whole units are converted, the remaining 50 points stay
+        ledger.redeem_points(alice, 1_050).unwrap();
+        let data = ledger.player(&alice).unwrap();
+        assert_eq!((data.points, data.idle_funds), (50, 10));
+        assert_eq!((ledger.state.total_funds, ledger.state.total_points_withdrawn), (10, 1_000));
+    }
+
+    #[test]
+    fn test_ticks_auto_claim_for_opted_in_certificates() {
+        let mut ledger = Ledger::default();
+        ledger.state.total_recharge_amount += 1_000_000;
+        let (alice, bob) = ([785, 1], [785, 2]);
+        ledger.install(alice, 10_000);
+        ledger.install(bob, 10_000);
+        let product_type_id = ledger.standard_product();
+        let cert_id = ledger.purchase(alice, [product_type_id, 10_000, 0, 1]).unwrap().id;
+        ledger.buy(bob, product_type_id, 10_000);
+
+        // A paused system does not claim on anyone's behalf
+        ledger.state.counter = 15 * TICKS_PER_DAY - 2;
+        ledger.state.paused = true;
+        ledger.tick();
+        assert_eq!(ledger.idle_funds(&alice), 0);
+
+        // Only alice opted in; after 15 days her 41 is claimed, the next tick has nothing new
+        ledger.state.paused = false;
+        ledger.tick();
+        assert_eq!((ledger.idle_funds(&alice), ledger.idle_funds(&bob)), (41, 0));
+        ledger.tick();
+        assert_eq!(ledger.idle_funds(&alice), 41);
+
+        ledger.state.counter = 30 * TICKS_PER_DAY - 1;
+        ledger.tick();
+        assert_eq!(ledger.idle_funds(&alice), 82);
+        assert_eq!(ledger.certificate(&bob, cert_id).unwrap().total_interest_claimed, 0);
+
+        // A redeemed certificate drops out of the pass
+        ledger.redeem_all(alice, cert_id).unwrap();
+        ledger.tick();
+        assert_eq!(ledger.idle_funds(&alice), 10_082);
+    }
+}
\ No newline at end of file
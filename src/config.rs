@@ -10,7 +10,7 @@ lazy_static::lazy_static! {
 
 #[derive(Serialize, Clone)]
 pub struct Config {
-    actions: [&'static str; 10],
+    actions: [&'static str; 33],
     name: [&'static str; 1],
 }
 
@@ -26,7 +26,30 @@ lazy_static::lazy_static! {
             "claim_interest",
             "redeem_principal",
             "admin_withdraw_to_multisig",
-            "set_reserve_ratio"
+            "set_reserve_ratio",
+            "set_payouts_paused",
+            "set_global_param",
+            "withdraw_reserve_pool",
+            "set_whitelist_batch",
+            "transfer_certificate",
+            "redeem_principal_partial",
+            "redeem_all",
+            "set_paused",
+            "admin_adjust_recharge",
+            "grant_role",
+            "revoke_role",
+            "redeem_early",
+            "batch_purchase",
+            "admin_force_withdraw_player",
+            "rollover_certificate",
+            "claim_interest_to_target",
+            "redeem_points_for_funds",
+            "admin_repay",
+            "withdraw_rounding_surplus",
+            "set_product_type_status",
+            "sweep_dust",
+            "deposit_and_purchase",
+            "set_apy_steps"
         ],
         name: ["zkwasm_solar_mining"],
     };
@@ -53,6 +76,43 @@ pub const EVENT_WITHDRAWAL: u64 = 12;
 pub const EVENT_POINTS_WITHDRAWAL: u64 = 13;
 pub const EVENT_ADMIN_WITHDRAWAL: u64 = 14;
 pub const EVENT_RESERVE_RATIO_CHANGE: u64 = 15;
+pub const EVENT_PAYOUTS_PAUSED: u64 = 16;
+pub const EVENT_GLOBAL_PARAM_CHANGED: u64 = 17;
+pub const EVENT_BOOSTER_BONUS: u64 = 18;
+pub const EVENT_RESERVE_POOL_WITHDRAWAL: u64 = 19;
+pub const EVENT_WHITELIST_BATCH: u64 = 20;
+pub const EVENT_CERTIFICATE_TRANSFERRED: u64 = 21;
+pub const EVENT_SYSTEM_PAUSED: u64 = 22;
+pub const EVENT_RECHARGE_ADJUSTED: u64 = 23;
+pub const EVENT_ROLE_CHANGED: u64 = 24;
+pub const EVENT_ADMIN_REPAYMENT: u64 = 25;
+pub const EVENT_ROUNDING_SURPLUS_WITHDRAWAL: u64 = 26;
+
+/// Every event type by name, served to indexers by query_event_types
+pub const EVENT_TYPES: &[(&str, u64)] = &[
+    ("indexed_object", EVENT_INDEXED_OBJECT),
+    ("product_type_created", EVENT_PRODUCT_TYPE_CREATED),
+    ("product_type_modified", EVENT_PRODUCT_TYPE_MODIFIED),
+    ("certificate_purchased", EVENT_CERTIFICATE_PURCHASED),
+    ("interest_claimed", EVENT_INTEREST_CLAIMED),
+    ("principal_redeemed", EVENT_PRINCIPAL_REDEEMED),
+    ("deposit", EVENT_DEPOSIT),
+    ("withdrawal", EVENT_WITHDRAWAL),
+    ("points_withdrawal", EVENT_POINTS_WITHDRAWAL),
+    ("admin_withdrawal", EVENT_ADMIN_WITHDRAWAL),
+    ("reserve_ratio_change", EVENT_RESERVE_RATIO_CHANGE),
+    ("payouts_paused", EVENT_PAYOUTS_PAUSED),
+    ("global_param_changed", EVENT_GLOBAL_PARAM_CHANGED),
+    ("booster_bonus", EVENT_BOOSTER_BONUS),
+    ("reserve_pool_withdrawal", EVENT_RESERVE_POOL_WITHDRAWAL),
+    ("whitelist_batch", EVENT_WHITELIST_BATCH),
+    ("certificate_transferred", EVENT_CERTIFICATE_TRANSFERRED),
+    ("system_paused", EVENT_SYSTEM_PAUSED),
+    ("recharge_adjusted", EVENT_RECHARGE_ADJUSTED),
+    ("role_changed", EVENT_ROLE_CHANGED),
+    ("admin_repayment", EVENT_ADMIN_REPAYMENT),
+    ("rounding_surplus_withdrawal", EVENT_ROUNDING_SURPLUS_WITHDRAWAL),
+];
 
 // Certificate info constants for IndexedObject (following launchpad pattern)
 pub const PRODUCT_TYPE_INFO: u64 = 1;
@@ -76,7 +136,15 @@ pub fn get_multisig_address_parts() -> (u64, u64, u64) {
     (CERTIFICATE_MULTISIG_FIRST, CERTIFICATE_MULTISIG_MIDDLE, CERTIFICATE_MULTISIG_LAST)
 }
 
-/// Validate reserve ratio (must be <= 50%)
+/// Validate withdrawal address parts `[first, middle, last]` as produced by ts/src/address_parser.ts
+///
+/// `first` is the 4-byte leading limb on its own, so its high 32 bits must be zero;
+/// the all-zero address is rejected as funds sent there are lost
+pub fn validate_address_parts(parts: [u64; 3]) -> bool {
+    parts[0] >> 32 == 0 && parts != [0; 3]
+}
+
+/// Validate reserve ratio (must be <= 50%), the MIN_RESERVE_RATIO floor is checked separately
 pub fn validate_reserve_ratio(reserve_ratio: u64) -> bool {
     reserve_ratio <= MAX_RESERVE_RATIO
 }
@@ -86,38 +154,139 @@ pub const POINTS_DIVISOR: u64 = 17280;
 pub const MIN_POINTS_WITHDRAWAL: u64 = 1; // Minimum 1 effective point withdrawal (will require 17280 actual points)
 
 // Time conversion helpers (5 seconds per tick) - used by certificate system
+// SECONDS_PER_TICK is the default of the seconds_per_tick global param
 pub const SECONDS_PER_TICK: u64 = 5;
 pub const TICKS_PER_DAY: u64 = 17280;
+pub const MAX_SECONDS_PER_TICK: u64 = 3600; // A tick may last at most an hour and must divide a day
+
+/// Ticks in a day at `seconds_per_tick`, TICKS_PER_DAY at the default tick length
+pub fn ticks_per_day(seconds_per_tick: u64) -> u64 {
+    crate::certificate::SECONDS_PER_DAY.checked_div(seconds_per_tick).unwrap_or(TICKS_PER_DAY)
+}
+
+// Global parameter ids for SetGlobalParam
+pub const PARAM_TVL_SAMPLE_INTERVAL: u64 = 1;
+pub const PARAM_BOOSTER_BPS: u64 = 2;
+pub const PARAM_BOOSTER_END_TICK: u64 = 3;
+pub const PARAM_DEPOSIT_MATURATION_TICKS: u64 = 4;
+pub const PARAM_RESERVE_RAMP_THRESHOLD: u64 = 5;
+pub const PARAM_RESERVE_RAMP_RATIO: u64 = 6;
+pub const PARAM_AUTO_REDEEM_ON_CLAIM: u64 = 7;
+pub const PARAM_QUERY_PAGE_SIZE: u64 = 8;
+pub const PARAM_WELCOME_POINTS: u64 = 9;
+pub const PARAM_MAX_CLAIM_LOOKBACK_TICKS: u64 = 10;
+pub const PARAM_WITHDRAW_COOLDOWN_TICKS: u64 = 11;
+pub const PARAM_RECONCILE_MODE: u64 = 12;
+pub const PARAM_USER_DAILY_WITHDRAW_LIMIT: u64 = 13;
+pub const PARAM_INTEREST_POINTS_RATE: u64 = 14;
+pub const PARAM_MIN_ABSOLUTE_RESERVE: u64 = 15;
+pub const PARAM_POINTS_TO_FUNDS_RATE: u64 = 16;
+pub const PARAM_MAX_INTEREST_PER_TICK: u64 = 17;
+pub const PARAM_INTEREST_TICK_PARTIAL: u64 = 18;
+pub const PARAM_SECONDS_PER_TICK: u64 = 19;
+pub const PARAM_WITHDRAWAL_FEE: u64 = 20;
+pub const PARAM_TRANSFER_FEE: u64 = 21;
+
+// Points credited per unit of interest when a USDT product pays interest in points
+pub const DEFAULT_INTEREST_POINTS_RATE: u64 = 1;
+
+// Interest booster: claims before booster_end_tick pay an extra booster_bps on top of earned interest
+pub const MAX_BOOSTER_BPS: u64 = 10000; // At most doubles the payout
+
+// TVL time series sampling (taken in preempt)
+pub const DEFAULT_TVL_SAMPLE_INTERVAL: u64 = TICKS_PER_DAY; // One sample per day
+pub const TVL_SAMPLE_CAPACITY: usize = 64; // Oldest sample is overwritten when full
+
+// List queries return at most query_page_size entries per page
+pub const DEFAULT_QUERY_PAGE_SIZE: u64 = 64;
+
+// Idle-funds withdrawals per player are spaced at least this many ticks apart (0 disables)
+pub const WITHDRAW_COOLDOWN_TICKS: u64 = 0;
+
+// Admin roles (bitmask); ADMIN_PUBKEY always holds every role
+pub const ROLE_SUPERADMIN: u64 = 1; // Grants and revokes roles, passes every role check
+pub const ROLE_TREASURY: u64 = 2; // Deposits, reserve ratio and fund movements to the multisig
+pub const ROLE_PRODUCT: u64 = 4; // Product types and whitelists
+pub const ROLE_OPERATOR: u64 = 8; // Pauses and global parameters
+pub const ROLE_MASK: u64 = ROLE_SUPERADMIN | ROLE_TREASURY | ROLE_PRODUCT | ROLE_OPERATOR;
+pub const MAX_ADMIN_ROLES: usize = 16; // Registry entries besides ADMIN_PUBKEY
+
+// Whitelist management
+pub const MAX_WHITELIST_BATCH: usize = 16; // Users per SetWhitelistBatch
+
+// Certificates bought by one BatchPurchase
+pub const MAX_BATCH_PURCHASE: usize = 8;
+
+// Auto-claim certificates visited by one tick, bounding the work each tick adds to the trace
+pub const MAX_AUTO_CLAIMS_PER_TICK: usize = 4;
 
 // Reserve ratio and recharge system constants
 pub const MAX_RESERVE_RATIO: u64 = 5000; // Max 50% reserve ratio
+pub const MIN_RESERVE_RATIO: u64 = 500; // Min 5% reserve ratio set through SetReserveRatio
+pub const RESERVE_RATIO_CHANGE_COOLDOWN_TICKS: u64 = TICKS_PER_DAY; // Min ticks between reserve ratio changes
+pub const DEFAULT_RESERVE_RAMP_RATIO: u64 = 5000; // 50% reserve while TVL is below the ramp threshold
 pub const RECHARGE_PRODUCT_DURATION: u64 = 36500; // 100 years in days
 pub const RECHARGE_PRODUCT_APY: u64 = 0; // 0% APY for recharge products
 
-/// Calculate available funds for admin withdrawal with reserve ratio
-/// Formula: (total_funds + total_recharge_amount - cumulative_admin_withdrawals) * (1 - reserve_ratio)
-pub fn calculate_available_funds(
+/// Reserve ratio in force at the given TVL
+/// Below `reserve_ramp_threshold` the higher of `reserve_ramp_ratio` and `reserve_ratio` applies
+pub fn effective_reserve_ratio(
+    total_funds: u64,
+    reserve_ratio: u64,
+    reserve_ramp_threshold: u64,
+    reserve_ramp_ratio: u64
+) -> u64 {
+    if total_funds < reserve_ramp_threshold {
+        reserve_ramp_ratio.max(reserve_ratio)
+    } else {
+        reserve_ratio
+    }
+}
+
+/// Funds still held in the treasury: total_funds + total_recharge_amount - cumulative_admin_withdrawals
+///
+/// User withdrawals are limited to this so funds lent to the admin stay covered
+pub fn calculate_user_withdrawable(
     total_funds: u64,
     cumulative_admin_withdrawals: u64,
-    total_recharge_amount: u64,
-    reserve_ratio: u64
+    total_recharge_amount: u64
 ) -> Result<u64, u32> {
-    use crate::math_safe::{safe_sub, safe_add, safe_mul};
-    use crate::error::ERROR_UNDERFLOW;
+    use crate::math_safe::{safe_sub, safe_add};
     
     // Calculate base user withdrawable funds: 先加后减
     let funds_with_recharge = safe_add(total_funds, total_recharge_amount)?;
     
     // 判断是否小于admin提取金额，如果是则返回0
-    let user_withdrawable = if funds_with_recharge >= cumulative_admin_withdrawals {
-        safe_sub(funds_with_recharge, cumulative_admin_withdrawals)?
+    if funds_with_recharge >= cumulative_admin_withdrawals {
+        safe_sub(funds_with_recharge, cumulative_admin_withdrawals)
     } else {
-        0 // Admin提取超过了总资金，用户无资金可提取
-    };
+        Ok(0) // Admin提取超过了总资金，用户无资金可提取
+    }
+}
+
+/// Calculate available funds for admin withdrawal with reserve ratio
+///
+/// Formula: (total_funds + total_recharge_amount - cumulative_admin_withdrawals) * (1 - effective_reserve_ratio),
+/// further limited so at least min_absolute_reserve stays in the system
+pub fn calculate_available_funds(
+    total_funds: u64,
+    cumulative_admin_withdrawals: u64,
+    total_recharge_amount: u64,
+    reserve_ratio: u64,
+    reserve_ramp_threshold: u64,
+    reserve_ramp_ratio: u64,
+    min_absolute_reserve: u64
+) -> Result<u64, u32> {
+    use crate::math_safe::{safe_sub, safe_mul};
+    use crate::error::ERROR_UNDERFLOW;
+    
+    let user_withdrawable = calculate_user_withdrawable(total_funds, cumulative_admin_withdrawals, total_recharge_amount)?;
     
-    // Apply reserve ratio for admin borrowable funds
+    // Apply reserve ratio for admin borrowable funds, raised during ramp-up
+    let reserve_ratio = effective_reserve_ratio(total_funds, reserve_ratio, reserve_ramp_threshold, reserve_ramp_ratio);
     let multiplier = safe_sub(10000u64, reserve_ratio).map_err(|_| ERROR_UNDERFLOW)?;
     let available_before_division = safe_mul(user_withdrawable, multiplier)?;
     
-    Ok(available_before_division / 10000)
+    // The larger of the two reserves binds
+    Ok((available_before_division / 10000).min(user_withdrawable.saturating_sub(min_absolute_reserve)))
 } 
\ No newline at end of file
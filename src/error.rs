@@ -2,12 +2,14 @@
 pub const ERROR_PLAYER_NOT_EXIST: u32 = 1;
 pub const ERROR_PLAYER_ALREADY_EXIST: u32 = 2;
 pub const ERROR_INSUFFICIENT_BALANCE: u32 = 3;
+pub const ERROR_INVALID_NONCE: u32 = 4;
 
 // Security errors (10-20)
 pub const ERROR_OVERFLOW: u32 = 11;
 pub const ERROR_DIVISION_BY_ZERO: u32 = 12;
 pub const ERROR_UNDERFLOW: u32 = 13;
 pub const ERROR_MATH_OVERFLOW: u32 = 14;
+pub const ERROR_COUNTER_OVERFLOW: u32 = 15;
 
 // Staking errors (20-30)
 pub const ERROR_INSUFFICIENT_STAKE: u32 = 21;
@@ -16,9 +18,11 @@ pub const ERROR_STAKE_TOO_SMALL: u32 = 23;
 pub const ERROR_STAKE_TOO_LARGE: u32 = 24;
 pub const ERROR_NO_STAKE_TO_WITHDRAW: u32 = 25;
 pub const ERROR_WITHDRAW_TOO_EARLY: u32 = 26;
+pub const ERROR_USER_WITHDRAW_LIMIT: u32 = 27;
 
 // Points errors (30-40)
 pub const ERROR_INSUFFICIENT_POINTS: u32 = 31;
+pub const ERROR_POINTS_CONVERSION_DISABLED: u32 = 32;
 
 // Points withdrawal errors (40-50)
 pub const ERROR_INVALID_POINTS_AMOUNT: u32 = 41;
@@ -35,4 +39,38 @@ pub const ERROR_INSUFFICIENT_INTEREST: u32 = 57;
 pub const ERROR_INVALID_PRINCIPAL_AMOUNT: u32 = 58;
 pub const ERROR_PRINCIPAL_AMOUNT_TOO_SMALL: u32 = 59;
 pub const ERROR_INVALID_APY: u32 = 60;
-pub const ERROR_INVALID_DURATION: u32 = 61; 
\ No newline at end of file
+pub const ERROR_INVALID_DURATION: u32 = 61;
+pub const ERROR_FUNDS_NOT_MATURED: u32 = 62;
+pub const ERROR_INVALID_COMPOUNDING: u32 = 63;
+pub const ERROR_INVALID_PRINCIPAL_CURRENCY: u32 = 64;
+pub const ERROR_NOT_WHITELISTED: u32 = 65;
+pub const ERROR_CERTIFICATE_SAME_OWNER: u32 = 66;
+pub const ERROR_UNCLAIMED_INTEREST: u32 = 67;
+pub const ERROR_APY_CHANGED: u32 = 68;
+pub const ERROR_PRODUCT_CAP_EXCEEDED: u32 = 69;
+pub const ERROR_INVALID_INTEREST_CURRENCY: u32 = 70;
+
+// Admin and system errors (70-90)
+pub const ERROR_INVALID_PARAM: u32 = 71;
+pub const ERROR_UNKNOWN_COMMAND: u32 = 72;
+pub const ERROR_ADMIN_ONLY: u32 = 73;
+pub const ERROR_ADMIN_NOT_INSTALLED: u32 = 74;
+pub const ERROR_SYSTEM_PAUSED: u32 = 75;
+pub const ERROR_RECONCILE_MODE_DISABLED: u32 = 76;
+pub const ERROR_ADMIN_REGISTRY_FULL: u32 = 77;
+pub const ERROR_INVALID_ADDRESS: u32 = 78;
+pub const ERROR_INVALID_WITHDRAW_ADDRESS: u32 = 79;
+
+// Certificate system errors, continued (90-110)
+pub const ERROR_EARLY_WITHDRAWAL_DISABLED: u32 = 91;
+pub const ERROR_CLAIM_TOO_SMALL: u32 = 92;
+pub const ERROR_PRODUCT_NON_INTEREST_BEARING: u32 = 93;
+pub const ERROR_PRODUCT_CERT_LIMIT: u32 = 94;
+pub const ERROR_INVALID_CLAIM_TARGET: u32 = 95;
+pub const ERROR_INTEREST_TICK_BUDGET: u32 = 96;
+pub const ERROR_RESERVE_RATIO_TOO_LOW: u32 = 97;
+pub const ERROR_RESERVE_RATIO_CHANGE_TOO_SOON: u32 = 98;
+pub const ERROR_ACCRUAL_PAUSE_LIMIT: u32 = 99;
+pub const ERROR_IDLE_FUNDS_NOT_DUST: u32 = 100;
+pub const ERROR_CLAIM_TOO_SOON: u32 = 101;
+pub const ERROR_INVALID_CERTIFICATE_STATUS: u32 = 102;
\ No newline at end of file
@@ -2,13 +2,17 @@ use serde::Serialize;
 use zkwasm_rest_abi::StorageData;
 use zkwasm_rest_convention::objects::IndexedObject;
 use zkwasm_rest_convention::event::insert_event;
-use crate::certificate::{ProductType, Certificate};
+use crate::certificate::{ProductType, Certificate, CertificateStatus};
 use crate::config::{
     EVENT_PRODUCT_TYPE_CREATED, EVENT_PRODUCT_TYPE_MODIFIED, EVENT_CERTIFICATE_PURCHASED, 
     EVENT_INTEREST_CLAIMED, EVENT_PRINCIPAL_REDEEMED, 
     EVENT_INDEXED_OBJECT, PRODUCT_TYPE_INFO, CERTIFICATE_INFO,
     EVENT_DEPOSIT, EVENT_WITHDRAWAL, EVENT_POINTS_WITHDRAWAL, 
-    EVENT_ADMIN_WITHDRAWAL, EVENT_RESERVE_RATIO_CHANGE
+    EVENT_ADMIN_WITHDRAWAL, EVENT_RESERVE_RATIO_CHANGE, EVENT_PAYOUTS_PAUSED,
+    EVENT_GLOBAL_PARAM_CHANGED, EVENT_BOOSTER_BONUS, EVENT_RESERVE_POOL_WITHDRAWAL,
+    EVENT_WHITELIST_BATCH, EVENT_CERTIFICATE_TRANSFERRED, EVENT_SYSTEM_PAUSED,
+    EVENT_RECHARGE_ADJUSTED, EVENT_ROLE_CHANGED, EVENT_ADMIN_REPAYMENT,
+    EVENT_ROUNDING_SURPLUS_WITHDRAWAL
 };
 
 // Re-export clear_events from zkwasm_rest_convention
@@ -70,8 +74,9 @@ pub struct CertificateEvent {
     pub status: u64, // CertificateStatus as u64
 }
 
-impl StorageData for CertificateEvent {
-    fn from_data(u64data: &mut std::slice::IterMut<u64>) -> Self {
+impl CertificateEvent {
+    /// Checked decode of a packed certificate event, rejecting an unknown status
+    pub fn try_from_data(u64data: &mut std::slice::IterMut<u64>) -> Result<Self, u32> {
         let id = *u64data.next().unwrap();
         let owner = [*u64data.next().unwrap(), *u64data.next().unwrap()];
         let product_type_id = *u64data.next().unwrap();
@@ -80,9 +85,9 @@ impl StorageData for CertificateEvent {
         let maturity_time = *u64data.next().unwrap();
         let locked_apy = *u64data.next().unwrap();
         let total_interest_claimed = *u64data.next().unwrap();
-        let status = *u64data.next().unwrap();
+        let status = CertificateStatus::try_from_u64(*u64data.next().unwrap())?.to_u64();
 
-        CertificateEvent {
+        Ok(CertificateEvent {
             id,
             owner,
             product_type_id,
@@ -92,7 +97,13 @@ impl StorageData for CertificateEvent {
             locked_apy,
             total_interest_claimed,
             status,
-        }
+        })
+    }
+}
+
+impl StorageData for CertificateEvent {
+    fn from_data(u64data: &mut std::slice::IterMut<u64>) -> Self {
+        Self::try_from_data(u64data).expect("malformed certificate event")
     }
 
     fn to_data(&self, data: &mut Vec<u64>) {
@@ -358,8 +369,168 @@ pub fn emit_reserve_ratio_change_event(
     insert_event(EVENT_RESERVE_RATIO_CHANGE, &mut data);
 }
 
+/// Helper function to emit Payouts Paused event
+pub fn emit_payouts_paused_event(
+    admin_id: [u64; 2],
+    paused: bool,
+    pause_start_tick: u64,
+    counter: u64
+) {
+    let mut data = vec![admin_id[0], admin_id[1], if paused { 1 } else { 0 }, pause_start_tick, counter];
+    
+    insert_event(EVENT_PAYOUTS_PAUSED, &mut data);
+}
+
+/// Helper function to emit Global Param Changed event
+pub fn emit_global_param_changed_event(
+    admin_id: [u64; 2],
+    param: u64,
+    old_value: u64,
+    new_value: u64,
+    counter: u64
+) {
+    let mut data = vec![admin_id[0], admin_id[1], param, old_value, new_value, counter];
+    
+    insert_event(EVENT_GLOBAL_PARAM_CHANGED, &mut data);
+}
+
+/// Helper function to emit System Paused event
+pub fn emit_system_paused_event(
+    admin_id: [u64; 2],
+    paused: bool,
+    counter: u64
+) {
+    let mut data = vec![admin_id[0], admin_id[1], if paused { 1 } else { 0 }, counter];
+    
+    insert_event(EVENT_SYSTEM_PAUSED, &mut data);
+}
+
+/// Helper function to emit Recharge Adjusted event (audit trail for admin corrections)
+pub fn emit_recharge_adjusted_event(
+    admin_id: [u64; 2],
+    delta: i64,
+    old_amount: u64,
+    new_amount: u64,
+    counter: u64
+) {
+    let mut data = vec![admin_id[0], admin_id[1], delta as u64, old_amount, new_amount, counter];
+    
+    insert_event(EVENT_RECHARGE_ADJUSTED, &mut data);
+}
+
+/// Helper function to emit Role Changed event (grant or revoke)
+pub fn emit_role_changed_event(
+    admin_id: [u64; 2],
+    target_id: [u64; 2],
+    old_roles: u64,
+    new_roles: u64,
+    counter: u64
+) {
+    let mut data = vec![admin_id[0], admin_id[1], target_id[0], target_id[1], old_roles, new_roles, counter];
+    
+    insert_event(EVENT_ROLE_CHANGED, &mut data);
+}
+
+/// Helper function to emit Admin Repayment event (funds returned against admin withdrawals)
+pub fn emit_admin_repayment_event(
+    admin_id: [u64; 2],
+    amount: u64,
+    old_withdrawals: u64,
+    new_withdrawals: u64,
+    counter: u64
+) {
+    let mut data = vec![admin_id[0], admin_id[1], amount, old_withdrawals, new_withdrawals, counter];
+    
+    insert_event(EVENT_ADMIN_REPAYMENT, &mut data);
+}
+
+/// Helper function to emit Booster Bonus event (bonus paid on top of a claim)
+pub fn emit_booster_bonus_event(
+    user_id: [u64; 2],
+    certificate_id: u64,
+    bonus: u64,
+    txid: u64,
+    counter: u64
+) {
+    let mut data = vec![user_id[0], user_id[1], certificate_id, bonus, txid, counter];
+    
+    insert_event(EVENT_BOOSTER_BONUS, &mut data);
+}
+
+/// Helper function to emit Reserve Pool Withdrawal event
+pub fn emit_reserve_pool_withdrawal_event(
+    admin_id: [u64; 2],
+    amount: u64,
+    txid: u64,
+    counter: u64
+) {
+    let mut data = vec![admin_id[0], admin_id[1], amount, txid, counter];
+    
+    insert_event(EVENT_RESERVE_POOL_WITHDRAWAL, &mut data);
+}
+
+/// Helper function to emit Rounding Surplus Withdrawal event (surplus sent to multisig)
+pub fn emit_rounding_surplus_withdrawal_event(
+    admin_id: [u64; 2],
+    amount: u64,
+    txid: u64,
+    counter: u64
+) {
+    let mut data = vec![admin_id[0], admin_id[1], amount, txid, counter];
+    
+    insert_event(EVENT_ROUNDING_SURPLUS_WITHDRAWAL, &mut data);
+}
+
+/// Helper function to emit Whitelist Batch event (one per batch, not per user)
+pub fn emit_whitelist_batch_event(
+    admin_id: [u64; 2],
+    product_type_id: u64,
+    add: bool,
+    user_count: u64,
+    txid: u64,
+    counter: u64
+) {
+    let mut data = vec![admin_id[0], admin_id[1], product_type_id, if add { 1 } else { 0 }, user_count, txid, counter];
+    
+    insert_event(EVENT_WHITELIST_BATCH, &mut data);
+}
+
+/// Helper function to emit Certificate Transfer event
+pub fn emit_certificate_transfer_event(
+    old_owner: [u64; 2],
+    new_owner: [u64; 2],
+    certificate_id: u64,
+    new_certificate_id: u64,
+    txid: u64,
+    counter: u64
+) {
+    let mut data = vec![old_owner[0], old_owner[1], new_owner[0], new_owner[1], certificate_id, new_certificate_id, txid, counter];
+    
+    insert_event(EVENT_CERTIFICATE_TRANSFERRED, &mut data);
+}
+
 /// Helper function to insert regular events
 pub fn insert_event_wrapper(event_type: u64, data: &[u64]) {
     let mut data_vec = data.to_vec();
     insert_event(event_type, &mut data_vec);
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zkwasm_rest_convention::event::clear_events;
+
+    #[test]
+    fn test_claim_and_redemption_events_carry_txid() {
+        clear_events(vec![]);
+
+        emit_interest_claim_event([1, 2], 7, 150, 42, 1000);
+        emit_principal_redemption_event([1, 2], 7, 5000, 43, 1001);
+
+        let events = clear_events(vec![]);
+        assert_eq!(events, vec![
+            (EVENT_INTEREST_CLAIMED << 32) + 6, 1, 2, 7, 150, 42, 1000,
+            (EVENT_PRINCIPAL_REDEEMED << 32) + 6, 1, 2, 7, 5000, 43, 1001,
+        ]);
+    }
+}
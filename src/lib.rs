@@ -17,4 +17,66 @@ pub mod event;
 use crate::config::Config;
 use crate::state::{GlobalState, Transaction};
 
-zkwasm_rest_abi::create_zkwasm_apis!(Transaction, GlobalState, Config); 
+zkwasm_rest_abi::create_zkwasm_apis!(Transaction, GlobalState, Config);
+
+#[wasm_bindgen]
+pub fn query_tvl_series() -> String {
+    GlobalState::tvl_series()
+}
+
+#[wasm_bindgen]
+pub fn query_treasury() -> String {
+    GlobalState::treasury()
+}
+
+#[wasm_bindgen]
+pub fn query_admin_available_funds() -> String {
+    GlobalState::admin_available_funds()
+}
+
+#[wasm_bindgen]
+pub fn query_active_products(start: u64, max: u64) -> String {
+    GlobalState::active_products(start, max)
+}
+
+#[wasm_bindgen]
+pub fn query_product_types() -> String {
+    GlobalState::product_types()
+}
+
+#[wasm_bindgen]
+pub fn query_certificates(pkey: Vec<u64>) -> String {
+    GlobalState::certificates(pkey)
+}
+
+#[wasm_bindgen]
+pub fn query_closed_certificates(pkey: Vec<u64>, max: u64) -> String {
+    GlobalState::closed_certificates(pkey, max)
+}
+
+#[wasm_bindgen]
+pub fn query_projected_interest(pkey: Vec<u64>, cert_id: u64, at_time: u64) -> String {
+    GlobalState::projected_interest(pkey, cert_id, at_time)
+}
+
+#[wasm_bindgen]
+pub fn query_interest_params() -> String {
+    let seconds_per_tick = crate::state::with_state(|state| state.seconds_per_tick);
+    serde_json::to_string(&crate::certificate::interest_params(seconds_per_tick)).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn query_error_table() -> String {
+    GlobalState::error_table()
+}
+
+#[wasm_bindgen]
+pub fn query_event_types() -> String {
+    GlobalState::event_types()
+}
+
+#[wasm_bindgen]
+pub fn query_can_execute(command: u64, pkey: Vec<u64>) -> String {
+    GlobalState::can_execute(command, pkey)
+}
+
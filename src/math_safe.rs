@@ -24,6 +24,80 @@ pub fn safe_div(a: u64, b: u64) -> Result<u64, u32> {
     Ok(a / b)
 }
 
+/// Fixed-point `a * b` where `one` represents 1.0, floored
+/// Both operands are split at `one` so only the final product must fit in u128
+pub fn safe_mul_fixed(a: u128, b: u128, one: u128) -> Result<u128, u32> {
+    if one == 0 {
+        return Err(ERROR_DIVISION_BY_ZERO);
+    }
+    let (a_whole, a_frac) = (a / one, a % one);
+    let (b_whole, b_frac) = (b / one, b % one);
+    // a_frac * b_frac is below one^2, so it fits whenever one does not exceed 2^64
+    let terms = [
+        a_whole.checked_mul(b_whole).and_then(|v| v.checked_mul(one)),
+        a_whole.checked_mul(b_frac),
+        a_frac.checked_mul(b_whole),
+        a_frac.checked_mul(b_frac).map(|v| v / one),
+    ];
+    terms.into_iter().try_fold(0u128, |sum, term| term.and_then(|term| sum.checked_add(term))).ok_or(ERROR_OVERFLOW)
+}
+
+/// Fixed-point `base^exp` by squaring, `exp == 0` gives `one`
+/// Every product is floored, so the result never exceeds the exact power
+pub fn safe_pow_fixed(base: u128, exp: u64, one: u128) -> Result<u128, u32> {
+    let (mut result, mut base, mut exp) = (one, base, exp);
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = safe_mul_fixed(result, base, one)?;
+        }
+        exp >>= 1;
+        // The last square would go unused and could overflow on its own
+        if exp > 0 {
+            base = safe_mul_fixed(base, base, one)?;
+        }
+    }
+    Ok(result)
+}
+
+/// `a * b / c` with a u128 intermediate, so the product cannot overflow before the division
+/// Only the final quotient must fit in u64
+pub fn safe_mul_div_u128(a: u64, b: u64, c: u64) -> Result<u64, u32> {
+    if c == 0 {
+        return Err(ERROR_DIVISION_BY_ZERO);
+    }
+    let result = (a as u128 * b as u128) / c as u128;
+    u64::try_from(result).map_err(|_| ERROR_OVERFLOW)
+}
+
+/// `safe_mul_div_u128` rounded to the nearest integer, halves round up
+pub fn safe_mul_div_u128_nearest(a: u64, b: u64, c: u64) -> Result<u64, u32> {
+    if c == 0 {
+        return Err(ERROR_DIVISION_BY_ZERO);
+    }
+    // a * b is at most (2^64 - 1)^2, adding c / 2 still fits in u128
+    let result = (a as u128 * b as u128 + c as u128 / 2) / c as u128;
+    u64::try_from(result).map_err(|_| ERROR_OVERFLOW)
+}
+
+/// Checked narrowing to u32, rejecting values that would be truncated
+pub fn to_u32_checked(value: u64) -> Result<u32, u32> {
+    u32::try_from(value).map_err(|_| ERROR_OVERFLOW)
+}
+
+/// Checked narrowing to u8, rejecting values that would be truncated
+pub fn to_u8_checked(value: u64) -> Result<u8, u32> {
+    u8::try_from(value).map_err(|_| ERROR_OVERFLOW)
+}
+
+/// Checked narrowing of a 0/1 flag to bool
+pub fn to_bool_checked(value: u64) -> Result<bool, u32> {
+    match to_u8_checked(value)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(ERROR_OVERFLOW),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -46,12 +120,55 @@ mod tests {
         assert_eq!(safe_mul(u64::MAX, 2), Err(ERROR_OVERFLOW));
     }
 
+    #[test]
+    fn test_safe_mul_fixed() {
+        let one = 1_000_000u128;
+        assert_eq!(safe_mul_fixed(3 * one / 2, 3 * one / 2, one).unwrap(), 9 * one / 4);
+        assert_eq!(safe_mul_fixed(7, 5 * one, one).unwrap(), 35);
+        // 1/3 * 1/3 floors
+        assert_eq!(safe_mul_fixed(one / 3, one / 3, one).unwrap(), 111_110);
+        // Operands whose raw product overflows u128 still multiply
+        let big = u128::MAX / one;
+        assert_eq!(safe_mul_fixed(big, one, one).unwrap(), big);
+        assert_eq!(safe_mul_fixed(u128::MAX, 2 * one, one), Err(ERROR_OVERFLOW));
+        assert_eq!(safe_mul_fixed(1, 1, 0), Err(ERROR_DIVISION_BY_ZERO));
+    }
+
+    #[test]
+    fn test_safe_pow_fixed() {
+        let one = 1_000_000_000_000_000_000u128;
+        assert_eq!(safe_pow_fixed(2 * one, 63, one).unwrap(), (1u128 << 63) * one);
+        assert_eq!(safe_pow_fixed(3 * one, 5, one).unwrap(), 243 * one);
+        assert_eq!(safe_pow_fixed(one / 2, 3, one).unwrap(), one / 8);
+        for base in [0, one, 7 * one, u128::MAX] {
+            assert_eq!(safe_pow_fixed(base, 0, one).unwrap(), one);
+        }
+        assert_eq!(safe_pow_fixed(0, 5, one).unwrap(), 0);
+        assert_eq!(safe_pow_fixed(one, u64::MAX, one).unwrap(), one);
+        assert_eq!(safe_pow_fixed(2 * one, 128, one), Err(ERROR_OVERFLOW));
+        assert_eq!(safe_pow_fixed(2 * one, u64::MAX, one), Err(ERROR_OVERFLOW));
+    }
+
     #[test]
     fn test_safe_div() {
         assert_eq!(safe_div(6, 2).unwrap(), 3);
         assert_eq!(safe_div(6, 0), Err(ERROR_DIVISION_BY_ZERO));
     }
 
+    #[test]
+    fn test_safe_mul_div_u128() {
+        assert_eq!(safe_mul_div_u128(7, 3, 2).unwrap(), 10);
+        // The intermediate product exceeds u64 but the quotient fits
+        assert_eq!(safe_mul_div_u128(u64::MAX, 4, 8).unwrap(), u64::MAX / 2);
+        assert_eq!(safe_mul_div_u128(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert_eq!(safe_mul_div_u128(u64::MAX, 2, 1), Err(ERROR_OVERFLOW));
+        assert_eq!(safe_mul_div_u128(1, 1, 0), Err(ERROR_DIVISION_BY_ZERO));
+        assert_eq!(safe_mul_div_u128_nearest(7, 3, 2).unwrap(), 11);
+        assert_eq!(safe_mul_div_u128_nearest(7, 3, 4).unwrap(), 5);
+        assert_eq!(safe_mul_div_u128_nearest(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert_eq!(safe_mul_div_u128_nearest(1, 1, 0), Err(ERROR_DIVISION_BY_ZERO));
+    }
+
     // Certificate system math tests
     #[test]
     fn test_safe_operations_comprehensive() {
@@ -127,6 +244,20 @@ mod tests {
         assert_eq!(cost, 34560);
     }
 
+    #[test]
+    fn test_checked_downcasts() {
+        assert_eq!(to_u32_checked(u32::MAX as u64).unwrap(), u32::MAX);
+        assert_eq!(to_u32_checked(u32::MAX as u64 + 1), Err(ERROR_OVERFLOW));
+        assert_eq!(to_u8_checked(255).unwrap(), 255);
+        assert_eq!(to_u8_checked(256), Err(ERROR_OVERFLOW));
+        assert_eq!(to_u8_checked(u64::MAX), Err(ERROR_OVERFLOW));
+
+        assert!(!to_bool_checked(0).unwrap());
+        assert!(to_bool_checked(1).unwrap());
+        assert_eq!(to_bool_checked(2), Err(ERROR_OVERFLOW));
+        assert_eq!(to_bool_checked(1 << 32), Err(ERROR_OVERFLOW));
+    }
+
     #[test]
     fn test_overflow_detection() {
         // Test various overflow scenarios
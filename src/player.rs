@@ -2,11 +2,21 @@ use serde::Serialize;
 use zkwasm_rest_abi::StorageData;
 use crate::error::*;
 use crate::math_safe::{safe_add, safe_sub};
+use crate::certificate::PRINCIPAL_CURRENCY_POINTS;
 
 #[derive(Serialize, Clone, Debug, Default)]
 pub struct PlayerData {
     pub points: u64,      // User points/score (static, for point withdrawals only)
     pub idle_funds: u64,  // Idle funds available for certificate purchases and withdrawals
+    pub funds_available_tick: u64, // Tick from which idle funds may buy certificates
+    pub last_withdraw_counter: u64, // Tick of the last idle-funds withdrawal (0 = never)
+    pub withdrawn_today: u64, // Idle funds withdrawn during last_withdraw_day
+    pub last_withdraw_day: u64, // Day (counter / ticks per day) of the last withdrawal
+    pub lifetime_deposited: u64, // Total idle funds ever deposited
+    pub lifetime_interest_claimed: u64, // Total USDT interest ever claimed, booster bonuses excluded
+    pub lifetime_principal_redeemed: u64, // Total USDT principal ever returned from certificates
+    pub certificate_nonce: u64, // Next per-owner certificate id (0 = none numbered yet)
+    pub first_certificate_id: u64, // Lowest id this owner's certificates use (0 = none yet), where listing starts
 }
 
 pub trait Owner: Sized {
@@ -24,11 +34,36 @@ impl Owner for StakingPlayer {
     }
 }
 
+/// Nonce check that fails the transaction with ERROR_INVALID_NONCE instead of aborting it
+/// A rejected nonce is not consumed, as the player is not stored
+pub trait NonceCheck {
+    fn try_check_and_inc_nonce(&mut self, nonce: u64) -> Result<(), u32>;
+}
+
+impl NonceCheck for StakingPlayer {
+    fn try_check_and_inc_nonce(&mut self, nonce: u64) -> Result<(), u32> {
+        if nonce != self.nonce {
+            return Err(ERROR_INVALID_NONCE);
+        }
+        self.nonce += 1;
+        Ok(())
+    }
+}
+
 impl PlayerData {
     pub fn new() -> Self {
         PlayerData {
             points: 0,
             idle_funds: 0,
+            funds_available_tick: 0,
+            last_withdraw_counter: 0,
+            withdrawn_today: 0,
+            last_withdraw_day: 0,
+            lifetime_deposited: 0,
+            lifetime_interest_claimed: 0,
+            lifetime_principal_redeemed: 0,
+            certificate_nonce: 0,
+            first_certificate_id: 0,
         }
     }
 
@@ -52,7 +87,113 @@ impl PlayerData {
         Ok(())
     }
 
+    /// Spend static points (for points-denominated certificate purchases)
+    pub fn spend_points(&mut self, amount: u64) -> Result<(), u32> {
+        if self.points < amount {
+            return Err(ERROR_INSUFFICIENT_POINTS);
+        }
+        self.points = safe_sub(self.points, amount)?;
+        Ok(())
+    }
+
+    /// Debit a certificate principal in its currency
+    pub fn debit_principal(&mut self, currency: u8, amount: u64) -> Result<(), u32> {
+        match currency {
+            PRINCIPAL_CURRENCY_POINTS => self.spend_points(amount),
+            _ => self.spend_idle_funds(amount),
+        }
+    }
+
+    /// Credit a certificate principal or its interest back in its currency
+    pub fn credit_principal(&mut self, currency: u8, amount: u64) -> Result<(), u32> {
+        match currency {
+            PRINCIPAL_CURRENCY_POINTS => {
+                self.points = safe_add(self.points, amount)?;
+                Ok(())
+            }
+            _ => self.add_idle_funds(amount),
+        }
+    }
+
+    /// Credit principal returned from a certificate, counting USDT towards lifetime_principal_redeemed
+    pub fn credit_redeemed_principal(&mut self, currency: u8, amount: u64) -> Result<(), u32> {
+        self.credit_principal(currency, amount)?;
+        if currency != PRINCIPAL_CURRENCY_POINTS {
+            self.lifetime_principal_redeemed = safe_add(self.lifetime_principal_redeemed, amount)?;
+        }
+        Ok(())
+    }
+
+    /// Restart the maturation delay after a deposit
+    pub fn mark_deposit(&mut self, current_time: u64, maturation_ticks: u64) -> Result<(), u32> {
+        self.funds_available_tick = safe_add(current_time, maturation_ticks)?;
+        Ok(())
+    }
 
+    /// Reject certificate purchases before deposited funds have matured
+    pub fn check_funds_matured(&self, current_time: u64) -> Result<(), u32> {
+        if current_time < self.funds_available_tick {
+            return Err(ERROR_FUNDS_NOT_MATURED);
+        }
+        Ok(())
+    }
+
+    /// Reject idle-funds withdrawals within cooldown_ticks of the previous one
+    pub fn check_withdraw_cooldown(&self, current_time: u64, cooldown_ticks: u64) -> Result<(), u32> {
+        if self.last_withdraw_counter != 0
+            && current_time.saturating_sub(self.last_withdraw_counter) < cooldown_ticks {
+            return Err(ERROR_WITHDRAW_TOO_EARLY);
+        }
+        Ok(())
+    }
+
+    /// Record an idle-funds withdrawal for the cooldown check
+    pub fn mark_withdraw(&mut self, current_time: u64) {
+        self.last_withdraw_counter = current_time;
+    }
+
+    /// Count a withdrawal against the per-day limit, restarting the tally on a new day
+    /// A day is `ticks_per_day` ticks at the current tick length
+    /// A zero limit disables the check but the tally is still kept
+    pub fn record_daily_withdrawal(&mut self, current_time: u64, amount: u64, daily_limit: u64, ticks_per_day: u64) -> Result<(), u32> {
+        let day = current_time.checked_div(ticks_per_day).unwrap_or(0);
+        let withdrawn = if day == self.last_withdraw_day { self.withdrawn_today } else { 0 };
+        let withdrawn = safe_add(withdrawn, amount)?;
+        if daily_limit != 0 && withdrawn > daily_limit {
+            return Err(ERROR_USER_WITHDRAW_LIMIT);
+        }
+        self.withdrawn_today = withdrawn;
+        self.last_withdraw_day = day;
+        Ok(())
+    }
+
+    /// The id this owner's next certificate gets
+    /// Numbering starts at `legacy_end` so it never reuses an id from the old global numbering
+    pub fn next_certificate_id(&self, legacy_end: u64) -> u64 {
+        self.certificate_nonce.max(legacy_end)
+    }
+
+    /// Hand out the next certificate id and advance the per-owner counter
+    pub fn take_certificate_id(&mut self, legacy_end: u64) -> Result<u64, u32> {
+        let id = self.next_certificate_id(legacy_end);
+        self.certificate_nonce = safe_add(id, 1).map_err(|_| ERROR_COUNTER_OVERFLOW)?;
+        if self.first_certificate_id == 0 {
+            self.first_certificate_id = id;
+        }
+        Ok(id)
+    }
+
+    /// Ids this owner's certificates may use, `[first, next)`, empty when none were handed out
+    /// Players stored before first_certificate_id existed read it as 1, as their certificates may
+    /// carry ids from the old global numbering
+    pub fn certificate_id_range(&self, legacy_end: u64) -> (u64, u64) {
+        let end = self.next_certificate_id(legacy_end);
+        if self.first_certificate_id == 0 {
+            (end, end)
+        } else {
+            (self.first_certificate_id, end)
+        }
+    }
 }
 
 impl StorageData for PlayerData {
@@ -60,12 +201,30 @@ impl StorageData for PlayerData {
         PlayerData {
             points: *u64data.next().unwrap(),
             idle_funds: *u64data.next().unwrap(),
+            funds_available_tick: u64data.next().copied().unwrap_or(0),
+            last_withdraw_counter: u64data.next().copied().unwrap_or(0),
+            withdrawn_today: u64data.next().copied().unwrap_or(0),
+            last_withdraw_day: u64data.next().copied().unwrap_or(0),
+            lifetime_deposited: u64data.next().copied().unwrap_or(0),
+            lifetime_interest_claimed: u64data.next().copied().unwrap_or(0),
+            lifetime_principal_redeemed: u64data.next().copied().unwrap_or(0),
+            certificate_nonce: u64data.next().copied().unwrap_or(0),
+            first_certificate_id: u64data.next().copied().unwrap_or(1),
         }
     }
 
     fn to_data(&self, data: &mut Vec<u64>) {
         data.push(self.points);
         data.push(self.idle_funds);
+        data.push(self.funds_available_tick);
+        data.push(self.last_withdraw_counter);
+        data.push(self.withdrawn_today);
+        data.push(self.last_withdraw_day);
+        data.push(self.lifetime_deposited);
+        data.push(self.lifetime_interest_claimed);
+        data.push(self.lifetime_principal_redeemed);
+        data.push(self.certificate_nonce);
+        data.push(self.first_certificate_id);
     }
 }
 
@@ -74,7 +233,10 @@ pub type StakingPlayer = zkwasm_rest_abi::Player<PlayerData>;
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::error::ERROR_INSUFFICIENT_BALANCE;
+    use crate::error::{ERROR_INSUFFICIENT_BALANCE, ERROR_FUNDS_NOT_MATURED, ERROR_INSUFFICIENT_POINTS,
+                       ERROR_WITHDRAW_TOO_EARLY, ERROR_USER_WITHDRAW_LIMIT, ERROR_COUNTER_OVERFLOW};
+    use crate::certificate::PRINCIPAL_CURRENCY_USDT;
+    use crate::config::TICKS_PER_DAY;
 
     #[test]
     fn test_player_data_new() {
@@ -88,6 +250,11 @@ mod tests {
         let player_data = PlayerData {
             points: 17280,
             idle_funds: 5000,
+            funds_available_tick: 0,
+            last_withdraw_counter: 0,
+            withdrawn_today: 0,
+            last_withdraw_day: 0,
+            ..PlayerData::new()
         };
         
         // Certificate system: points are static
@@ -121,6 +288,11 @@ mod tests {
         let mut player_data = PlayerData {
             points: 1000,
             idle_funds: 500,
+            funds_available_tick: 0,
+            last_withdraw_counter: 0,
+            withdrawn_today: 0,
+            last_withdraw_day: 0,
+            ..PlayerData::new()
         };
         
         // Try to spend more than available
@@ -136,18 +308,58 @@ mod tests {
         let player_data = PlayerData {
             points: 12345,
             idle_funds: 67890,
+            funds_available_tick: 0,
+            last_withdraw_counter: 0,
+            withdrawn_today: 0,
+            last_withdraw_day: 0,
+            ..PlayerData::new()
         };
         
         // Serialize
         let mut data = Vec::new();
         player_data.to_data(&mut data);
-        assert_eq!(data, vec![12345, 67890]);
+        assert_eq!(data, vec![12345, 67890, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         
         // Deserialize
         let mut iter = data.iter_mut();
         let restored = PlayerData::from_data(&mut iter);
         assert_eq!(restored.points, 12345);
         assert_eq!(restored.idle_funds, 67890);
+
+        // Players stored before the lifetime statistics existed read them as 0
+        let mut legacy = [12345, 67890, 0, 0, 0, 0];
+        let restored = PlayerData::from_data(&mut legacy.iter_mut());
+        assert_eq!(restored.lifetime_deposited, 0);
+        assert_eq!(restored.lifetime_principal_redeemed, 0);
+        assert_eq!(restored.certificate_nonce, 0);
+        // Their certificates may predate per-owner numbering, listing scans from id 1
+        assert_eq!(restored.first_certificate_id, 1);
+    }
+
+    #[test]
+    fn test_certificate_id_range_starts_at_first_id() {
+        let mut player_data = PlayerData::new();
+        assert_eq!(player_data.certificate_id_range(500), (500, 500));
+
+        player_data.take_certificate_id(500).unwrap();
+        player_data.take_certificate_id(500).unwrap();
+        assert_eq!(player_data.first_certificate_id, 500);
+        assert_eq!(player_data.certificate_id_range(500), (500, 502));
+    }
+
+    #[test]
+    fn test_certificate_ids_continue_after_legacy_numbering() {
+        let mut player_data = PlayerData::new();
+
+        // A player with no certificates yet starts where the global numbering stopped
+        assert_eq!(player_data.next_certificate_id(7), 7);
+        assert_eq!(player_data.take_certificate_id(7).unwrap(), 7);
+        assert_eq!(player_data.take_certificate_id(7).unwrap(), 8);
+        assert_eq!(player_data.next_certificate_id(1), 9);
+
+        player_data.certificate_nonce = u64::MAX;
+        assert_eq!(player_data.take_certificate_id(1), Err(ERROR_COUNTER_OVERFLOW));
+        assert_eq!(player_data.certificate_nonce, u64::MAX);
     }
 
     #[test]
@@ -155,6 +367,11 @@ mod tests {
         let mut player_data = PlayerData {
             points: u64::MAX - 100,
             idle_funds: u64::MAX - 100,
+            funds_available_tick: 0,
+            last_withdraw_counter: 0,
+            withdrawn_today: 0,
+            last_withdraw_day: 0,
+            ..PlayerData::new()
         };
         
         // These operations should not overflow
@@ -166,4 +383,95 @@ mod tests {
         let result2 = player_data.add_idle_funds(100);
         assert_eq!(result2.unwrap_err(), ERROR_OVERFLOW);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_purchase_blocked_until_deposit_matures() {
+        let mut player_data = PlayerData::new();
+        // No deposit yet: nothing to wait for
+        assert!(player_data.check_funds_matured(0).is_ok());
+
+        player_data.mark_deposit(1000, 720).unwrap();
+        assert_eq!(player_data.funds_available_tick, 1720);
+        assert_eq!(player_data.check_funds_matured(1000), Err(ERROR_FUNDS_NOT_MATURED));
+        assert_eq!(player_data.check_funds_matured(1719), Err(ERROR_FUNDS_NOT_MATURED));
+        assert!(player_data.check_funds_matured(1720).is_ok());
+
+        // A later deposit restarts the delay
+        player_data.mark_deposit(2000, 720).unwrap();
+        assert_eq!(player_data.check_funds_matured(2500), Err(ERROR_FUNDS_NOT_MATURED));
+
+        // A zero delay never blocks
+        player_data.mark_deposit(3000, 0).unwrap();
+        assert!(player_data.check_funds_matured(3000).is_ok());
+    }
+
+    #[test]
+    fn test_withdraw_cooldown() {
+        let mut player_data = PlayerData::new();
+        // Never withdrawn: nothing to wait for
+        assert!(player_data.check_withdraw_cooldown(10, 100).is_ok());
+
+        player_data.mark_withdraw(1000);
+        assert_eq!(player_data.check_withdraw_cooldown(1099, 100), Err(ERROR_WITHDRAW_TOO_EARLY));
+        assert!(player_data.check_withdraw_cooldown(1100, 100).is_ok());
+
+        // A zero cooldown never blocks
+        assert!(player_data.check_withdraw_cooldown(1000, 0).is_ok());
+    }
+
+    #[test]
+    fn test_daily_withdrawal_limit_resets_each_day() {
+        let mut player_data = PlayerData::new();
+        player_data.record_daily_withdrawal(10, 600, 1000, TICKS_PER_DAY).unwrap();
+        player_data.record_daily_withdrawal(20, 400, 1000, TICKS_PER_DAY).unwrap();
+        assert_eq!(player_data.record_daily_withdrawal(30, 1, 1000, TICKS_PER_DAY), Err(ERROR_USER_WITHDRAW_LIMIT));
+        assert_eq!(player_data.withdrawn_today, 1000);
+
+        // The next day starts a fresh tally
+        player_data.record_daily_withdrawal(TICKS_PER_DAY, 1000, 1000, TICKS_PER_DAY).unwrap();
+        assert_eq!(player_data.last_withdraw_day, 1);
+        assert_eq!(player_data.record_daily_withdrawal(TICKS_PER_DAY + 1, 1, 1000, TICKS_PER_DAY), Err(ERROR_USER_WITHDRAW_LIMIT));
+
+        // A zero limit never blocks
+        player_data.record_daily_withdrawal(TICKS_PER_DAY + 2, 5000, 0, TICKS_PER_DAY).unwrap();
+    }
+
+    #[test]
+    fn test_daily_withdrawal_day_follows_tick_length() {
+        // With 10-second ticks a day is half as many ticks
+        let ticks_per_day = TICKS_PER_DAY / 2;
+        let mut player_data = PlayerData::new();
+        player_data.record_daily_withdrawal(ticks_per_day - 1, 1000, 1000, ticks_per_day).unwrap();
+        player_data.record_daily_withdrawal(ticks_per_day, 1000, 1000, ticks_per_day).unwrap();
+        assert_eq!(player_data.last_withdraw_day, 1);
+    }
+
+    #[test]
+    fn test_legacy_player_data_has_matured_funds() {
+        let mut legacy = [10, 20];
+        let restored = PlayerData::from_data(&mut legacy.iter_mut());
+        assert_eq!(restored.funds_available_tick, 0);
+        assert!(restored.check_funds_matured(0).is_ok());
+    }
+
+    #[test]
+    fn test_principal_debit_and_credit_by_currency() {
+        let mut player_data = PlayerData::new();
+        player_data.add_idle_funds(1000).unwrap();
+        player_data.points = 500;
+
+        // Points principal leaves idle funds untouched
+        player_data.debit_principal(PRINCIPAL_CURRENCY_POINTS, 300).unwrap();
+        assert_eq!(player_data.points, 200);
+        assert_eq!(player_data.idle_funds, 1000);
+        assert_eq!(player_data.debit_principal(PRINCIPAL_CURRENCY_POINTS, 201), Err(ERROR_INSUFFICIENT_POINTS));
+
+        player_data.debit_principal(PRINCIPAL_CURRENCY_USDT, 400).unwrap();
+        assert_eq!(player_data.idle_funds, 600);
+
+        player_data.credit_principal(PRINCIPAL_CURRENCY_POINTS, 300).unwrap();
+        player_data.credit_principal(PRINCIPAL_CURRENCY_USDT, 400).unwrap();
+        assert_eq!(player_data.points, 500);
+        assert_eq!(player_data.idle_funds, 1000);
+    }
+}
@@ -3,6 +3,19 @@ use zkwasm_rest_abi::{StorageData, MERKLE_MAP};
 use std::cell::RefCell;
 use crate::error::*;
 use crate::player::{StakingPlayer, Owner};
+use crate::config::{DEFAULT_TVL_SAMPLE_INTERVAL, TVL_SAMPLE_CAPACITY, PARAM_TVL_SAMPLE_INTERVAL,
+                    PARAM_BOOSTER_BPS, PARAM_BOOSTER_END_TICK, PARAM_DEPOSIT_MATURATION_TICKS, MAX_BOOSTER_BPS,
+                    PARAM_RESERVE_RAMP_THRESHOLD, PARAM_RESERVE_RAMP_RATIO, DEFAULT_RESERVE_RAMP_RATIO,
+                    PARAM_AUTO_REDEEM_ON_CLAIM, MAX_WHITELIST_BATCH, MAX_BATCH_PURCHASE, PARAM_QUERY_PAGE_SIZE,
+                    DEFAULT_QUERY_PAGE_SIZE, PARAM_WELCOME_POINTS,
+                    PARAM_MAX_CLAIM_LOOKBACK_TICKS, PARAM_WITHDRAW_COOLDOWN_TICKS,
+                    PARAM_RECONCILE_MODE, PARAM_USER_DAILY_WITHDRAW_LIMIT, WITHDRAW_COOLDOWN_TICKS,
+                    PARAM_INTEREST_POINTS_RATE, DEFAULT_INTEREST_POINTS_RATE, PARAM_MIN_ABSOLUTE_RESERVE,
+                    PARAM_POINTS_TO_FUNDS_RATE, PARAM_MAX_INTEREST_PER_TICK, PARAM_INTEREST_TICK_PARTIAL,
+                    PARAM_SECONDS_PER_TICK, SECONDS_PER_TICK, PARAM_WITHDRAWAL_FEE, PARAM_TRANSFER_FEE,
+                    ROLE_SUPERADMIN, ROLE_TREASURY, ROLE_PRODUCT, ROLE_OPERATOR, ROLE_MASK, MAX_ADMIN_ROLES};
+use crate::math_safe::{safe_add, safe_sub, safe_mul, safe_div};
+use crate::certificate::MAX_APY_STEPS;
 
 #[derive(Serialize)]
 pub struct QueryState {
@@ -16,6 +29,15 @@ pub struct QueryState {
     cumulative_admin_withdrawals: u64,
     total_recharge_amount: u64,
     reserve_ratio: u64,
+    // Catalog size vs live offerings
+    total_products_created: u64,
+    active_products: u64,
+    // Outstanding certificate liabilities in USDT
+    total_certificate_principal: u64,
+    total_interest_owed: u64,
+    // Certificates sold vs still open
+    certificates_issued: u64,
+    active_certificate_count: u64,
 }
 
 #[derive(Serialize, Clone)]
@@ -27,12 +49,154 @@ pub struct GlobalState {
     pub txcounter: u64,
     // Certificate system counters (minimal addition)
     pub product_type_counter: u64,   // Product type ID counter
-    pub certificate_counter: u64,    // Certificate ID counter
+    pub certificate_counter: u64,    // End of the legacy global certificate numbering, per-owner ids start here
     // Reserve ratio and admin withdrawal tracking
     pub reserve_ratio: u64,          // Reserve ratio in basis points (e.g., 1000 = 10%)
     pub cumulative_admin_withdrawals: u64,  // Total amount admin has withdrawn
     pub interest_claimed: u64,       // Total interest claimed by users
     pub total_recharge_amount: u64,  // Total amount recharged via product 0
+    // Payout pause: interest claims are limited to accrual before pause_start_tick
+    pub payouts_paused: bool,
+    pub pause_start_tick: u64,
+    // TVL time series: (counter, total_funds) sampled every tvl_sample_interval ticks
+    pub tvl_sample_interval: u64,
+    pub tvl_samples: Vec<(u64, u64)>,
+    // Interest booster window and total bonus paid on top of earned interest
+    pub booster_bps: u64,
+    pub booster_end_tick: u64,
+    pub booster_paid: u64,
+    // Minimum ticks deposited funds must sit idle before they can buy a certificate
+    pub deposit_maturation_ticks: u64,
+    // Protocol revenue from penalties and fees, kept apart from user funds
+    pub reserve_pool: u64,
+    // Below this total_funds the admin withdrawal reserve is at least reserve_ramp_ratio (0 disables)
+    pub reserve_ramp_threshold: u64,
+    pub reserve_ramp_ratio: u64,
+    // Sum of USDT certificates' rounding drift at their last claim (ROUNDING_SURPLUS_SCALE units)
+    pub rounding_surplus: u64,
+    // Claims on matured certificates also redeem the principal
+    pub auto_redeem_on_claim: bool,
+    // Default and maximum entries returned by one list query page
+    pub query_page_size: u64,
+    // Points credited to each newly installed player
+    pub welcome_points: u64,
+    // Points issued through welcome bonuses
+    pub total_points_issued: u64,
+    // Caps one claim at the interest accrued over this many ticks, 0 = unlimited
+    pub max_claim_lookback_ticks: u64,
+    // Minimum ticks between idle-funds withdrawals per player (0 disables)
+    pub withdraw_cooldown_ticks: u64,
+    // Halts all non-admin commands except player install
+    pub paused: bool,
+    // Enables AdminAdjustRecharge accounting corrections
+    pub reconcile_mode: bool,
+    // Idle funds a player may withdraw per day (0 disables)
+    pub user_daily_withdraw_limit: u64,
+    // Admin registry: (pid, role bitmask) for keys other than ADMIN_PUBKEY
+    pub admin_roles: Vec<([u64; 2], u64)>,
+    // Product types currently open for purchase (recharge product 0 excluded)
+    pub active_products: u64,
+    // Points credited per unit of interest on USDT products that pay interest in points
+    pub interest_points_rate: u64,
+    // Outstanding USDT liabilities: principal locked in unredeemed certificates
+    pub total_certificate_principal: u64,
+    // Interest scheduled through maturity on those certificates, not yet paid or forfeited
+    pub total_interest_owed: u64,
+    // Absolute floor on funds kept back from admin withdrawals, alongside the reserve ratio
+    pub min_absolute_reserve: u64,
+    // Points exchanged for one unit of idle funds by RedeemPointsForFunds, 0 disables it
+    pub points_to_funds_rate: u64,
+    // Points converted into idle funds
+    pub total_points_withdrawn: u64,
+    // Whole units of rounding surplus sent to the multisig by WithdrawRoundingSurplus
+    pub rounding_surplus_withdrawn: u64,
+    // System-wide interest budget per tick for user claims, 0 disables the throttle
+    pub max_interest_per_tick: u64,
+    // Pay a claim over the budget down to what is left instead of rejecting it
+    pub interest_tick_partial: bool,
+    // Budget used at last_interest_tick, reset on the first claim of a later tick
+    pub interest_paid_this_tick: u64,
+    pub last_interest_tick: u64,
+    // Counter of the last SetReserveRatio change (0 = never changed)
+    pub last_reserve_change_counter: u64,
+    // Auto-claim registry: entries [4, 0, 0, 0..auto_claim_count], the tick pass resumes at auto_claim_cursor
+    pub auto_claim_count: u64,
+    pub auto_claim_cursor: u64,
+    // Certificates sold and those not yet redeemed, counted from when these fields were added
+    pub certificates_issued: u64,
+    pub active_certificate_count: u64,
+    // Tick length used by interest math, locked onto each certificate at purchase
+    pub seconds_per_tick: u64,
+    // Flat fee taken into the reserve pool on each user withdrawal
+    pub withdrawal_fee: u64,
+    // Flat fee the sender pays into the reserve pool on each certificate transfer
+    pub transfer_fee: u64,
+}
+
+#[derive(Serialize)]
+pub struct CommandPreflight {
+    allowed: bool,
+    reason: u32, // Error code the command would currently fail with, 0 if allowed
+}
+
+#[derive(Serialize)]
+pub struct QueryError {
+    error: u32, // Error code a query was rejected with
+}
+
+#[derive(Serialize)]
+pub struct InterestProjection {
+    interest: u64,
+    error: u32, // Error code the projection failed with, 0 on success
+}
+
+#[derive(Serialize)]
+pub struct TvlSeries<'a> {
+    interval: u64,
+    samples: &'a [(u64, u64)],
+}
+
+#[derive(Serialize)]
+pub struct Treasury {
+    reserve_pool: u64,
+    cumulative_admin_withdrawals: u64,
+    interest_claimed: u64,
+    booster_paid: u64,
+    rounding_surplus: u64,       // In rounding_surplus_scale units
+    rounding_surplus_scale: u64,
+    rounding_surplus_withdrawn: u64,
+    withdrawable_rounding_surplus: u64,
+}
+
+#[derive(Serialize)]
+pub struct AdminAvailableFunds {
+    available: u64,              // Max the admin may withdraw now, 0 on error
+    error: u32,                  // Error code the calculation failed with, 0 on success
+    total_funds: u64,
+    total_recharge_amount: u64,
+    cumulative_admin_withdrawals: u64,
+    reserve_ratio: u64,
+    effective_reserve_ratio: u64, // reserve_ratio raised by the ramp while below reserve_ramp_threshold
+    reserve_ramp_threshold: u64,
+    reserve_ramp_ratio: u64,
+    min_absolute_reserve: u64,
+}
+
+#[derive(Serialize)]
+pub struct ErrorInfo {
+    code: u32,
+    name: &'static str,
+    category: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct CertificateView {
+    #[serde(flatten)]
+    pub certificate: crate::certificate::Certificate,
+    pub available_interest: u64, // Claimable now (respects the payout pause cutoff)
+    pub is_matured: bool,
+    pub duration_days: u64,      // Term in days at the tick length locked on the certificate
+    pub time_to_maturity: u64,   // Ticks until redemption opens, 0 once matured
 }
 
 impl Default for GlobalState {
@@ -50,27 +214,795 @@ impl GlobalState {
             txsize: 0,
             txcounter: 0,
             product_type_counter: 1, // Start from 1 for product types
-            certificate_counter: 1,  // Start from 1 for certificates
+            certificate_counter: 1,  // Per-owner certificate ids start from 1
             reserve_ratio: 1000,    // Default 10% reserve ratio
             cumulative_admin_withdrawals: 0,
             interest_claimed: 0,
             total_recharge_amount: 0,
+            payouts_paused: false,
+            pause_start_tick: 0,
+            tvl_sample_interval: DEFAULT_TVL_SAMPLE_INTERVAL,
+            tvl_samples: vec![],
+            booster_bps: 0,
+            booster_end_tick: 0,
+            booster_paid: 0,
+            deposit_maturation_ticks: 0,
+            reserve_pool: 0,
+            reserve_ramp_threshold: 0,
+            reserve_ramp_ratio: DEFAULT_RESERVE_RAMP_RATIO,
+            rounding_surplus: 0,
+            auto_redeem_on_claim: false,
+            query_page_size: DEFAULT_QUERY_PAGE_SIZE,
+            welcome_points: 0,
+            total_points_issued: 0,
+            max_claim_lookback_ticks: 0,
+            withdraw_cooldown_ticks: WITHDRAW_COOLDOWN_TICKS,
+            paused: false,
+            reconcile_mode: false,
+            user_daily_withdraw_limit: 0,
+            admin_roles: vec![],
+            active_products: 0,
+            interest_points_rate: DEFAULT_INTEREST_POINTS_RATE,
+            total_certificate_principal: 0,
+            total_interest_owed: 0,
+            min_absolute_reserve: 0,
+            points_to_funds_rate: 0,
+            total_points_withdrawn: 0,
+            rounding_surplus_withdrawn: 0,
+            max_interest_per_tick: 0,
+            interest_tick_partial: false,
+            interest_paid_this_tick: 0,
+            last_interest_tick: 0,
+            last_reserve_change_counter: 0,
+            auto_claim_count: 0,
+            auto_claim_cursor: 0,
+            certificates_issued: 0,
+            active_certificate_count: 0,
+            seconds_per_tick: SECONDS_PER_TICK,
+            withdrawal_fee: 0,
+            transfer_fee: 0,
+        }
+    }
+
+    /// Stateless preflight for a command id: whether it exists and whether the caller may send it
+    /// Returns the error code the command would be rejected with, or 0
+    pub fn command_precheck(&self, command: u64, roles: u64) -> u32 {
+        if !is_known_command(command) {
+            return ERROR_UNKNOWN_COMMAND;
+        }
+        if is_admin_command(command) && !roles_include(roles, command_role(command)) {
+            return ERROR_ADMIN_ONLY;
+        }
+        if self.paused && roles == 0 && command != INSTALL_PLAYER {
+            return ERROR_SYSTEM_PAUSED;
+        }
+        0
+    }
+
+    /// Admin roles held by pkey; ADMIN_PUBKEY holds every role and cannot be revoked
+    pub fn roles_of(&self, pkey: &[u64; 4]) -> u64 {
+        if *pkey == *crate::config::ADMIN_PUBKEY {
+            return ROLE_MASK;
+        }
+        let pid = StakingPlayer::pkey_to_pid(pkey);
+        self.admin_roles.iter().find(|(admin, _)| *admin == pid).map_or(0, |(_, roles)| *roles)
+    }
+
+    /// Add roles to an admin pid, returns the (old, new) role bitmask
+    pub fn grant_roles(&mut self, pid: [u64; 2], roles: u64) -> Result<(u64, u64), u32> {
+        self.update_roles(pid, roles, |old| old | roles)
+    }
+
+    /// Remove roles from an admin pid, returns the (old, new) role bitmask
+    pub fn revoke_roles(&mut self, pid: [u64; 2], roles: u64) -> Result<(u64, u64), u32> {
+        self.update_roles(pid, roles, |old| old & !roles)
+    }
+
+    /// Entries left without roles are dropped from the registry
+    fn update_roles(&mut self, pid: [u64; 2], roles: u64, update: impl Fn(u64) -> u64) -> Result<(u64, u64), u32> {
+        if roles == 0 || roles & !ROLE_MASK != 0 {
+            return Err(ERROR_INVALID_PARAM);
+        }
+        let index = self.admin_roles.iter().position(|(admin, _)| *admin == pid);
+        let old = index.map_or(0, |i| self.admin_roles[i].1);
+        let new = update(old);
+        match index {
+            Some(i) if new == 0 => {
+                self.admin_roles.remove(i);
+            }
+            Some(i) => self.admin_roles[i].1 = new,
+            None if new != 0 => {
+                if self.admin_roles.len() >= MAX_ADMIN_ROLES {
+                    return Err(ERROR_ADMIN_REGISTRY_FULL);
+                }
+                self.admin_roles.push((pid, new));
+            }
+            None => (),
+        }
+        Ok((old, new))
+    }
+
+    /// Promotional bonus paid on top of a claim while the booster window is open
+    /// The bonus is funded separately and never counts against a certificate's earned interest
+    pub fn booster_bonus(&self, amount: u64, current_time: u64) -> Result<u64, u32> {
+        if self.booster_bps == 0 || current_time >= self.booster_end_tick {
+            return Ok(0);
+        }
+        safe_div(safe_mul(amount, self.booster_bps)?, crate::certificate::BASIS_POINTS_DIVISOR)
+    }
+
+    /// Record a (counter, total_funds) sample when the counter hits the sampling interval
+    /// The buffer is bounded: once full the oldest sample is dropped
+    pub fn sample_tvl(&mut self) {
+        if self.tvl_sample_interval == 0 || self.counter % self.tvl_sample_interval != 0 {
+            return;
+        }
+        if self.tvl_samples.last().map(|(counter, _)| *counter) == Some(self.counter) {
+            return; // Already sampled this tick
+        }
+        if self.tvl_samples.len() >= TVL_SAMPLE_CAPACITY {
+            self.tvl_samples.remove(0);
+        }
+        self.tvl_samples.push((self.counter, self.total_funds));
+    }
+
+    // Global fund accounting, shared by the command handlers and the conservation tests.
+    // Invariant: tracked_user_liabilities() == Σ(idle_funds) + Σ(unredeemed certificate principal).
+    // Admin withdrawals lend out cash but do not change what users are owed.
+    // Interest is not pre-funded by deposits: every USDT interest credit, claims and redemptions
+    // alike, keeps interest_claimed + booster_paid within total_recharge_amount (see interest_reserve).
+    // Each method validates everything before mutating so a failure leaves the state untouched.
+
+    /// Funds owed to users according to the global counters
+    /// Claimed interest is credited to idle funds without being added to total_funds
+    pub fn tracked_user_liabilities(&self) -> Result<u64, u32> {
+        let funds = safe_add(self.total_funds, self.total_recharge_amount)?;
+        safe_add(safe_add(funds, self.interest_claimed)?, self.booster_paid)
+    }
+
+    /// Admin deposit credited to a user's idle funds
+    pub fn record_deposit(&mut self, amount: u64) -> Result<(), u32> {
+        self.total_funds = safe_add(self.total_funds, amount)?;
+        Ok(())
+    }
+
+    /// User withdrawal of idle funds to an external address
+    pub fn record_withdrawal(&mut self, amount: u64) -> Result<(), u32> {
+        self.check_withdrawal(amount)?;
+        self.total_funds = safe_sub(self.total_funds, amount)?;
+        Ok(())
+    }
+
+    /// Whether `amount` can be withdrawn without leaving admin-borrowed funds uncovered
+    pub fn check_withdrawal(&self, amount: u64) -> Result<(), u32> {
+        let held = crate::config::calculate_user_withdrawable(
+            self.total_funds,
+            self.cumulative_admin_withdrawals,
+            self.total_recharge_amount
+        )?;
+        if amount > held {
+            return Err(ERROR_INSUFFICIENT_BALANCE);
+        }
+        safe_sub(self.total_funds, amount).map(|_| ())
+    }
+
+    /// User withdrawal together with the withdrawal fee, both validated before either is recorded
+    pub fn record_withdrawal_with_fee(&mut self, amount: u64, fee: u64) -> Result<(), u32> {
+        self.check_withdrawal(safe_add(amount, fee)?)?;
+        self.check_fee(fee)?;
+        self.record_withdrawal(amount)?;
+        self.record_fee(fee)
+    }
+
+    /// Fee or penalty taken from a user's idle funds into the reserve pool
+    /// The amount stops being a user liability and becomes protocol revenue
+    pub fn record_fee(&mut self, amount: u64) -> Result<(), u32> {
+        self.check_fee(amount)?;
+        self.release_user_funds(amount)?;
+        self.reserve_pool += amount;
+        Ok(())
+    }
+
+    /// Whether `amount` can move into the reserve pool, for handlers that store before charging
+    pub fn check_fee(&self, amount: u64) -> Result<(), u32> {
+        safe_add(self.reserve_pool, amount)?;
+        self.check_user_funds_release(amount)
+    }
+
+    /// Interest an early exit forfeits, paid into the reserve pool instead of the user
+    /// It is drawn on the interest reserve like a claim, so only the part the reserve backs is
+    /// moved; the rest was never funded. Returns the amount credited to the pool
+    pub fn record_forfeited_interest(&mut self, amount: u64) -> Result<u64, u32> {
+        let amount = amount.min(self.interest_reserve());
+        let interest_claimed = safe_add(self.interest_claimed, amount)?;
+        self.check_fee(amount)?;
+        self.interest_claimed = interest_claimed;
+        self.record_fee(amount)?;
+        Ok(amount)
+    }
+
+    /// Admin withdrawal of accumulated protocol revenue
+    pub fn record_reserve_pool_withdrawal(&mut self, amount: u64) -> Result<(), u32> {
+        if amount > self.reserve_pool {
+            return Err(ERROR_INSUFFICIENT_BALANCE);
+        }
+        self.reserve_pool = safe_sub(self.reserve_pool, amount)?;
+        Ok(())
+    }
+
+    /// Whether `amount` can leave user ownership, for handlers that must check before several steps
+    pub fn check_user_funds_release(&self, amount: u64) -> Result<(), u32> {
+        self.release_split(amount).map(|_| ())
+    }
+
+    /// Split a release into the part drawn from deposits and the part drawn from recharge funding
+    fn release_split(&self, amount: u64) -> Result<(u64, u64), u32> {
+        let from_deposits = amount.min(self.total_funds);
+        let from_recharge = safe_sub(amount, from_deposits)?;
+        if from_recharge > self.total_recharge_amount {
+            return Err(ERROR_INSUFFICIENT_BALANCE);
+        }
+        Ok((from_deposits, from_recharge))
+    }
+
+    /// Remove funds leaving user ownership (withdrawals, fees)
+    fn release_user_funds(&mut self, amount: u64) -> Result<(), u32> {
+        let (from_deposits, from_recharge) = self.release_split(amount)?;
+        self.total_funds = safe_sub(self.total_funds, from_deposits)?;
+        self.total_recharge_amount = safe_sub(self.total_recharge_amount, from_recharge)?;
+        Ok(())
+    }
+
+    /// Certificate purchase from idle funds
+    /// Normal purchases keep the money in the system as locked principal; product 0 (recharge)
+    /// converts the user's funds from "user principal" into "external recharge funding"
+    pub fn record_certificate_purchase(&mut self, product_type_id: u64, amount: u64) -> Result<(), u32> {
+        if product_type_id == 0 {
+            let total_funds = safe_sub(self.total_funds, amount)?;
+            let total_recharge_amount = safe_add(self.total_recharge_amount, amount)?;
+            self.total_funds = total_funds;
+            self.total_recharge_amount = total_recharge_amount;
+        }
+        Ok(())
+    }
+
+    /// Admin correction to total_recharge_amount, only while reconcile_mode is on
+    /// Returns the amount before and after the correction
+    pub fn adjust_recharge(&mut self, delta: i64) -> Result<(u64, u64), u32> {
+        if !self.reconcile_mode {
+            return Err(ERROR_RECONCILE_MODE_DISABLED);
+        }
+        let old_amount = self.total_recharge_amount;
+        let new_amount = if delta >= 0 {
+            safe_add(old_amount, delta.unsigned_abs())?
+        } else {
+            safe_sub(old_amount, delta.unsigned_abs())?
+        };
+        self.total_recharge_amount = new_amount;
+        Ok((old_amount, new_amount))
+    }
+
+    /// Product types ever created; ids start at 1, so this is the counter minus one
+    pub fn total_products_created(&self) -> u64 {
+        self.product_type_counter.saturating_sub(1)
+    }
+
+    /// Track a product opening for or closing to purchase
+    /// Saturating: state written before active_products existed starts the count at 0
+    pub fn record_product_status(&mut self, was_active: bool, is_active: bool) {
+        match (was_active, is_active) {
+            (false, true) => self.active_products += 1,
+            (true, false) => self.active_products = self.active_products.saturating_sub(1),
+            _ => (),
+        }
+    }
+
+    /// A certificate was sold
+    pub fn record_certificate_opened(&mut self) {
+        self.certificates_issued = self.certificates_issued.saturating_add(1);
+        self.active_certificate_count = self.active_certificate_count.saturating_add(1);
+    }
+
+    /// A certificate was redeemed in full, saturating as certificates sold before the count existed were never added
+    pub fn record_certificate_closed(&mut self) {
+        self.active_certificate_count = self.active_certificate_count.saturating_sub(1);
+    }
+
+    /// A certificate opened, its USDT principal and scheduled interest become liabilities
+    pub fn record_certificate_liability(&mut self, principal: u64, interest: u64) {
+        self.total_certificate_principal = self.total_certificate_principal.saturating_add(principal);
+        self.total_interest_owed = self.total_interest_owed.saturating_add(interest);
+    }
+
+    /// Principal returned to its owner, saturating as certificates sold before the
+    /// liability aggregates existed were never added
+    pub fn release_certificate_principal(&mut self, principal: u64) {
+        self.total_certificate_principal = self.total_certificate_principal.saturating_sub(principal);
+    }
+
+    /// How much of a `requested` interest claim the per-tick budget allows at the current counter
+    /// Over budget the claim is rejected, or with interest_tick_partial paid down to what is left
+    /// as long as that still meets the claim minimum
+    pub fn interest_tick_allowance(&self, requested: u64) -> Result<u64, u32> {
+        if self.max_interest_per_tick == 0 {
+            return Ok(requested);
+        }
+        let used = if self.last_interest_tick == self.counter { self.interest_paid_this_tick } else { 0 };
+        let remaining = self.max_interest_per_tick.saturating_sub(used);
+        if requested <= remaining {
+            Ok(requested)
+        } else if self.interest_tick_partial && remaining >= crate::certificate::MIN_INTEREST_CLAIM {
+            Ok(remaining)
+        } else {
+            Err(ERROR_INTEREST_TICK_BUDGET)
         }
     }
 
+    /// Change the reserve ratio, returning the old one
+    /// The ratio may not drop below MIN_RESERVE_RATIO or change again within a day of ticks at the
+    /// current tick length (RESERVE_RATIO_CHANGE_COOLDOWN_TICKS at the default)
+    pub fn set_reserve_ratio(&mut self, reserve_ratio: u64, counter: u64) -> Result<u64, u32> {
+        if reserve_ratio < crate::config::MIN_RESERVE_RATIO {
+            return Err(ERROR_RESERVE_RATIO_TOO_LOW);
+        }
+        if self.last_reserve_change_counter != 0
+            && counter.saturating_sub(self.last_reserve_change_counter) < crate::config::ticks_per_day(self.seconds_per_tick) {
+            return Err(ERROR_RESERVE_RATIO_CHANGE_TOO_SOON);
+        }
+        self.last_reserve_change_counter = counter;
+        Ok(std::mem::replace(&mut self.reserve_ratio, reserve_ratio))
+    }
+
+    /// Count a claim against the current tick's interest budget
+    pub fn record_tick_interest(&mut self, interest: u64) {
+        if self.last_interest_tick != self.counter {
+            self.last_interest_tick = self.counter;
+            self.interest_paid_this_tick = 0;
+        }
+        self.interest_paid_this_tick = self.interest_paid_this_tick.saturating_add(interest);
+    }
+
+    /// Scheduled interest paid out or given up (early exits, forced maturity), saturating likewise
+    pub fn settle_interest_owed(&mut self, interest: u64) {
+        self.total_interest_owed = self.total_interest_owed.saturating_sub(interest);
+    }
+
+    /// Welcome bonus granted to a newly installed player, returns the points to credit
+    pub fn record_welcome_points(&mut self) -> Result<u64, u32> {
+        self.total_points_issued = safe_add(self.total_points_issued, self.welcome_points)?;
+        Ok(self.welcome_points)
+    }
+
+    /// Data for a newly installed player, counted in total_players
+    /// The admin account is not a user and gets no welcome bonus
+    pub fn record_new_player(&mut self, pkey: &[u64; 4]) -> Result<crate::player::PlayerData, u32> {
+        let mut data = crate::player::PlayerData::new();
+        if *pkey != *crate::config::ADMIN_PUBKEY {
+            data.points = self.record_welcome_points()?;
+        }
+        self.total_players += 1;
+        Ok(data)
+    }
+
+    /// Whether interest paid in points can be recorded, for credits worked out ahead
+    pub fn check_points_interest(&self, amount: u64) -> Result<(), u32> {
+        safe_add(self.total_points_issued, amount).map(|_| ())
+    }
+
+    /// Interest paid out in points rather than idle funds
+    pub fn record_points_interest(&mut self, amount: u64) -> Result<(), u32> {
+        self.total_points_issued = safe_add(self.total_points_issued, amount)?;
+        Ok(())
+    }
+
+    /// Points exchanged for idle funds, which become part of total_funds like a deposit
+    pub fn record_points_conversion(&mut self, points: u64, funds: u64) -> Result<(), u32> {
+        let total_points_withdrawn = safe_add(self.total_points_withdrawn, points)?;
+        self.total_funds = safe_add(self.total_funds, funds)?;
+        self.total_points_withdrawn = total_points_withdrawn;
+        Ok(())
+    }
+
+    /// Recharge funding not yet spent on interest, what a USDT interest credit may still draw
+    /// Invariant kept by check_interest_claim: interest_claimed + booster_paid <= total_recharge_amount
+    pub fn interest_reserve(&self) -> u64 {
+        let paid = self.interest_claimed.saturating_add(self.booster_paid);
+        self.total_recharge_amount.saturating_sub(paid)
+    }
+
+    /// Whether the recharge funding can back a further `interest` payout and its booster `bonus`
+    pub fn check_interest_reserve(&self, interest: u64, bonus: u64) -> Result<(), u32> {
+        if safe_add(interest, bonus)? > self.interest_reserve() {
+            return Err(ERROR_INSUFFICIENT_INTEREST);
+        }
+        Ok(())
+    }
+
+    /// Whether an interest claim and its bonus can be recorded, for credits worked out ahead
+    /// Both must be backed by the recharge reserve, uncapped redemption payouts included
+    pub fn check_interest_claim(&self, amount: u64, bonus: u64) -> Result<(), u32> {
+        safe_add(self.interest_claimed, amount)?;
+        safe_add(self.booster_paid, bonus)?;
+        self.check_interest_reserve(amount, bonus)
+    }
+
+    /// Interest credited to a user's idle funds, with any booster bonus tracked separately
+    pub fn record_interest_claim(&mut self, amount: u64, bonus: u64) -> Result<(), u32> {
+        self.check_interest_claim(amount, bonus)?;
+        let interest_claimed = safe_add(self.interest_claimed, amount)?;
+        let booster_paid = safe_add(self.booster_paid, bonus)?;
+        self.interest_claimed = interest_claimed;
+        self.booster_paid = booster_paid;
+        Ok(())
+    }
+
+    /// Replace a certificate's previous rounding drift with its drift at the current claim
+    /// Summed over certificates this is the interest the floored formula has withheld so far
+    pub fn record_rounding_drift(&mut self, previous: u64, current: u64) -> Result<(), u32> {
+        self.rounding_surplus = self.rounding_surplus_after(previous, current)?;
+        Ok(())
+    }
+
+    /// Rounding surplus once a certificate's drift moves from `previous` to `current`
+    pub fn rounding_surplus_after(&self, previous: u64, current: u64) -> Result<u64, u32> {
+        safe_add(safe_sub(self.rounding_surplus, previous)?, current)
+    }
+
+    /// Global accounting for a claim of USDT interest: the interest leaves the scheduled liability,
+    /// counts against this tick's budget when `budgeted`, and the certificate's rounding drift
+    /// moves from `previous` to `drift`
+    /// The fallible drift update runs first, so a failing claim records nothing
+    pub fn record_usdt_claim(&mut self, interest: u64, budgeted: bool, previous: u64, drift: u64) -> Result<(), u32> {
+        self.record_rounding_drift(previous, drift)?;
+        self.settle_interest_owed(interest);
+        if budgeted {
+            self.record_tick_interest(interest);
+        }
+        Ok(())
+    }
+
+    /// Whole units of rounding surplus not yet withdrawn
+    /// The surplus itself keeps tracking live drift, so withdrawals are counted separately
+    pub fn withdrawable_rounding_surplus(&self) -> u64 {
+        (self.rounding_surplus / crate::certificate::ROUNDING_SURPLUS_SCALE).saturating_sub(self.rounding_surplus_withdrawn)
+    }
+
+    pub fn record_rounding_surplus_withdrawal(&mut self, amount: u64) -> Result<(), u32> {
+        if amount > self.withdrawable_rounding_surplus() {
+            return Err(ERROR_INSUFFICIENT_BALANCE);
+        }
+        self.rounding_surplus_withdrawn = safe_add(self.rounding_surplus_withdrawn, amount)?;
+        Ok(())
+    }
+
+    /// Funds the admin may still withdraw under the reserve ratio
+    pub fn available_admin_funds(&self) -> Result<u64, u32> {
+        crate::config::calculate_available_funds(
+            self.total_funds,
+            self.cumulative_admin_withdrawals,
+            self.total_recharge_amount,
+            self.reserve_ratio,
+            self.reserve_ramp_threshold,
+            self.reserve_ramp_ratio,
+            self.min_absolute_reserve
+        )
+    }
+
+    /// Admin withdrawal to multisig, limited by the reserve ratio
+    pub fn record_admin_withdrawal(&mut self, amount: u64) -> Result<(), u32> {
+        if amount > self.available_admin_funds()? {
+            return Err(ERROR_INSUFFICIENT_BALANCE);
+        }
+        self.cumulative_admin_withdrawals = safe_add(self.cumulative_admin_withdrawals, amount)?;
+        Ok(())
+    }
+
+    /// Funds returned by the admin, reducing cumulative withdrawals (saturating at 0)
+    pub fn record_admin_repayment(&mut self, amount: u64) -> (u64, u64) {
+        let old_withdrawals = self.cumulative_admin_withdrawals;
+        self.cumulative_admin_withdrawals = old_withdrawals.saturating_sub(amount);
+        (old_withdrawals, self.cumulative_admin_withdrawals)
+    }
+
+    /// Update a tunable global parameter, returning the previous value
+    pub fn set_param(&mut self, param: u64, value: u64) -> Result<u64, u32> {
+        let old = match param {
+            PARAM_TVL_SAMPLE_INTERVAL => std::mem::replace(&mut self.tvl_sample_interval, value),
+            PARAM_BOOSTER_BPS => {
+                if value > MAX_BOOSTER_BPS {
+                    return Err(ERROR_INVALID_PARAM);
+                }
+                std::mem::replace(&mut self.booster_bps, value)
+            }
+            PARAM_BOOSTER_END_TICK => std::mem::replace(&mut self.booster_end_tick, value),
+            PARAM_DEPOSIT_MATURATION_TICKS => std::mem::replace(&mut self.deposit_maturation_ticks, value),
+            PARAM_RESERVE_RAMP_THRESHOLD => std::mem::replace(&mut self.reserve_ramp_threshold, value),
+            PARAM_RESERVE_RAMP_RATIO => {
+                if value > 10000 {
+                    return Err(ERROR_INVALID_PARAM);
+                }
+                std::mem::replace(&mut self.reserve_ramp_ratio, value)
+            }
+            PARAM_AUTO_REDEEM_ON_CLAIM => {
+                if value > 1 {
+                    return Err(ERROR_INVALID_PARAM);
+                }
+                std::mem::replace(&mut self.auto_redeem_on_claim, value == 1) as u64
+            }
+            PARAM_RECONCILE_MODE => {
+                if value > 1 {
+                    return Err(ERROR_INVALID_PARAM);
+                }
+                std::mem::replace(&mut self.reconcile_mode, value == 1) as u64
+            }
+            PARAM_INTEREST_TICK_PARTIAL => {
+                if value > 1 {
+                    return Err(ERROR_INVALID_PARAM);
+                }
+                std::mem::replace(&mut self.interest_tick_partial, value == 1) as u64
+            }
+            PARAM_QUERY_PAGE_SIZE => {
+                if value == 0 || value > crate::cert_manager::MAX_ITERATE_KEYS as u64 {
+                    return Err(ERROR_INVALID_PARAM);
+                }
+                std::mem::replace(&mut self.query_page_size, value)
+            }
+            PARAM_WELCOME_POINTS => std::mem::replace(&mut self.welcome_points, value),
+            PARAM_MAX_CLAIM_LOOKBACK_TICKS => std::mem::replace(&mut self.max_claim_lookback_ticks, value),
+            PARAM_WITHDRAW_COOLDOWN_TICKS => std::mem::replace(&mut self.withdraw_cooldown_ticks, value),
+            PARAM_USER_DAILY_WITHDRAW_LIMIT => std::mem::replace(&mut self.user_daily_withdraw_limit, value),
+            PARAM_MIN_ABSOLUTE_RESERVE => std::mem::replace(&mut self.min_absolute_reserve, value),
+            PARAM_POINTS_TO_FUNDS_RATE => std::mem::replace(&mut self.points_to_funds_rate, value),
+            PARAM_MAX_INTEREST_PER_TICK => std::mem::replace(&mut self.max_interest_per_tick, value),
+            PARAM_INTEREST_POINTS_RATE => {
+                if value == 0 {
+                    return Err(ERROR_INVALID_PARAM);
+                }
+                std::mem::replace(&mut self.interest_points_rate, value)
+            }
+            // Only certificates bought after the change use the new length
+            // A day must be a whole number of ticks, so day-based periods stay exact
+            PARAM_SECONDS_PER_TICK => {
+                if value == 0 || value > crate::config::MAX_SECONDS_PER_TICK || crate::certificate::SECONDS_PER_DAY % value != 0 {
+                    return Err(ERROR_INVALID_PARAM);
+                }
+                std::mem::replace(&mut self.seconds_per_tick, value)
+            }
+            PARAM_WITHDRAWAL_FEE => std::mem::replace(&mut self.withdrawal_fee, value),
+            PARAM_TRANSFER_FEE => std::mem::replace(&mut self.transfer_fee, value),
+            _ => return Err(ERROR_INVALID_PARAM),
+        };
+        Ok(old)
+    }
+
+    /// Entries a list query may return: the requested count clamped to query_page_size (0 means the page size)
+    pub fn page_limit(&self, requested: u64) -> usize {
+        if requested == 0 || requested > self.query_page_size {
+            self.query_page_size as usize
+        } else {
+            requested as usize
+        }
+    }
+
+    /// Time up to which interest may be claimed
+    /// While payouts are paused only interest accrued before the pause is claimable
+    pub fn interest_claim_cutoff(&self, current_time: u64) -> u64 {
+        if self.payouts_paused {
+            current_time.min(self.pause_start_tick)
+        } else {
+            current_time
+        }
+    }
+
+    /// Pause or resume payouts, recording the pause start on the first pause
+    pub fn set_payouts_paused(&mut self, paused: bool) {
+        if paused && !self.payouts_paused {
+            self.pause_start_tick = self.counter;
+        }
+        self.payouts_paused = paused;
+    }
+
     pub fn snapshot() -> String {
-        let state = GLOBAL_STATE.0.borrow();
-        
-        let query_state = QueryState {
-            counter: state.counter,
-            total_players: state.total_players,
-            total_funds: state.total_funds,
-            interest_claimed: state.interest_claimed,
-            cumulative_admin_withdrawals: state.cumulative_admin_withdrawals,
-            total_recharge_amount: state.total_recharge_amount,
-            reserve_ratio: state.reserve_ratio,
+        with_state(|state| {
+            let query_state = QueryState {
+                counter: state.counter,
+                total_players: state.total_players,
+                total_funds: state.total_funds,
+                interest_claimed: state.interest_claimed,
+                cumulative_admin_withdrawals: state.cumulative_admin_withdrawals,
+                total_recharge_amount: state.total_recharge_amount,
+                reserve_ratio: state.reserve_ratio,
+                total_products_created: state.total_products_created(),
+                active_products: state.active_products,
+                total_certificate_principal: state.total_certificate_principal,
+                total_interest_owed: state.total_interest_owed,
+                certificates_issued: state.certificates_issued,
+                active_certificate_count: state.active_certificate_count,
+            };
+            serde_json::to_string(&query_state).unwrap()
+        })
+    }
+
+    pub fn tvl_series() -> String {
+        with_state(|state| {
+            let series = TvlSeries {
+                interval: state.tvl_sample_interval,
+                samples: &state.tvl_samples,
+            };
+            serde_json::to_string(&series).unwrap()
+        })
+    }
+
+    /// Protocol-side balances kept outside user funds
+    pub fn treasury() -> String {
+        with_state(|state| {
+            let treasury = Treasury {
+                reserve_pool: state.reserve_pool,
+                cumulative_admin_withdrawals: state.cumulative_admin_withdrawals,
+                interest_claimed: state.interest_claimed,
+                booster_paid: state.booster_paid,
+                rounding_surplus: state.rounding_surplus,
+                rounding_surplus_scale: crate::certificate::ROUNDING_SURPLUS_SCALE,
+                rounding_surplus_withdrawn: state.rounding_surplus_withdrawn,
+                withdrawable_rounding_surplus: state.withdrawable_rounding_surplus(),
+            };
+            serde_json::to_string(&treasury).unwrap()
+        })
+    }
+
+    /// The admin-withdrawable amount from the on-chain formula, with the inputs it was computed from
+    pub fn admin_available_funds() -> String {
+        with_state(|state| serde_json::to_string(&state.admin_available_funds_view()).unwrap())
+    }
+
+    fn admin_available_funds_view(&self) -> AdminAvailableFunds {
+        let (available, error) = match self.available_admin_funds() {
+            Ok(available) => (available, 0),
+            Err(error) => (0, error),
+        };
+        AdminAvailableFunds {
+            available,
+            error,
+            total_funds: self.total_funds,
+            total_recharge_amount: self.total_recharge_amount,
+            cumulative_admin_withdrawals: self.cumulative_admin_withdrawals,
+            reserve_ratio: self.reserve_ratio,
+            effective_reserve_ratio: crate::config::effective_reserve_ratio(
+                self.total_funds,
+                self.reserve_ratio,
+                self.reserve_ramp_threshold,
+                self.reserve_ramp_ratio
+            ),
+            reserve_ramp_threshold: self.reserve_ramp_threshold,
+            reserve_ramp_ratio: self.reserve_ramp_ratio,
+            min_absolute_reserve: self.min_absolute_reserve,
+        }
+    }
+
+    /// Active product types in display order, one page starting at product id `start`
+    pub fn active_products(start: u64, max: u64) -> String {
+        let limit = with_state(|state| state.page_limit(max));
+        let page = crate::cert_manager::ProductTypeManager::list_active_products(start, limit);
+        serde_json::to_string(&page).unwrap()
+    }
+
+    /// Every error code with its name and category, for client error docs
+    pub fn error_table() -> String {
+        let table: Vec<ErrorInfo> = crate::command::ERROR_CODES
+            .iter()
+            .map(|&code| ErrorInfo {
+                code,
+                name: crate::command::decode_error(code),
+                category: crate::command::error_category(code),
+            })
+            .collect();
+        serde_json::to_string(&table).unwrap()
+    }
+
+    /// Event name to event type number, for indexers
+    pub fn event_types() -> String {
+        let types: std::collections::BTreeMap<&str, u64> = crate::config::EVENT_TYPES.iter().copied().collect();
+        serde_json::to_string(&types).unwrap()
+    }
+
+    /// All product types, including inactive ones and the recharge product
+    pub fn product_types() -> String {
+        let products = crate::cert_manager::ProductTypeManager::list_product_types();
+        serde_json::to_string(&products).unwrap()
+    }
+
+    /// Whether `command` sent by `pkey` would pass the command-level checks, without executing it
+    /// Only the command id, the caller's roles, the system pause and player installation are checked;
+    /// argument-dependent limits such as caps, balances and the payout cut-off apply when it executes
+    pub fn can_execute(command: u64, pkey: Vec<u64>) -> String {
+        let reason = Self::preflight_reason(command, pkey);
+        let preflight = CommandPreflight {
+            allowed: reason == 0,
+            reason,
         };
-        serde_json::to_string(&query_state).unwrap()
+        serde_json::to_string(&preflight).unwrap()
+    }
+
+    /// A pkey that is not four words is rejected like any other malformed argument
+    fn preflight_reason(command: u64, pkey: Vec<u64>) -> u32 {
+        let pkey: [u64; 4] = match pkey.try_into() {
+            Ok(pkey) => pkey,
+            Err(_) => return ERROR_INVALID_PARAM,
+        };
+        let reason = with_state(|state| state.command_precheck(command, state.roles_of(&pkey)));
+        if reason != 0 {
+            return reason;
+        }
+        let installed = StakingPlayer::get(&pkey).is_some();
+        if command == INSTALL_PLAYER && installed {
+            ERROR_PLAYER_ALREADY_EXIST
+        } else if command != INSTALL_PLAYER && command != TICK && !installed {
+            ERROR_PLAYER_NOT_EXIST
+        } else {
+            0
+        }
+    }
+
+    /// The pid of a queried pkey, one that is not four words is rejected as in `preflight_reason`
+    fn query_pid(pkey: Vec<u64>) -> Result<[u64; 2], u32> {
+        let pkey: [u64; 4] = pkey.try_into().map_err(|_| ERROR_INVALID_PARAM)?;
+        Ok(StakingPlayer::pkey_to_pid(&pkey))
+    }
+
+    /// A player's open certificates with their claimable interest and maturity at the current counter
+    pub fn certificates(pkey: Vec<u64>) -> String {
+        match Self::query_pid(pkey) {
+            Ok(pid) => serde_json::to_string(&Self::certificate_views(&pid)).unwrap(),
+            Err(error) => serde_json::to_string(&QueryError { error }).unwrap(),
+        }
+    }
+
+    /// Interest a player's certificate will have available at tick `at_time`
+    pub fn projected_interest(pkey: Vec<u64>, cert_id: u64, at_time: u64) -> String {
+        let projected = Self::query_pid(pkey)
+            .and_then(|pid| crate::cert_manager::CertificateManager::project_interest(&pid, cert_id, at_time));
+        let projection = match projected {
+            Ok(interest) => InterestProjection { interest, error: 0 },
+            Err(error) => InterestProjection { interest: 0, error },
+        };
+        serde_json::to_string(&projection).unwrap()
+    }
+
+    /// A player's redeemed certificates, at most one page of `max`
+    pub fn closed_certificates(pkey: Vec<u64>, max: u64) -> String {
+        let pid = match Self::query_pid(pkey) {
+            Ok(pid) => pid,
+            Err(error) => return serde_json::to_string(&QueryError { error }).unwrap(),
+        };
+        let limit = with_state(|state| state.page_limit(max));
+        let closed = crate::cert_manager::CertificateManager::closed_certificates(&pid, limit);
+        serde_json::to_string(&closed).unwrap()
+    }
+
+    /// A player's live certificates, next to mature first (ties keep id order)
+    pub fn certificate_views(owner: &[u64; 2]) -> Vec<CertificateView> {
+        let certificates = crate::cert_manager::CertificateManager::list_certificates(owner);
+        with_state(|state| state.certificate_views_from(certificates))
+    }
+
+    /// Storage-independent core of `certificate_views`, over an owner's listed certificates
+    pub fn certificate_views_from(&self, certificates: Vec<crate::certificate::Certificate>) -> Vec<CertificateView> {
+        let (counter, claim_time) = (self.counter, self.interest_claim_cutoff(self.counter));
+        let mut views: Vec<CertificateView> = certificates
+            .into_iter()
+            .filter(|certificate| !matches!(certificate.status, crate::certificate::CertificateStatus::Redeemed))
+            .map(|certificate| crate::cert_manager::CertificateManager::with_current_status(certificate, counter))
+            .map(|certificate| CertificateView {
+                available_interest: certificate.calculate_available_interest(claim_time).unwrap_or(0),
+                is_matured: certificate.is_matured(counter),
+                duration_days: certificate.duration_days(crate::config::ticks_per_day(certificate.seconds_per_tick)),
+                // Measured to accrual_end so it reaches 0 exactly when is_matured flips
+                time_to_maturity: certificate.accrual_end().saturating_sub(counter),
+                certificate,
+            })
+            .collect();
+        views.sort_by_key(|view| view.certificate.accrual_end());
+        views
     }
 
     pub fn get_state(pid: Vec<u64>) -> String {
@@ -79,12 +1011,17 @@ impl GlobalState {
     }
 
     pub fn preempt() -> bool {
-        let mut state = GLOBAL_STATE.0.borrow_mut();
-        let counter = state.counter;
-        let txsize = state.txsize;
         let withdraw_size = crate::settlement::SettlementInfo::settlement_size();
-        if counter % 600 == 0 || txsize >= 40 || withdraw_size > 40 {
-            state.txsize = 0;
+        with_state_mut(|state| state.preempt_due(withdraw_size))
+    }
+
+    /// Storage-independent core of `preempt`, with `withdraw_size` settlements queued
+    pub fn preempt_due(&mut self, withdraw_size: usize) -> bool {
+        self.sample_tvl();
+        // The periodic flush starts after genesis, counter 0 has nothing to settle yet
+        let periodic = self.counter != 0 && self.counter % 600 == 0;
+        if periodic || self.txsize >= 40 || withdraw_size > 40 {
+            self.txsize = 0;
             true
         } else {
             false
@@ -101,7 +1038,7 @@ impl GlobalState {
 
     pub fn store() {
         let mut data = vec![];
-        GLOBAL_STATE.0.borrow_mut().to_data(&mut data);
+        with_state(|state| state.to_data(&mut data));
         let kvpair = unsafe { &mut MERKLE_MAP };
         kvpair.set(&[0, 0, 0, 0], data.as_slice());
     }
@@ -111,12 +1048,13 @@ impl GlobalState {
         let mut data = kvpair.get(&[0, 0, 0, 0]);
         if !data.is_empty() {
             let mut u64data = data.iter_mut();
-            *GLOBAL_STATE.0.borrow_mut() = Self::from_data(&mut u64data);
+            let loaded = Self::from_data(&mut u64data);
+            with_state_mut(|state| *state = loaded);
         }
     }
 
     pub fn get_counter() -> u64 {
-        GLOBAL_STATE.0.borrow().counter
+        with_state(|state| state.counter)
     }
 }
 
@@ -136,6 +1074,59 @@ impl StorageData for GlobalState {
         let cumulative_admin_withdrawals = u64data.next().copied().unwrap_or(0);
         let interest_claimed = u64data.next().copied().unwrap_or(0);
         let total_recharge_amount = u64data.next().copied().unwrap_or(0);
+        let payouts_paused = u64data.next().copied().unwrap_or(0) != 0;
+        let pause_start_tick = u64data.next().copied().unwrap_or(0);
+        let tvl_sample_interval = u64data.next().copied().unwrap_or(DEFAULT_TVL_SAMPLE_INTERVAL);
+        let tvl_sample_count = u64data.next().copied().unwrap_or(0);
+        let mut tvl_samples = Vec::with_capacity(tvl_sample_count as usize);
+        for _ in 0..tvl_sample_count {
+            let sample_counter = *u64data.next().unwrap();
+            let sample_funds = *u64data.next().unwrap();
+            tvl_samples.push((sample_counter, sample_funds));
+        }
+        let booster_bps = u64data.next().copied().unwrap_or(0);
+        let booster_end_tick = u64data.next().copied().unwrap_or(0);
+        let booster_paid = u64data.next().copied().unwrap_or(0);
+        let deposit_maturation_ticks = u64data.next().copied().unwrap_or(0);
+        let reserve_pool = u64data.next().copied().unwrap_or(0);
+        let reserve_ramp_threshold = u64data.next().copied().unwrap_or(0);
+        let reserve_ramp_ratio = u64data.next().copied().unwrap_or(DEFAULT_RESERVE_RAMP_RATIO);
+        let rounding_surplus = u64data.next().copied().unwrap_or(0);
+        let auto_redeem_on_claim = u64data.next().copied().unwrap_or(0) != 0;
+        let query_page_size = u64data.next().copied().unwrap_or(DEFAULT_QUERY_PAGE_SIZE);
+        let welcome_points = u64data.next().copied().unwrap_or(0);
+        let total_points_issued = u64data.next().copied().unwrap_or(0);
+        let max_claim_lookback_ticks = u64data.next().copied().unwrap_or(0);
+        let withdraw_cooldown_ticks = u64data.next().copied().unwrap_or(WITHDRAW_COOLDOWN_TICKS);
+        let paused = u64data.next().copied().unwrap_or(0) != 0;
+        let reconcile_mode = u64data.next().copied().unwrap_or(0) != 0;
+        let user_daily_withdraw_limit = u64data.next().copied().unwrap_or(0);
+        let admin_count = u64data.next().copied().unwrap_or(0);
+        let mut admin_roles = Vec::with_capacity(admin_count as usize);
+        for _ in 0..admin_count {
+            let admin = [*u64data.next().unwrap(), *u64data.next().unwrap()];
+            admin_roles.push((admin, *u64data.next().unwrap()));
+        }
+        let active_products = u64data.next().copied().unwrap_or(0);
+        let interest_points_rate = u64data.next().copied().unwrap_or(DEFAULT_INTEREST_POINTS_RATE);
+        let total_certificate_principal = u64data.next().copied().unwrap_or(0);
+        let total_interest_owed = u64data.next().copied().unwrap_or(0);
+        let min_absolute_reserve = u64data.next().copied().unwrap_or(0);
+        let points_to_funds_rate = u64data.next().copied().unwrap_or(0);
+        let total_points_withdrawn = u64data.next().copied().unwrap_or(0);
+        let rounding_surplus_withdrawn = u64data.next().copied().unwrap_or(0);
+        let max_interest_per_tick = u64data.next().copied().unwrap_or(0);
+        let interest_tick_partial = u64data.next().copied().unwrap_or(0) != 0;
+        let interest_paid_this_tick = u64data.next().copied().unwrap_or(0);
+        let last_interest_tick = u64data.next().copied().unwrap_or(0);
+        let last_reserve_change_counter = u64data.next().copied().unwrap_or(0);
+        let auto_claim_count = u64data.next().copied().unwrap_or(0);
+        let auto_claim_cursor = u64data.next().copied().unwrap_or(0);
+        let certificates_issued = u64data.next().copied().unwrap_or(0);
+        let active_certificate_count = u64data.next().copied().unwrap_or(0);
+        let seconds_per_tick = u64data.next().copied().unwrap_or(SECONDS_PER_TICK);
+        let withdrawal_fee = u64data.next().copied().unwrap_or(0);
+        let transfer_fee = u64data.next().copied().unwrap_or(0);
         
         GlobalState {
             counter,
@@ -149,6 +1140,48 @@ impl StorageData for GlobalState {
             cumulative_admin_withdrawals,
             interest_claimed,
             total_recharge_amount,
+            payouts_paused,
+            pause_start_tick,
+            tvl_sample_interval,
+            tvl_samples,
+            booster_bps,
+            booster_end_tick,
+            booster_paid,
+            deposit_maturation_ticks,
+            reserve_pool,
+            reserve_ramp_threshold,
+            reserve_ramp_ratio,
+            rounding_surplus,
+            auto_redeem_on_claim,
+            query_page_size,
+            welcome_points,
+            total_points_issued,
+            max_claim_lookback_ticks,
+            withdraw_cooldown_ticks,
+            paused,
+            reconcile_mode,
+            user_daily_withdraw_limit,
+            admin_roles,
+            active_products,
+            interest_points_rate,
+            total_certificate_principal,
+            total_interest_owed,
+            min_absolute_reserve,
+            points_to_funds_rate,
+            total_points_withdrawn,
+            rounding_surplus_withdrawn,
+            max_interest_per_tick,
+            interest_tick_partial,
+            interest_paid_this_tick,
+            last_interest_tick,
+            last_reserve_change_counter,
+            auto_claim_count,
+            auto_claim_cursor,
+            certificates_issued,
+            active_certificate_count,
+            seconds_per_tick,
+            withdrawal_fee,
+            transfer_fee,
         }
     }
 
@@ -164,6 +1197,57 @@ impl StorageData for GlobalState {
         data.push(self.cumulative_admin_withdrawals);
         data.push(self.interest_claimed);
         data.push(self.total_recharge_amount);
+        data.push(if self.payouts_paused { 1 } else { 0 });
+        data.push(self.pause_start_tick);
+        data.push(self.tvl_sample_interval);
+        data.push(self.tvl_samples.len() as u64);
+        for (sample_counter, sample_funds) in &self.tvl_samples {
+            data.push(*sample_counter);
+            data.push(*sample_funds);
+        }
+        data.push(self.booster_bps);
+        data.push(self.booster_end_tick);
+        data.push(self.booster_paid);
+        data.push(self.deposit_maturation_ticks);
+        data.push(self.reserve_pool);
+        data.push(self.reserve_ramp_threshold);
+        data.push(self.reserve_ramp_ratio);
+        data.push(self.rounding_surplus);
+        data.push(if self.auto_redeem_on_claim { 1 } else { 0 });
+        data.push(self.query_page_size);
+        data.push(self.welcome_points);
+        data.push(self.total_points_issued);
+        data.push(self.max_claim_lookback_ticks);
+        data.push(self.withdraw_cooldown_ticks);
+        data.push(if self.paused { 1 } else { 0 });
+        data.push(if self.reconcile_mode { 1 } else { 0 });
+        data.push(self.user_daily_withdraw_limit);
+        data.push(self.admin_roles.len() as u64);
+        for (admin, roles) in &self.admin_roles {
+            data.push(admin[0]);
+            data.push(admin[1]);
+            data.push(*roles);
+        }
+        data.push(self.active_products);
+        data.push(self.interest_points_rate);
+        data.push(self.total_certificate_principal);
+        data.push(self.total_interest_owed);
+        data.push(self.min_absolute_reserve);
+        data.push(self.points_to_funds_rate);
+        data.push(self.total_points_withdrawn);
+        data.push(self.rounding_surplus_withdrawn);
+        data.push(self.max_interest_per_tick);
+        data.push(if self.interest_tick_partial { 1 } else { 0 });
+        data.push(self.interest_paid_this_tick);
+        data.push(self.last_interest_tick);
+        data.push(self.last_reserve_change_counter);
+        data.push(self.auto_claim_count);
+        data.push(self.auto_claim_cursor);
+        data.push(self.certificates_issued);
+        data.push(self.active_certificate_count);
+        data.push(self.seconds_per_tick);
+        data.push(self.withdrawal_fee);
+        data.push(self.transfer_fee);
     }
 }
 
@@ -174,6 +1258,20 @@ lazy_static::lazy_static! {
     pub static ref GLOBAL_STATE: SafeState = SafeState(RefCell::new(GlobalState::new()));
 }
 
+/// Read the global state inside `f`
+///
+/// The borrow is scoped to the closure, so it can never be held across a handler call,
+/// storage access or event emission that might borrow again. Only nested `with_state_mut`
+/// (inside either accessor) conflicts, so closures should only touch `state` itself
+pub fn with_state<R>(f: impl FnOnce(&GlobalState) -> R) -> R {
+    f(&GLOBAL_STATE.0.borrow())
+}
+
+/// Mutate the global state inside `f`, see `with_state`
+pub fn with_state_mut<R>(f: impl FnOnce(&mut GlobalState) -> R) -> R {
+    f(&mut GLOBAL_STATE.0.borrow_mut())
+}
+
 // Staking Transaction constants
 const TICK: u64 = 0;
 const INSTALL_PLAYER: u64 = 1;
@@ -190,6 +1288,62 @@ const REDEEM_PRINCIPAL: u64 = 12;
 // Admin functions
 const ADMIN_WITHDRAW_TO_MULTISIG: u64 = 13;
 const SET_RESERVE_RATIO: u64 = 14;
+const SET_PAYOUTS_PAUSED: u64 = 15;
+const SET_GLOBAL_PARAM: u64 = 16;
+const WITHDRAW_RESERVE_POOL: u64 = 17;
+const SET_WHITELIST_BATCH: u64 = 18;
+const TRANSFER_CERTIFICATE: u64 = 19;
+const REDEEM_PRINCIPAL_PARTIAL: u64 = 20;
+const REDEEM_ALL: u64 = 21;
+const SET_PAUSED: u64 = 22;
+const ADMIN_ADJUST_RECHARGE: u64 = 23;
+const GRANT_ROLE: u64 = 24;
+const REVOKE_ROLE: u64 = 25;
+const REDEEM_EARLY: u64 = 26;
+const BATCH_PURCHASE: u64 = 27;
+const ADMIN_FORCE_WITHDRAW_PLAYER: u64 = 28;
+const ROLLOVER_CERTIFICATE: u64 = 29;
+const CLAIM_INTEREST_TO_TARGET: u64 = 30;
+const REDEEM_POINTS_FOR_FUNDS: u64 = 31;
+const ADMIN_REPAY: u64 = 32;
+const WITHDRAW_ROUNDING_SURPLUS: u64 = 33;
+const SET_PRODUCT_TYPE_STATUS: u64 = 34;
+const SWEEP_DUST: u64 = 35;
+const DEPOSIT_AND_PURCHASE: u64 = 36;
+const SET_APY_STEPS: u64 = 37;
+
+/// Admin role a command requires, 0 for user commands
+fn command_role(command: u64) -> u64 {
+    match command {
+        TICK | GRANT_ROLE | REVOKE_ROLE => ROLE_SUPERADMIN,
+        DEPOSIT | ADMIN_WITHDRAW_TO_MULTISIG | SET_RESERVE_RATIO | WITHDRAW_RESERVE_POOL
+            | ADMIN_ADJUST_RECHARGE | ADMIN_FORCE_WITHDRAW_PLAYER | ADMIN_REPAY
+            | WITHDRAW_ROUNDING_SURPLUS | DEPOSIT_AND_PURCHASE => ROLE_TREASURY,
+        CREATE_PRODUCT_TYPE | MODIFY_PRODUCT_TYPE | SET_WHITELIST_BATCH | SET_PRODUCT_TYPE_STATUS
+            | SET_APY_STEPS => ROLE_PRODUCT,
+        SET_PAYOUTS_PAUSED | SET_GLOBAL_PARAM | SET_PAUSED => ROLE_OPERATOR,
+        _ => 0,
+    }
+}
+
+/// Superadmins pass every role check
+fn roles_include(roles: u64, role: u64) -> bool {
+    roles & (role | ROLE_SUPERADMIN) != 0
+}
+
+fn is_admin_command(command: u64) -> bool {
+    command_role(command) != 0
+}
+
+fn is_known_command(command: u64) -> bool {
+    is_admin_command(command)
+        || matches!(
+            command,
+            INSTALL_PLAYER | WITHDRAW | WITHDRAW_POINTS | PURCHASE_CERTIFICATE | CLAIM_INTEREST | REDEEM_PRINCIPAL
+                | TRANSFER_CERTIFICATE | REDEEM_PRINCIPAL_PARTIAL | REDEEM_ALL | REDEEM_EARLY | BATCH_PURCHASE
+                | ROLLOVER_CERTIFICATE | CLAIM_INTEREST_TO_TARGET | REDEEM_POINTS_FOR_FUNDS | SWEEP_DUST
+        )
+}
 
 pub struct Transaction {
     command: crate::command::Command,
@@ -206,7 +1360,10 @@ impl Transaction {
             Command, Deposit, Withdraw, WithdrawPoints,
             CreateProductType, ModifyProductType, PurchaseCertificate,
             ClaimInterest, RedeemPrincipal, AdminWithdrawToMultisig,
-            SetReserveRatio
+            SetReserveRatio, SetPayoutsPaused, SetGlobalParam, WithdrawReservePool,
+            SetWhitelistBatch, TransferCertificate, RedeemPrincipalPartial, RedeemAll, RedeemEarly, BatchPurchase, SetPaused,
+            RolloverCertificate, ClaimInterestToTarget, RedeemPointsForFunds, AdminAdjustRecharge, GrantRole, RevokeRole, AdminForceWithdrawPlayer,
+            AdminRepay, WithdrawRoundingSurplus, SetProductTypeStatus, SetApySteps, SweepDust, DepositAndPurchase
         };
         use zkwasm_rest_abi::enforce;
         
@@ -228,26 +1385,37 @@ impl Transaction {
             Command::Deposit(Deposit {
                 data: [params[1], params[2], params[3]] // [userPid[0], userPid[1], amount]
             })
+        } else if command == DEPOSIT_AND_PURCHASE {
+            enforce(params.len() == 5, "deposit_and_purchase needs 5 params");
+            Command::DepositAndPurchase(DepositAndPurchase {
+                data: [params[1], params[2], params[3], params[4]] // [userPid[0], userPid[1], product_type_id, amount]
+            })
         } else if command == CREATE_PRODUCT_TYPE {
-            enforce(params.len() == 5, "create_product_type needs 5 params");
+            enforce(params.len() >= 5 && params.len() <= 15, "create_product_type needs 5 to 15 params");
             Command::CreateProductType(CreateProductType {
-                data: [params[1], params[2], params[3], params[4]] // [duration_ticks, apy, min_amount, is_active]
+                data: [params[1], params[2], params[3], params[4]], // [duration_ticks, apy, min_amount, is_active]
+                options: params[5..].to_vec() // optional [display_order, compounding, principal_currency, whitelist_only, max_total_principal, interest_currency, early_withdrawal_penalty_bps, max_certificates, grace_ticks, min_claim_interval_ticks]
             })
         } else if command == MODIFY_PRODUCT_TYPE {
-            enforce(params.len() == 6, "modify_product_type needs 6 params");
+            enforce(params.len() >= 6 && params.len() <= 16, "modify_product_type needs 6 to 16 params");
             Command::ModifyProductType(ModifyProductType {
-                data: [params[1], params[2], params[3], params[4], params[5]] // [product_type_id, new_apy, new_duration, new_min_amount, is_active]
+                data: [params[1], params[2], params[3], params[4], params[5]], // [product_type_id, new_apy, new_duration, new_min_amount, is_active]
+                options: params[6..].to_vec() // optional [display_order, compounding, principal_currency, whitelist_only, max_total_principal, interest_currency, early_withdrawal_penalty_bps, max_certificates, grace_ticks, min_claim_interval_ticks]
             })
         } else if command == PURCHASE_CERTIFICATE {
-            enforce(params.len() == 3, "purchase_certificate needs 3 params");
+            enforce((3..=5).contains(&params.len()), "purchase_certificate needs 3 to 5 params");
+            // params[3] = optional expected_apy, 0 or absent skips the check
+            // params[4] = optional auto_claim (1 = interest is claimed by the tick pass)
             Command::PurchaseCertificate(PurchaseCertificate {
-                data: [params[1], params[2]] // [product_type_id, amount]
+                // [product_type_id, amount, expected_apy, auto_claim]
+                data: [params[1], params[2], params.get(3).copied().unwrap_or(0), params.get(4).copied().unwrap_or(0)]
             })
         } else if command == CLAIM_INTEREST {
-            enforce(params.len() == 2, "claim_interest needs 2 params");
-            // params[1] = certificate_id
+            enforce(params.len() == 2 || params.len() == 3, "claim_interest needs 2 or 3 params");
+            // params[1] = certificate_id, params[2] = optional amount (0 or absent claims everything)
             Command::ClaimInterest(ClaimInterest {
-                certificate_id: params[1]
+                certificate_id: params[1],
+                amount: params.get(2).copied().unwrap_or(0)
             })
         } else if command == REDEEM_PRINCIPAL {
             enforce(params.len() == 2, "redeem_principal needs 2 params");
@@ -267,6 +1435,157 @@ impl Transaction {
             Command::SetReserveRatio(SetReserveRatio {
                 reserve_ratio: params[1]
             })
+        } else if command == SET_PAYOUTS_PAUSED {
+            enforce(params.len() == 2, "set_payouts_paused needs 2 params");
+            // params[1] = paused (0 = resume, non-zero = pause)
+            Command::SetPayoutsPaused(SetPayoutsPaused {
+                paused: params[1] != 0
+            })
+        } else if command == SET_GLOBAL_PARAM {
+            enforce(params.len() == 3, "set_global_param needs 3 params");
+            // params[1] = param id, params[2] = value
+            Command::SetGlobalParam(SetGlobalParam {
+                param: params[1],
+                value: params[2]
+            })
+        } else if command == WITHDRAW_RESERVE_POOL {
+            enforce(params.len() == 2, "withdraw_reserve_pool needs 2 params");
+            // params[1] = amount
+            Command::WithdrawReservePool(WithdrawReservePool {
+                amount: params[1]
+            })
+        } else if command == SET_PRODUCT_TYPE_STATUS {
+            enforce(params.len() == 3 || params.len() == 4, "set_product_type_status needs 3 or 4 params");
+            // params[1] = product_type_id, params[2] = is_active (0 = closed, non-zero = open),
+            // params[3] = freeze_accrual (optional, non-zero stops accrual while closed)
+            Command::SetProductTypeStatus(SetProductTypeStatus {
+                product_type_id: params[1],
+                is_active: params[2] != 0,
+                freeze_accrual: params.get(3).is_some_and(|freeze| *freeze != 0)
+            })
+        } else if command == SET_APY_STEPS {
+            enforce(params.len() >= 2 && params.len() <= 2 + 2 * MAX_APY_STEPS, "set_apy_steps needs 2 to 10 params");
+            // params[1] = product_type_id, then (threshold_ticks, apy) per step, none clears the schedule
+            Command::SetApySteps(SetApySteps {
+                product_type_id: params[1],
+                steps: params[2..].to_vec()
+            })
+        } else if command == SET_WHITELIST_BATCH {
+            enforce(params.len() >= 4, "set_whitelist_batch needs at least 4 params");
+            // params[1] = product_type_id, params[2] = add (0 = remove, non-zero = add),
+            // params[3] = user count, then [pid1, pid2] per user
+            let count = params[3];
+            enforce(count <= MAX_WHITELIST_BATCH as u64, "set_whitelist_batch user count exceeds MAX_WHITELIST_BATCH");
+            enforce(params.len() == 4 + 2 * count as usize, "set_whitelist_batch user count does not match params");
+            Command::SetWhitelistBatch(SetWhitelistBatch {
+                product_type_id: params[1],
+                users: params[4..].chunks(2).map(|c| [c[0], c[1]]).collect(),
+                add: params[2] != 0
+            })
+        } else if command == REDEEM_ALL {
+            enforce(params.len() == 2, "redeem_all needs 2 params");
+            // params[1] = certificate_id
+            Command::RedeemAll(RedeemAll {
+                certificate_id: params[1]
+            })
+        } else if command == REDEEM_EARLY {
+            enforce(params.len() == 2, "redeem_early needs 2 params");
+            // params[1] = certificate_id
+            Command::RedeemEarly(RedeemEarly {
+                certificate_id: params[1]
+            })
+        } else if command == REDEEM_POINTS_FOR_FUNDS {
+            enforce(params.len() == 2, "redeem_points_for_funds needs 2 params");
+            // params[1] = points_amount
+            Command::RedeemPointsForFunds(RedeemPointsForFunds {
+                points_amount: params[1]
+            })
+        } else if command == SWEEP_DUST {
+            enforce(params.len() == 2, "sweep_dust needs 2 params");
+            // params[1] = product_type_id
+            Command::SweepDust(SweepDust {
+                product_type_id: params[1]
+            })
+        } else if command == CLAIM_INTEREST_TO_TARGET {
+            enforce(params.len() == 3, "claim_interest_to_target needs 3 params");
+            // params[1] = certificate_id, params[2] = interest to leave unclaimed
+            Command::ClaimInterestToTarget(ClaimInterestToTarget {
+                certificate_id: params[1],
+                target_remaining: params[2]
+            })
+        } else if command == ROLLOVER_CERTIFICATE {
+            enforce(params.len() == 2, "rollover_certificate needs 2 params");
+            // params[1] = certificate_id
+            Command::RolloverCertificate(RolloverCertificate {
+                certificate_id: params[1]
+            })
+        } else if command == BATCH_PURCHASE {
+            enforce(params.len() >= 2, "batch_purchase needs at least 2 params");
+            // params[1] = item count, then [product_type_id, amount] per certificate
+            let count = params[1];
+            enforce(count <= MAX_BATCH_PURCHASE as u64, "batch_purchase item count exceeds MAX_BATCH_PURCHASE");
+            enforce(params.len() == 2 + 2 * count as usize, "batch_purchase item count does not match params");
+            Command::BatchPurchase(BatchPurchase {
+                items: params[2..].chunks(2).map(|c| (c[0], c[1])).collect()
+            })
+        } else if command == REDEEM_PRINCIPAL_PARTIAL {
+            enforce(params.len() == 3, "redeem_principal_partial needs 3 params");
+            // params[1] = certificate_id, params[2] = amount
+            Command::RedeemPrincipalPartial(RedeemPrincipalPartial {
+                certificate_id: params[1],
+                amount: params[2]
+            })
+        } else if command == TRANSFER_CERTIFICATE {
+            enforce(params.len() == 4, "transfer_certificate needs 4 params");
+            // params[1] = certificate_id, params[2..4] = new owner pid
+            Command::TransferCertificate(TransferCertificate {
+                certificate_id: params[1],
+                new_owner: [params[2], params[3]]
+            })
+        } else if command == SET_PAUSED {
+            enforce(params.len() == 2, "set_paused needs 2 params");
+            // params[1] = paused (0 = resume, non-zero = pause)
+            Command::SetPaused(SetPaused {
+                paused: params[1] != 0
+            })
+        } else if command == ADMIN_ADJUST_RECHARGE {
+            enforce(params.len() == 2, "admin_adjust_recharge needs 2 params");
+            // params[1] = delta, two's complement encoded (negative values subtract)
+            Command::AdminAdjustRecharge(AdminAdjustRecharge {
+                delta: params[1] as i64
+            })
+        } else if command == WITHDRAW_ROUNDING_SURPLUS {
+            enforce(params.len() == 2, "withdraw_rounding_surplus needs 2 params");
+            // params[1] = whole units of rounding surplus to withdraw
+            Command::WithdrawRoundingSurplus(WithdrawRoundingSurplus {
+                amount: params[1]
+            })
+        } else if command == ADMIN_REPAY {
+            enforce(params.len() == 2, "admin_repay needs 2 params");
+            // params[1] = amount returned to the treasury
+            Command::AdminRepay(AdminRepay {
+                amount: params[1]
+            })
+        } else if command == GRANT_ROLE || command == REVOKE_ROLE {
+            enforce(params.len() == 4, "grant_role and revoke_role need 4 params");
+            // params[1..3] = admin pid, params[3] = role bitmask
+            let (admin, roles) = ([params[1], params[2]], params[3]);
+            if command == GRANT_ROLE {
+                Command::GrantRole(GrantRole { admin, roles })
+            } else {
+                Command::RevokeRole(RevokeRole { admin, roles })
+            }
+        } else if command == ADMIN_FORCE_WITHDRAW_PLAYER {
+            enforce(params.len() == 7 || params.len() == 8, "admin_force_withdraw_player needs 7 or 8 params");
+            // params[1..3] = player pid, params[3] = force_mature (non-zero also closes unmatured
+            // certificates), params[4..7] = destination address packed as in withdraw,
+            // optional params[7] = certificate id to resume a large sweep from
+            Command::AdminForceWithdrawPlayer(AdminForceWithdrawPlayer {
+                pid: [params[1], params[2]],
+                data: [params[4], params[5], params[6]],
+                force_mature: params[3] != 0,
+                start: params.get(7).copied().unwrap_or(0)
+            })
         } else if command == TICK {
             Command::Tick
         } else if command == INSTALL_PLAYER {
@@ -284,43 +1603,45 @@ impl Transaction {
             Some(_) => Err(ERROR_PLAYER_ALREADY_EXIST),
             None => {
                 let mut player = StakingPlayer::new(pkey);
-                player.data = crate::player::PlayerData::new();
+                player.data = with_state_mut(|state| state.record_new_player(pkey))?;
                 player.store();
                 
-                let mut state = GLOBAL_STATE.0.borrow_mut();
-                state.total_players += 1;
-                
                 Ok(())
             }
         }
     }
 
     pub fn inc_tx_number(&self) {
-        let mut state = GLOBAL_STATE.0.borrow_mut();
-        state.txsize += 1;
-        state.txcounter += 1;
+        with_state_mut(|state| {
+            state.txsize += 1;
+            state.txcounter += 1;
+        });
     }
 
     pub fn tick(&self) {
-        let mut state = GLOBAL_STATE.0.borrow_mut();
-        state.counter += 1;
+        with_state_mut(|state| state.counter += 1);
+        crate::command::run_auto_claims();
     }
 
     pub fn process(&self, pkey: &[u64; 4], rand: &[u64; 4]) -> Vec<u64> {
         use crate::command::{Command, CommandHandler};
-        use crate::config::ADMIN_PUBKEY;
         use zkwasm_rest_convention::event::clear_events;
         use zkwasm_rust_sdk::require;
         
         let pid = StakingPlayer::pkey_to_pid(pkey);
-        let counter = GLOBAL_STATE.0.borrow().counter;
+        crate::certificate::clear_interest_cache();
+        let (counter, paused, roles) = with_state(|state| (state.counter, state.paused, state.roles_of(pkey)));
+        let has_role = |role: u64| roles_include(roles, role);
         
         let e = match &self.command {
+            // While paused only admin commands and player installs go through
+            command if paused && roles == 0
+                && !matches!(command, Command::InstallPlayer | Command::Tick) => ERROR_SYSTEM_PAUSED,
             Command::InstallPlayer => {
                 self.create_player(pkey).map_or_else(|e| e, |_| 0)
             }
             Command::Tick => {
-                unsafe { require(*pkey == *ADMIN_PUBKEY) };
+                unsafe { require(has_role(ROLE_SUPERADMIN)) };
                 self.tick();
                 0
             }
@@ -328,7 +1649,7 @@ impl Transaction {
                 withdraw.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
             }
             Command::WithdrawPoints(withdraw_points) => {
-                if *pkey == *ADMIN_PUBKEY {
+                if *pkey == *crate::config::ADMIN_PUBKEY {
                     // Admin can withdraw negative amounts (add points) without checks
                     withdraw_points.handle_admin(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
                 } else {
@@ -337,16 +1658,20 @@ impl Transaction {
                 }
             }
             Command::Deposit(deposit) => {
-                unsafe { require(*pkey == *ADMIN_PUBKEY) };
+                unsafe { require(has_role(ROLE_TREASURY)) };
                 deposit.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
             }
+            Command::DepositAndPurchase(deposit_and_purchase) => {
+                unsafe { require(has_role(ROLE_TREASURY)) };
+                deposit_and_purchase.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
             // Certificate system commands
             Command::CreateProductType(create_product_type) => {
-                unsafe { require(*pkey == *ADMIN_PUBKEY) };
+                unsafe { require(has_role(ROLE_PRODUCT)) };
                 create_product_type.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
             }
             Command::ModifyProductType(modify_product_type) => {
-                unsafe { require(*pkey == *ADMIN_PUBKEY) };
+                unsafe { require(has_role(ROLE_PRODUCT)) };
                 modify_product_type.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
             }
             Command::PurchaseCertificate(purchase_certificate) => {
@@ -358,14 +1683,93 @@ impl Transaction {
             Command::RedeemPrincipal(redeem_principal) => {
                 redeem_principal.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
             }
+            Command::RedeemAll(redeem_all) => {
+                redeem_all.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::RedeemEarly(redeem_early) => {
+                redeem_early.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::BatchPurchase(batch_purchase) => {
+                batch_purchase.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::RolloverCertificate(rollover_certificate) => {
+                rollover_certificate.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::ClaimInterestToTarget(claim_interest_to_target) => {
+                claim_interest_to_target.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::RedeemPointsForFunds(redeem_points_for_funds) => {
+                redeem_points_for_funds.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::SweepDust(sweep_dust) => {
+                sweep_dust.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::RedeemPrincipalPartial(redeem_principal_partial) => {
+                redeem_principal_partial.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::TransferCertificate(transfer_certificate) => {
+                transfer_certificate.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
             Command::AdminWithdrawToMultisig(admin_withdraw) => {
-                unsafe { require(*pkey == *ADMIN_PUBKEY) };
+                unsafe { require(has_role(ROLE_TREASURY)) };
                 admin_withdraw.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
             }
             Command::SetReserveRatio(set_reserve_ratio) => {
-                unsafe { require(*pkey == *ADMIN_PUBKEY) };
+                unsafe { require(has_role(ROLE_TREASURY)) };
                 set_reserve_ratio.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
             }
+            Command::SetPayoutsPaused(set_payouts_paused) => {
+                unsafe { require(has_role(ROLE_OPERATOR)) };
+                set_payouts_paused.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::SetGlobalParam(set_global_param) => {
+                unsafe { require(has_role(ROLE_OPERATOR)) };
+                set_global_param.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::AdminForceWithdrawPlayer(force_withdraw) => {
+                unsafe { require(has_role(ROLE_TREASURY)) };
+                force_withdraw.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::WithdrawReservePool(withdraw_reserve_pool) => {
+                unsafe { require(has_role(ROLE_TREASURY)) };
+                withdraw_reserve_pool.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::WithdrawRoundingSurplus(withdraw_rounding_surplus) => {
+                unsafe { require(has_role(ROLE_TREASURY)) };
+                withdraw_rounding_surplus.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::SetWhitelistBatch(set_whitelist_batch) => {
+                unsafe { require(has_role(ROLE_PRODUCT)) };
+                set_whitelist_batch.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::SetProductTypeStatus(set_product_type_status) => {
+                unsafe { require(has_role(ROLE_PRODUCT)) };
+                set_product_type_status.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::SetApySteps(set_apy_steps) => {
+                unsafe { require(has_role(ROLE_PRODUCT)) };
+                set_apy_steps.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::SetPaused(set_paused) => {
+                unsafe { require(has_role(ROLE_OPERATOR)) };
+                set_paused.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::AdminAdjustRecharge(admin_adjust_recharge) => {
+                unsafe { require(has_role(ROLE_TREASURY)) };
+                admin_adjust_recharge.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::AdminRepay(admin_repay) => {
+                unsafe { require(has_role(ROLE_TREASURY)) };
+                admin_repay.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::GrantRole(grant_role) => {
+                unsafe { require(has_role(ROLE_SUPERADMIN)) };
+                grant_role.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
+            Command::RevokeRole(revoke_role) => {
+                unsafe { require(has_role(ROLE_SUPERADMIN)) };
+                revoke_role.handle(&pid, self.nonce, rand, counter).map_or_else(|e| e, |_| 0)
+            }
         };
         
         if e == 0 {
@@ -377,12 +1781,568 @@ impl Transaction {
             }
         }
         
-        let eventid = {
-            let state = GLOBAL_STATE.0.borrow();
-            (state.counter << 32) + state.txcounter
-        };
+        let eventid = with_state(|state| (state.counter << 32) + state.txcounter);
         clear_events(vec![e as u64, eventid])
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certificate::Certificate;
+    use crate::config::TICKS_PER_DAY;
+
+    #[test]
+    fn test_pre_pause_interest_claimable_during_pause() {
+        let mut state = GlobalState::new();
+        let mut cert = Certificate::new(1, [1, 2], 1, 100000, 0, 365 * TICKS_PER_DAY, 1200);
+
+        // Pause payouts after 100 days
+        state.counter = 100 * TICKS_PER_DAY;
+        state.set_payouts_paused(true);
+        assert_eq!(state.pause_start_tick, 100 * TICKS_PER_DAY);
+
+        // 100 days later only the pre-pause accrual is claimable
+        state.counter = 200 * TICKS_PER_DAY;
+        let cutoff = state.interest_claim_cutoff(state.counter);
+        assert_eq!(cutoff, 100 * TICKS_PER_DAY);
+        let pre_pause = cert.calculate_available_interest(cutoff).unwrap();
+        assert_eq!(pre_pause, cert.calculate_total_simple_interest(100 * TICKS_PER_DAY).unwrap());
+        cert.claim_interest(pre_pause).unwrap();
+
+        // Post-pause accrual stays blocked while paused
+        assert_eq!(cert.calculate_available_interest(state.interest_claim_cutoff(state.counter)).unwrap(), 0);
+
+        // Re-pausing does not move the pause start
+        state.set_payouts_paused(true);
+        assert_eq!(state.pause_start_tick, 100 * TICKS_PER_DAY);
+
+        // After resuming the post-pause accrual becomes claimable
+        state.set_payouts_paused(false);
+        let resumed = cert.calculate_available_interest(state.interest_claim_cutoff(state.counter)).unwrap();
+        let total = cert.calculate_total_simple_interest(200 * TICKS_PER_DAY).unwrap();
+        assert_eq!(resumed, total - pre_pause);
+        assert!(resumed > 0);
+    }
+
+    #[test]
+    fn test_tvl_sampling_records_each_interval() {
+        let mut state = GlobalState::new();
+        state.set_param(PARAM_TVL_SAMPLE_INTERVAL, 10).unwrap();
+
+        for counter in 0..=45 {
+            state.counter = counter;
+            state.total_funds = counter * 100;
+            // preempt may run several times per tick
+            state.sample_tvl();
+            state.sample_tvl();
+        }
+
+        assert_eq!(state.tvl_samples, vec![(0, 0), (10, 1000), (20, 2000), (30, 3000), (40, 4000)]);
+    }
+
+    #[test]
+    fn test_tvl_sampling_overwrites_oldest_and_persists() {
+        let mut state = GlobalState::new();
+        state.set_param(PARAM_TVL_SAMPLE_INTERVAL, 1).unwrap();
+
+        let total = TVL_SAMPLE_CAPACITY as u64 + 5;
+        for counter in 0..total {
+            state.counter = counter;
+            state.total_funds = counter;
+            state.sample_tvl();
+        }
+
+        assert_eq!(state.tvl_samples.len(), TVL_SAMPLE_CAPACITY);
+        assert_eq!(state.tvl_samples.first(), Some(&(5, 5)));
+        assert_eq!(state.tvl_samples.last(), Some(&(total - 1, total - 1)));
+
+        // Samples survive a storage round trip
+        let mut data = vec![];
+        state.to_data(&mut data);
+        let restored = GlobalState::from_data(&mut data.iter_mut());
+        assert_eq!(restored.tvl_sample_interval, 1);
+        assert_eq!(restored.tvl_samples, state.tvl_samples);
+    }
+
+    #[test]
+    fn test_booster_applies_only_inside_window() {
+        let mut state = GlobalState::new();
+        // No booster configured
+        assert_eq!(state.booster_bonus(10000, 0).unwrap(), 0);
+
+        // +2% on claims until tick 1000
+        state.set_param(PARAM_BOOSTER_BPS, 200).unwrap();
+        state.set_param(PARAM_BOOSTER_END_TICK, 1000).unwrap();
+        assert_eq!(state.booster_bonus(10000, 0).unwrap(), 200);
+        assert_eq!(state.booster_bonus(10000, 999).unwrap(), 200);
+        assert_eq!(state.booster_bonus(10000, 1000).unwrap(), 0);
+        assert_eq!(state.booster_bonus(10000, 5000).unwrap(), 0);
+
+        // The bonus is tracked apart from claimed interest, both drawn from the recharge reserve
+        state.total_recharge_amount = 100_000;
+        let mut cert = Certificate::new(1, [1, 2], 1, 100000, 0, 365 * TICKS_PER_DAY, 1200);
+        let available = cert.calculate_available_interest(TICKS_PER_DAY).unwrap();
+        let bonus = state.booster_bonus(available, 999).unwrap();
+        cert.claim_interest(available).unwrap();
+        state.record_interest_claim(available, bonus).unwrap();
+        assert_eq!(cert.total_interest_claimed, available);
+        assert_eq!(state.interest_claimed, available);
+        assert_eq!(state.booster_paid, bonus);
+        assert_eq!(cert.calculate_available_interest(TICKS_PER_DAY).unwrap(), 0);
+
+        // Booster is capped
+        assert_eq!(state.set_param(PARAM_BOOSTER_BPS, MAX_BOOSTER_BPS + 1), Err(ERROR_INVALID_PARAM));
+    }
+
+    #[test]
+    fn test_command_precheck_reasons() {
+        let state = GlobalState::new();
+        // User commands are open to everyone
+        assert_eq!(state.command_precheck(PURCHASE_CERTIFICATE, 0), 0);
+        assert_eq!(state.command_precheck(CLAIM_INTEREST, ROLE_MASK), 0);
+        // Admin commands are rejected for regular users
+        assert_eq!(state.command_precheck(CREATE_PRODUCT_TYPE, 0), ERROR_ADMIN_ONLY);
+        assert_eq!(state.command_precheck(SET_GLOBAL_PARAM, 0), ERROR_ADMIN_ONLY);
+        assert_eq!(state.command_precheck(SET_GLOBAL_PARAM, ROLE_MASK), 0);
+        assert_eq!(state.command_precheck(SET_APY_STEPS, 0), ERROR_ADMIN_ONLY);
+        assert_eq!(state.command_precheck(SET_APY_STEPS, ROLE_PRODUCT), 0);
+        // Unknown ids are never accepted
+        assert_eq!(state.command_precheck(4, ROLE_MASK), ERROR_UNKNOWN_COMMAND);
+        assert_eq!(state.command_precheck(255, 0), ERROR_UNKNOWN_COMMAND);
+    }
+
+    #[test]
+    fn test_can_execute_rejects_a_malformed_pkey() {
+        assert_eq!(GlobalState::preflight_reason(PURCHASE_CERTIFICATE, vec![1, 2, 3]), ERROR_INVALID_PARAM);
+        assert_eq!(GlobalState::preflight_reason(PURCHASE_CERTIFICATE, vec![1, 2, 3, 4, 5]), ERROR_INVALID_PARAM);
+    }
+
+    #[test]
+    fn test_queries_reject_a_malformed_pkey() {
+        assert_eq!(GlobalState::query_pid(vec![1, 2, 3]), Err(ERROR_INVALID_PARAM));
+        assert_eq!(GlobalState::query_pid(vec![]), Err(ERROR_INVALID_PARAM));
+        assert_eq!(GlobalState::query_pid(vec![1, 2, 3, 4]), Ok(StakingPlayer::pkey_to_pid(&[1, 2, 3, 4])));
+    }
+
+    #[test]
+    fn test_seconds_per_tick_is_bounded_and_sets_the_day() {
+        use crate::config::{MAX_SECONDS_PER_TICK, MIN_RESERVE_RATIO};
+        let mut state = GlobalState::new();
+        assert_eq!(state.set_param(PARAM_SECONDS_PER_TICK, 0), Err(ERROR_INVALID_PARAM));
+        assert_eq!(state.set_param(PARAM_SECONDS_PER_TICK, MAX_SECONDS_PER_TICK + 1), Err(ERROR_INVALID_PARAM));
+        // 7 seconds does not divide a day
+        assert_eq!(state.set_param(PARAM_SECONDS_PER_TICK, 7), Err(ERROR_INVALID_PARAM));
+        assert_eq!(state.set_param(PARAM_SECONDS_PER_TICK, MAX_SECONDS_PER_TICK), Ok(SECONDS_PER_TICK));
+
+        // The reserve ratio cooldown is a day at the current tick length, 24 ticks of an hour
+        state.set_reserve_ratio(MIN_RESERVE_RATIO, 1).unwrap();
+        assert_eq!(state.set_reserve_ratio(MIN_RESERVE_RATIO, 24), Err(ERROR_RESERVE_RATIO_CHANGE_TOO_SOON));
+        assert!(state.set_reserve_ratio(MIN_RESERVE_RATIO, 25).is_ok());
+    }
+
+    #[test]
+    fn test_set_param_rejects_unknown_id() {
+        let mut state = GlobalState::new();
+        assert_eq!(state.set_param(0, 1), Err(ERROR_INVALID_PARAM));
+        assert_eq!(state.set_param(PARAM_TVL_SAMPLE_INTERVAL, 5), Ok(DEFAULT_TVL_SAMPLE_INTERVAL));
+    }
+
+    #[test]
+    fn test_failed_claim_leaves_interest_owed_untouched() {
+        let mut state = GlobalState::new();
+        state.total_interest_owed = 1_000;
+        state.rounding_surplus = 10;
+
+        // A previous drift above the tracked surplus cannot be replaced
+        assert_eq!(state.record_usdt_claim(300, false, 11, 4), Err(ERROR_UNDERFLOW));
+        assert_eq!((state.total_interest_owed, state.rounding_surplus), (1_000, 10));
+
+        state.record_usdt_claim(300, false, 6, 4).unwrap();
+        assert_eq!((state.total_interest_owed, state.rounding_surplus), (700, 8));
+    }
+
+    #[test]
+    fn test_failed_claim_leaves_tick_budget_untouched() {
+        let mut state = GlobalState::new();
+        state.counter = 5;
+        state.max_interest_per_tick = 1_000;
+        state.record_tick_interest(200);
+
+        assert_eq!(state.record_usdt_claim(300, true, 1, 0), Err(ERROR_UNDERFLOW));
+        assert_eq!(state.interest_paid_this_tick, 200);
+
+        state.record_usdt_claim(300, true, 0, 0).unwrap();
+        assert_eq!(state.interest_paid_this_tick, 500);
+        // Unbudgeted claims (redemptions) leave the budget alone
+        state.record_usdt_claim(300, false, 0, 0).unwrap();
+        assert_eq!(state.interest_paid_this_tick, 500);
+    }
+
+    #[test]
+    fn test_fees_credit_reserve_pool_and_admin_can_withdraw() {
+        let mut state = GlobalState::new();
+        state.record_deposit(10_000).unwrap();
+
+        state.record_fee(150).unwrap();
+        state.record_fee(50).unwrap();
+        assert_eq!(state.reserve_pool, 200);
+        // Fees leave user liabilities
+        assert_eq!(state.total_funds, 9_800);
+        assert_eq!(state.tracked_user_liabilities().unwrap(), 9_800);
+
+        assert_eq!(state.record_reserve_pool_withdrawal(201), Err(ERROR_INSUFFICIENT_BALANCE));
+        state.record_reserve_pool_withdrawal(120).unwrap();
+        assert_eq!(state.reserve_pool, 80);
+        // Withdrawing revenue never touches user funds
+        assert_eq!(state.total_funds, 9_800);
+    }
+
+    #[test]
+    fn test_withdrawal_and_transfer_fees_are_params() {
+        let mut state = GlobalState::new();
+        assert_eq!((state.withdrawal_fee, state.transfer_fee), (0, 0));
+        assert_eq!(state.set_param(PARAM_WITHDRAWAL_FEE, 3), Ok(0));
+        assert_eq!(state.set_param(PARAM_TRANSFER_FEE, 7), Ok(0));
+
+        let mut data = vec![];
+        state.to_data(&mut data);
+        let restored = GlobalState::from_data(&mut data.iter_mut());
+        assert_eq!((restored.withdrawal_fee, restored.transfer_fee), (3, 7));
+    }
+
+    #[test]
+    fn test_withdrawal_fee_credits_reserve_pool() {
+        let mut state = GlobalState::new();
+        state.record_deposit(1_000).unwrap();
+
+        // Amount and fee are checked together, a failure records neither
+        assert_eq!(state.record_withdrawal_with_fee(995, 10), Err(ERROR_INSUFFICIENT_BALANCE));
+        assert_eq!((state.total_funds, state.reserve_pool), (1_000, 0));
+
+        state.record_withdrawal_with_fee(900, 10).unwrap();
+        assert_eq!(state.reserve_pool, 10);
+        assert_eq!(state.total_funds, 90);
+    }
+
+    #[test]
+    fn test_forfeited_interest_credits_reserve_pool() {
+        let mut state = GlobalState::new();
+        state.record_deposit(1_000).unwrap();
+        state.total_recharge_amount = 100;
+        let liabilities = state.tracked_user_liabilities().unwrap();
+
+        assert_eq!(state.record_forfeited_interest(40), Ok(40));
+        assert_eq!(state.reserve_pool, 40);
+        assert_eq!(state.interest_reserve(), 60);
+        // The forfeited interest is never owed to a user, so liabilities are unchanged
+        assert_eq!(state.tracked_user_liabilities().unwrap(), liabilities);
+
+        // Only the part the interest reserve backs reaches the pool
+        assert_eq!(state.record_forfeited_interest(100), Ok(60));
+        assert_eq!(state.reserve_pool, 100);
+        assert_eq!(state.interest_reserve(), 0);
+    }
+
+    #[test]
+    fn test_interest_reserve_backs_claim_and_bonus() {
+        let mut state = GlobalState::new();
+        state.total_recharge_amount = 100;
+
+        // The booster bonus draws on the recharge funding alongside the interest
+        assert_eq!(state.check_interest_reserve(95, 5), Ok(()));
+        assert_eq!(state.check_interest_reserve(95, 6), Err(ERROR_INSUFFICIENT_INTEREST));
+        assert_eq!(state.record_interest_claim(95, 6), Err(ERROR_INSUFFICIENT_INTEREST));
+        assert_eq!((state.interest_claimed, state.booster_paid), (0, 0));
+        state.record_interest_claim(95, 5).unwrap();
+        assert_eq!(state.interest_reserve(), 0);
+    }
+
+    #[test]
+    fn test_reserve_pool_persists() {
+        let mut state = GlobalState::new();
+        state.record_deposit(1_000).unwrap();
+        state.record_fee(42).unwrap();
+
+        let mut data = vec![];
+        state.to_data(&mut data);
+        let restored = GlobalState::from_data(&mut data.iter_mut());
+        assert_eq!(restored.reserve_pool, 42);
+        assert_eq!(restored.total_funds, 958);
+    }
+
+    #[test]
+    fn test_reserve_ramp_raises_reserve_below_threshold() {
+        let mut state = GlobalState::new();
+        state.set_param(PARAM_RESERVE_RAMP_THRESHOLD, 100_000).unwrap();
+        assert_eq!(state.set_param(PARAM_RESERVE_RAMP_RATIO, 10001), Err(ERROR_INVALID_PARAM));
+
+        // Below the threshold the 50% ramp ratio applies instead of the 10% reserve ratio
+        state.total_funds = 50_000;
+        assert_eq!(state.record_admin_withdrawal(25_001), Err(ERROR_INSUFFICIENT_BALANCE));
+        state.record_admin_withdrawal(25_000).unwrap();
+
+        // Above it the configured ratio applies again
+        let mut state = GlobalState::new();
+        state.set_param(PARAM_RESERVE_RAMP_THRESHOLD, 100_000).unwrap();
+        state.total_funds = 200_000;
+        assert_eq!(state.record_admin_withdrawal(180_001), Err(ERROR_INSUFFICIENT_BALANCE));
+        state.record_admin_withdrawal(180_000).unwrap();
+    }
+
+    #[test]
+    fn test_reserve_ramp_disabled_by_default() {
+        let mut state = GlobalState::new();
+        state.total_funds = 50_000;
+        state.record_admin_withdrawal(45_000).unwrap();
+    }
+
+    #[test]
+    fn test_min_absolute_reserve_binds_when_larger() {
+        // 10% of 50,000 is 5,000: an 8,000 floor binds instead
+        let mut state = GlobalState::new();
+        state.set_param(PARAM_MIN_ABSOLUTE_RESERVE, 8_000).unwrap();
+        state.total_funds = 50_000;
+        assert_eq!(state.record_admin_withdrawal(42_001), Err(ERROR_INSUFFICIENT_BALANCE));
+        state.record_admin_withdrawal(42_000).unwrap();
+        assert_eq!(state.record_admin_withdrawal(1), Err(ERROR_INSUFFICIENT_BALANCE));
+
+        // 10% of 200,000 is 20,000: the percentage reserve binds over the same floor
+        let mut state = GlobalState::new();
+        state.set_param(PARAM_MIN_ABSOLUTE_RESERVE, 8_000).unwrap();
+        state.total_funds = 200_000;
+        assert_eq!(state.record_admin_withdrawal(180_001), Err(ERROR_INSUFFICIENT_BALANCE));
+        state.record_admin_withdrawal(180_000).unwrap();
+
+        // A floor above everything in the system blocks withdrawals entirely
+        let mut state = GlobalState::new();
+        state.set_param(PARAM_MIN_ABSOLUTE_RESERVE, 100_000).unwrap();
+        state.total_funds = 50_000;
+        assert_eq!(state.record_admin_withdrawal(1), Err(ERROR_INSUFFICIENT_BALANCE));
+    }
+
+
+    fn whitelist_batch_params(count: u64, users: usize) -> Vec<u64> {
+        let mut params = vec![SET_WHITELIST_BATCH, 1, 1, count];
+        params.extend(vec![7; 2 * users]);
+        params
+    }
 
+    #[test]
+    fn test_decode_whitelist_batch_with_matching_count() {
+        let tx = Transaction::decode(&whitelist_batch_params(2, 2));
+        match tx.command {
+            crate::command::Command::SetWhitelistBatch(batch) => assert_eq!(batch.users, vec![[7, 7], [7, 7]]),
+            _ => panic!("expected SetWhitelistBatch"),
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_decode_rejects_inconsistent_whitelist_count() {
+        Transaction::decode(&whitelist_batch_params(3, 2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_decode_rejects_oversized_whitelist_count() {
+        // A huge declared count is rejected before it is used in any length computation
+        Transaction::decode(&whitelist_batch_params(u64::MAX, 1));
+    }
+
+    #[test]
+    fn test_query_page_limit_is_clamped() {
+        let mut state = GlobalState::new();
+        assert_eq!(state.page_limit(0), 64);
+        assert_eq!(state.page_limit(10), 10);
+        assert_eq!(state.page_limit(1000), 64);
+
+        state.set_param(PARAM_QUERY_PAGE_SIZE, 16).unwrap();
+        assert_eq!(state.page_limit(32), 16);
+        assert_eq!(state.set_param(PARAM_QUERY_PAGE_SIZE, 0), Err(ERROR_INVALID_PARAM));
+        assert_eq!(state.set_param(PARAM_QUERY_PAGE_SIZE, 257), Err(ERROR_INVALID_PARAM));
+    }
+
+    #[test]
+    fn test_welcome_points_credited_to_new_players_only() {
+        use crate::config::ADMIN_PUBKEY;
+
+        let mut state = GlobalState::new();
+        state.set_param(PARAM_WELCOME_POINTS, 500).unwrap();
+        assert_eq!(state.record_new_player(&[0, 764, 1, 0]).unwrap().points, 500);
+        assert_eq!(state.record_new_player(&ADMIN_PUBKEY).unwrap().points, 0);
+        assert_eq!((state.total_points_issued, state.total_players), (500, 2));
+    }
+
+    #[test]
+    fn test_pause_halts_user_commands_only() {
+        use crate::config::ADMIN_PUBKEY;
+
+        let mut state = GlobalState::new();
+        state.paused = true;
+
+        // Users are turned away, installs still go through and admin commands reach their handler
+        assert_eq!(state.command_precheck(PURCHASE_CERTIFICATE, 0), ERROR_SYSTEM_PAUSED);
+        assert_eq!(state.command_precheck(INSTALL_PLAYER, 0), 0);
+        assert_eq!(state.command_precheck(SET_PAUSED, state.roles_of(&ADMIN_PUBKEY)), 0);
+
+        state.paused = false;
+        assert_eq!(state.command_precheck(PURCHASE_CERTIFICATE, 0), 0);
+    }
+
+    #[test]
+    fn test_role_registry_grants_and_revokes() {
+        use crate::config::ADMIN_PUBKEY;
+
+        let mut state = GlobalState::new();
+        let pkey = [0, 768, 20, 0];
+        let pid = StakingPlayer::pkey_to_pid(&pkey);
+        assert_eq!(state.roles_of(&ADMIN_PUBKEY), ROLE_MASK);
+        assert_eq!(state.roles_of(&pkey), 0);
+
+        assert_eq!(state.grant_roles(pid, ROLE_PRODUCT), Ok((0, ROLE_PRODUCT)));
+        assert_eq!(state.grant_roles(pid, ROLE_OPERATOR), Ok((ROLE_PRODUCT, ROLE_PRODUCT | ROLE_OPERATOR)));
+        assert_eq!(state.revoke_roles(pid, ROLE_PRODUCT), Ok((ROLE_PRODUCT | ROLE_OPERATOR, ROLE_OPERATOR)));
+        assert_eq!(state.roles_of(&pkey), ROLE_OPERATOR);
+
+        let mut data = Vec::new();
+        state.to_data(&mut data);
+        let restored = GlobalState::from_data(&mut data.iter_mut());
+        assert_eq!(restored.admin_roles, vec![(pid, ROLE_OPERATOR)]);
+
+        // Losing the last role drops the entry; empty or unknown bits are rejected
+        assert_eq!(state.revoke_roles(pid, ROLE_OPERATOR), Ok((ROLE_OPERATOR, 0)));
+        assert!(state.admin_roles.is_empty());
+        assert_eq!(state.grant_roles(pid, 0), Err(ERROR_INVALID_PARAM));
+        assert_eq!(state.grant_roles(pid, ROLE_MASK + 1), Err(ERROR_INVALID_PARAM));
+
+        for i in 0..MAX_ADMIN_ROLES as u64 {
+            state.grant_roles([768, 100 + i], ROLE_TREASURY).unwrap();
+        }
+        assert_eq!(state.grant_roles(pid, ROLE_TREASURY), Err(ERROR_ADMIN_REGISTRY_FULL));
+    }
+
+    fn product_admin_state(pkey: &[u64; 4]) -> GlobalState {
+        let mut state = GlobalState::new();
+        state.grant_roles(StakingPlayer::pkey_to_pid(pkey), ROLE_PRODUCT).unwrap();
+        state
+    }
+
+    #[test]
+    fn test_product_admin_limited_to_product_commands() {
+        let pkey = [0, 768, 21, 0];
+        let state = product_admin_state(&pkey);
+
+        let roles = state.roles_of(&pkey);
+        assert_eq!(state.command_precheck(CREATE_PRODUCT_TYPE, roles), 0);
+        assert_eq!(state.command_precheck(MODIFY_PRODUCT_TYPE, roles), 0);
+        assert_eq!(state.command_precheck(ADMIN_WITHDRAW_TO_MULTISIG, roles), ERROR_ADMIN_ONLY);
+        assert_eq!(state.command_precheck(GRANT_ROLE, roles), ERROR_ADMIN_ONLY);
+    }
+
+    #[test]
+    fn test_product_admin_denied_treasury_withdrawal() {
+        let pkey = [0, 768, 22, 0];
+        let state = product_admin_state(&pkey);
+
+        let roles = state.roles_of(&pkey);
+        assert!(!roles_include(roles, ROLE_TREASURY));
+        assert_eq!(state.command_precheck(ADMIN_WITHDRAW_TO_MULTISIG, roles), ERROR_ADMIN_ONLY);
+    }
+
+    #[test]
+    fn test_event_types_registry() {
+        use crate::config::EVENT_TYPES;
+        let lookup = |name| EVENT_TYPES.iter().find(|(n, _)| *n == name).map(|(_, t)| *t);
+        assert_eq!(lookup("indexed_object"), Some(5));
+        assert_eq!(lookup("product_type_created"), Some(6));
+        assert_eq!(lookup("interest_claimed"), Some(9));
+        assert_eq!(lookup("deposit"), Some(11));
+        assert_eq!(lookup("role_changed"), Some(24));
+
+        // Neither names nor numbers repeat
+        let mut names: Vec<_> = EVENT_TYPES.iter().map(|(name, _)| *name).collect();
+        let mut types: Vec<_> = EVENT_TYPES.iter().map(|(_, t)| *t).collect();
+        names.sort_unstable();
+        names.dedup();
+        types.sort_unstable();
+        types.dedup();
+        assert_eq!((names.len(), types.len()), (EVENT_TYPES.len(), EVENT_TYPES.len()));
+    }
+
+    #[test]
+    fn test_genesis_tick_before_any_player() {
+        use crate::cert_manager::{CertificateManager, ProductTypeManager, ProductTypeOptions};
+
+        let mut state = GlobalState::new();
+
+        // Genesis does not force an empty settlement flush
+        assert!(!state.preempt_due(0));
+
+        // Ticks advance the counter before any player, including the admin, is installed
+        state.counter += 2;
+        assert_eq!((state.counter, state.txcounter), (2, 0));
+
+        // The first purchaser's interest is measured from the advanced counter
+        let mut player = crate::player::PlayerData::new();
+        player.add_idle_funds(10_000).unwrap();
+        let product_type = ProductTypeManager::create_product_type_with(&mut state, 30 * TICKS_PER_DAY, 1000, 100, true, &ProductTypeOptions::default()).unwrap();
+        let (mut product_type, maturity_time) = CertificateManager::prepare_purchase_with(Some(product_type), || false, &state, &player, 10_000, 0, true).unwrap();
+        let cert = CertificateManager::issue_certificate_with([784, 9], &mut player, &mut product_type, &mut state, maturity_time, 10_000, false).unwrap();
+        assert_eq!((cert.purchase_time, cert.maturity_time), (2, 2 + 30 * TICKS_PER_DAY));
+        assert_eq!(cert.calculate_available_interest(2).unwrap(), 0);
+
+        // The periodic flush still runs once the counter moves on
+        state.counter = 600;
+        assert!(state.preempt_due(0));
+    }
+
+    #[test]
+    fn test_stale_and_future_nonces_fail_cleanly() {
+        use crate::error::ERROR_INVALID_NONCE;
+        use crate::player::NonceCheck;
+
+        let mut player = StakingPlayer::new_from_pid([800, 1]);
+        assert_eq!(player.try_check_and_inc_nonce(0), Ok(()));
+        // A replayed nonce and one skipping ahead are both rejected without consuming one
+        assert_eq!(player.try_check_and_inc_nonce(0), Err(ERROR_INVALID_NONCE));
+        assert_eq!(player.try_check_and_inc_nonce(5), Err(ERROR_INVALID_NONCE));
+        assert_eq!(player.nonce, 1);
+        assert_eq!(player.try_check_and_inc_nonce(1), Ok(()));
+        assert_eq!(player.nonce, 2);
+
+        // The nonce travels in the upper bits of the command word
+        assert_eq!(Transaction::decode(&[PURCHASE_CERTIFICATE | (5 << 16), 1, 100]).nonce, 5);
+    }
+
+    #[test]
+    fn test_admin_available_funds_query_matches_on_chain_formula() {
+        use crate::config::calculate_available_funds;
+
+        let mut state = GlobalState::new();
+        state.total_funds = 100_000;
+        state.total_recharge_amount = 20_000;
+        state.cumulative_admin_withdrawals = 30_000;
+        state.reserve_ratio = 1500;
+        let view = state.admin_available_funds_view();
+
+        let expected = calculate_available_funds(
+            state.total_funds,
+            state.cumulative_admin_withdrawals,
+            state.total_recharge_amount,
+            state.reserve_ratio,
+            state.reserve_ramp_threshold,
+            state.reserve_ramp_ratio,
+            state.min_absolute_reserve
+        ).unwrap();
+        assert!(expected > 0);
+        assert_eq!((view.available, view.error), (expected, 0));
+        assert_eq!((view.total_funds, view.total_recharge_amount), (100_000, 20_000));
+        assert_eq!((view.cumulative_admin_withdrawals, view.reserve_ratio), (30_000, 1500));
+
+        // A failing calculation is reported instead of a wrong amount
+        state.reserve_ratio = 10_001;
+        let view = state.admin_available_funds_view();
+        assert_eq!((view.available, view.error), (0, ERROR_UNDERFLOW));
+    }
+}
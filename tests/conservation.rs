@@ -0,0 +1,273 @@
+//! Money conservation across the command surface.
+//!
+//! The handlers in `command.rs` and `CertificateManager` keep their accounting in storage-free
+//! functions and only load and store around them; this harness calls those same functions
+//! (without merkle storage) and checks after every step that the global counters match
+//! the sum of what users hold.
+
+use zkwasm_launchpad::cert_manager::{CertificateManager, ProductTypeManager};
+use zkwasm_launchpad::certificate::{Certificate, CertificateStatus, ProductType, PRINCIPAL_CURRENCY_USDT};
+use zkwasm_launchpad::command::{apply_deposit, apply_interest_credit, apply_purchase, apply_withdrawal};
+use zkwasm_launchpad::config::{TICKS_PER_DAY, PARAM_BOOSTER_BPS, PARAM_BOOSTER_END_TICK};
+use zkwasm_launchpad::error::{ERROR_INSUFFICIENT_INTEREST, ERROR_UNDERFLOW};
+use zkwasm_launchpad::player::PlayerData;
+use zkwasm_launchpad::state::GlobalState;
+
+struct Ledger {
+    state: GlobalState,
+    players: Vec<PlayerData>,
+    certificates: Vec<Certificate>,
+}
+
+impl Ledger {
+    fn new(players: usize) -> Self {
+        Ledger {
+            state: GlobalState::new(),
+            players: vec![PlayerData::new(); players],
+            certificates: vec![],
+        }
+    }
+
+    fn advance(&mut self, ticks: u64) {
+        self.state.counter += ticks;
+    }
+
+    /// Deposit::handle
+    fn deposit(&mut self, player: usize, amount: u64) {
+        let now = self.state.counter;
+        apply_deposit(&mut self.state, &mut self.players[player], amount, now).unwrap();
+        self.assert_conserved();
+    }
+
+    /// PurchaseCertificate::handle of a product with these terms
+    fn purchase(&mut self, player: usize, product_type_id: u64, amount: u64, duration_ticks: u64, apy: u64) -> usize {
+        self.issue(player, ProductType::new(product_type_id, duration_ticks, apy, 0), amount)
+    }
+
+    /// PurchaseCertificate::handle of the recharge product 0, funding interest
+    fn recharge(&mut self, player: usize, amount: u64) -> usize {
+        let product_type = ProductTypeManager::get_product_type_with(|_| vec![], 0).unwrap();
+        self.issue(player, product_type, amount)
+    }
+
+    /// PurchaseCertificate::handle's steps, numbering the certificate from the owner's own counter
+    fn issue(&mut self, player: usize, product_type: ProductType, amount: u64) -> usize {
+        let owner = [player as u64, 0];
+        let (mut product_type, maturity_time) =
+            CertificateManager::prepare_purchase_with(Some(product_type), || true, &self.state, &self.players[player], amount, 0, true).unwrap();
+        apply_purchase(&mut self.state, &mut self.players[player], product_type.id, PRINCIPAL_CURRENCY_USDT, amount).unwrap();
+        let cert = CertificateManager::issue_certificate_with(
+            owner,
+            &mut self.players[player],
+            &mut product_type,
+            &mut self.state,
+            maturity_time,
+            amount,
+            false
+        ).unwrap();
+        self.certificates.push(cert);
+        self.assert_conserved();
+        self.certificates.len() - 1
+    }
+
+    /// CertificateManager::prepare_claim, capped for user claims, then the credit settle_claim makes
+    fn settle(&mut self, cert: usize, capped: bool) -> Result<u64, u32> {
+        let claim = CertificateManager::prepare_claim_with(self.certificates[cert].clone(), &self.state, capped, 0, 0)?;
+        let payout = CertificateManager::record_claim(&mut self.state, &claim)?;
+        self.certificates[cert] = claim.certificate;
+        let owner = self.certificates[cert].owner[0] as usize;
+        let now = self.state.counter;
+        apply_interest_credit(&mut self.state, &mut self.players[owner], payout, now)?;
+        self.assert_conserved();
+        Ok(payout.0)
+    }
+
+    /// ClaimInterest::handle
+    fn claim(&mut self, cert: usize) -> u64 {
+        self.settle(cert, true).unwrap()
+    }
+
+    /// RedeemPrincipal::handle, paying any unclaimed interest first; a failure leaves everything untouched
+    fn redeem(&mut self, cert: usize) -> Result<u64, u32> {
+        let redemption = CertificateManager::prepare_redeem_all_with(self.certificates[cert].clone(), &self.state)?;
+        let (mut state, mut players) = (self.state.clone(), self.players.clone());
+        CertificateManager::record_redemption(&mut state, &redemption)?;
+        let (owner, now) = (redemption.certificate.owner[0] as usize, state.counter);
+        if redemption.interest.0 > 0 {
+            apply_interest_credit(&mut state, &mut players[owner], redemption.interest, now)?;
+        }
+        let (principal, currency) = redemption.principal;
+        players[owner].credit_redeemed_principal(currency, principal)?;
+        (self.state, self.players) = (state, players);
+        self.certificates[cert] = redemption.certificate;
+        self.assert_conserved();
+        Ok(principal)
+    }
+
+    /// Withdraw::handle; a failure leaves both the state and the player untouched
+    fn withdraw(&mut self, player: usize, amount: u64) -> Result<(), u32> {
+        let (mut state, mut data) = (self.state.clone(), self.players[player].clone());
+        let now = state.counter;
+        apply_withdrawal(&mut state, &mut data, amount, now)?;
+        (self.state, self.players[player]) = (state, data);
+        self.assert_conserved();
+        Ok(())
+    }
+
+    /// TransferCertificate::handle's fee, charged from a user's idle funds
+    fn charge_fee(&mut self, player: usize, amount: u64) {
+        self.players[player].spend_idle_funds(amount).unwrap();
+        self.state.record_fee(amount).unwrap();
+        self.assert_conserved();
+    }
+
+    /// AdminWithdrawToMultisig::handle
+    fn admin_withdraw(&mut self, amount: u64) -> Result<(), u32> {
+        self.state.record_admin_withdrawal(amount)?;
+        self.assert_conserved();
+        Ok(())
+    }
+
+    fn user_holdings(&self) -> u64 {
+        let idle: u64 = self.players.iter().map(|p| p.idle_funds).sum();
+        let principal: u64 = self.certificates
+            .iter()
+            .filter(|c| c.status != CertificateStatus::Redeemed)
+            .map(|c| c.principal)
+            .sum();
+        idle + principal
+    }
+
+    fn assert_conserved(&self) {
+        assert_eq!(
+            self.state.tracked_user_liabilities().unwrap(),
+            self.user_holdings(),
+            "global accounting diverged from user holdings"
+        );
+        // Every USDT interest credit is drawn from the recharge funding
+        assert!(
+            self.state.interest_claimed + self.state.booster_paid <= self.state.total_recharge_amount,
+            "interest paid beyond the recharge funding"
+        );
+    }
+}
+
+#[test]
+fn test_conservation_full_lifecycle() {
+    let mut ledger = Ledger::new(3);
+
+    ledger.deposit(0, 100_000);
+    ledger.deposit(1, 50_000);
+    ledger.deposit(2, 20_000);
+
+    // Operator funds interest through the recharge product
+    let recharge = ledger.recharge(2, 20_000);
+    let cert_a = ledger.purchase(0, 1, 60_000, 30 * TICKS_PER_DAY, 1200);
+    let cert_b = ledger.purchase(1, 1, 50_000, 90 * TICKS_PER_DAY, 1500);
+    // Certificate ids are numbered per owner
+    assert_eq!(ledger.certificates[cert_a].id, ledger.certificates[cert_b].id);
+
+    ledger.advance(10 * TICKS_PER_DAY);
+    assert!(ledger.claim(cert_a) > 0);
+    ledger.admin_withdraw(10_000).unwrap();
+
+    ledger.advance(20 * TICKS_PER_DAY);
+    ledger.claim(cert_a);
+    ledger.redeem(cert_a).unwrap();
+    ledger.withdraw(0, 30_000).unwrap();
+
+    // Promotional booster pays a bonus on top of earned interest
+    ledger.advance(60 * TICKS_PER_DAY);
+    let end_tick = ledger.state.counter + 1;
+    ledger.state.set_param(PARAM_BOOSTER_BPS, 500).unwrap();
+    ledger.state.set_param(PARAM_BOOSTER_END_TICK, end_tick).unwrap();
+    ledger.claim(cert_b);
+    assert!(ledger.state.booster_paid > 0);
+    ledger.redeem(cert_b).unwrap();
+
+    // Withdrawals come out of total_funds, so claimed interest above what is left of the deposits stays in
+    let balance_0 = ledger.players[0].idle_funds;
+    ledger.withdraw(0, balance_0).unwrap();
+    let remaining = ledger.state.total_funds;
+    let balance_1 = ledger.players[1].idle_funds;
+    assert!(balance_1 > remaining);
+    assert_eq!(ledger.withdraw(1, balance_1), Err(ERROR_UNDERFLOW));
+    ledger.withdraw(1, remaining).unwrap();
+
+    assert_eq!(ledger.players[0].idle_funds, 0);
+    assert_eq!(ledger.state.total_funds, 0);
+    assert_eq!(ledger.certificates[recharge].status, CertificateStatus::Active);
+}
+
+#[test]
+fn test_withdrawing_claimed_interest_is_limited_to_total_funds() {
+    let mut ledger = Ledger::new(2);
+    ledger.deposit(0, 100_000);
+    ledger.deposit(1, 20_000);
+    ledger.recharge(1, 20_000);
+
+    let cert = ledger.purchase(0, 1, 100_000, 365 * TICKS_PER_DAY, 1200);
+    ledger.advance(365 * TICKS_PER_DAY);
+    let interest = ledger.claim(cert);
+    ledger.redeem(cert).unwrap();
+
+    // Recharge funding backs the claim but not the withdrawal, which only draws on total_funds
+    assert_eq!(ledger.withdraw(0, 100_000 + interest), Err(ERROR_UNDERFLOW));
+    ledger.withdraw(0, 100_000).unwrap();
+    assert_eq!(ledger.state.total_funds, 0);
+    assert_eq!(ledger.state.total_recharge_amount, 20_000);
+    assert_eq!(ledger.players[0].idle_funds, interest);
+}
+
+#[test]
+fn test_unfunded_interest_is_not_paid_out() {
+    let mut ledger = Ledger::new(2);
+    ledger.deposit(0, 100_000);
+    ledger.deposit(1, 20_000);
+    let cert = ledger.purchase(0, 1, 100_000, 365 * TICKS_PER_DAY, 1200);
+    ledger.advance(365 * TICKS_PER_DAY);
+
+    // No recharge funding backs the interest, so neither a user claim nor a redemption can pay it
+    assert_eq!(ledger.settle(cert, true), Err(ERROR_INSUFFICIENT_INTEREST));
+    assert_eq!(ledger.redeem(cert), Err(ERROR_INSUFFICIENT_INTEREST));
+    assert_eq!(ledger.state.interest_claimed, 0);
+    assert_ne!(ledger.certificates[cert].status, CertificateStatus::Redeemed);
+
+    // Once the operator funds it, the redemption returns the principal with its interest
+    ledger.recharge(1, 20_000);
+    assert_eq!(ledger.redeem(cert), Ok(100_000));
+    let interest = ledger.state.interest_claimed;
+    assert!(interest > 0);
+    assert_eq!(ledger.players[0].idle_funds, 100_000 + interest);
+}
+
+#[test]
+fn test_fees_move_from_user_funds_to_reserve_pool() {
+    let mut ledger = Ledger::new(2);
+    ledger.deposit(0, 10_000);
+    ledger.deposit(1, 5_000);
+
+    ledger.charge_fee(0, 100);
+    ledger.charge_fee(1, 25);
+    assert_eq!(ledger.state.reserve_pool, 125);
+
+    ledger.state.record_reserve_pool_withdrawal(125).unwrap();
+    ledger.assert_conserved();
+    ledger.withdraw(0, 9_900).unwrap();
+    ledger.withdraw(1, 4_975).unwrap();
+    assert_eq!(ledger.state.total_funds, 0);
+}
+
+#[test]
+fn test_redeem_without_claim_pays_interest() {
+    let mut ledger = Ledger::new(2);
+    ledger.deposit(0, 100_000);
+    ledger.deposit(1, 20_000);
+    ledger.recharge(1, 20_000);
+
+    let cert = ledger.purchase(0, 1, 100_000, 365 * TICKS_PER_DAY, 1200);
+    ledger.advance(400 * TICKS_PER_DAY);
+    assert_eq!(ledger.redeem(cert), Ok(100_000));
+    assert!(ledger.state.interest_claimed > 0);
+    assert_eq!(ledger.players[0].idle_funds, 100_000 + ledger.state.interest_claimed);
+}
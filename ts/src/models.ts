@@ -258,13 +258,6 @@ export interface Certificate {
     status: CertificateStatus;
 }
 
-export interface CertificateInfo {
-    certificate: Certificate;
-    availableInterest: bigint;  // Interest available for withdrawal
-    totalInterest: bigint;      // Total interest generated since purchase
-    currentTime: bigint;        // Current counter
-}
-
 export class ProductTypeManager {
     static fromData(data: bigint[]): ProductType {
         return {
@@ -378,63 +371,9 @@ export class CertificateManager {
         }
     }
 
-    static calculateAvailableInterest(cert: Certificate, currentTime: bigint): bigint {
-        // Calculate total interest from purchase time to current time
-        const totalInterest = this.calculateTotalInterest(cert, currentTime);
-        
-        // Available interest = total earned - already claimed
-        const availableInterest = totalInterest - cert.totalInterestClaimed;
-        return availableInterest > 0n ? availableInterest : 0n;
-    }
-
-    static calculateTotalInterest(cert: Certificate, currentTime: bigint): bigint {
-        if (currentTime <= cert.purchaseTime) {
-            return 0n;
-        }
-
-        const timeElapsed = currentTime - cert.purchaseTime;
-        
-        // Convert to Numbers for precise calculation (avoid BigInt precision loss)
-        const principal = Number(cert.principal);
-        const apy = Number(cert.lockedApy);
-        const timeElapsedNum = Number(timeElapsed);
-        
-        // Use consistent time calculation with Rust backend
-        const SECONDS_PER_TICK = 5;
-        const timeElapsedSeconds = timeElapsedNum * SECONDS_PER_TICK;
-        
-        // Simple interest calculation with floating point precision
-        const SECONDS_PER_YEAR = 365 * 24 * 60 * 60; // 31,536,000
-        const BASIS_POINTS_DIVISOR = 10000;
-        
-        // Calculate: (principal * apy * time_elapsed_seconds) / (basis_points * seconds_per_year)
-        const interestFloat = (principal * apy * timeElapsedSeconds) / (BASIS_POINTS_DIVISOR * SECONDS_PER_YEAR);
-        
-        // Round and convert back to BigInt
-        return BigInt(Math.floor(interestFloat));
-    }
-
-    static calculateTotalSimpleInterest(cert: Certificate, currentTime: bigint): bigint {
-        if (currentTime <= cert.purchaseTime) {
-            return 0n;
-        }
-
-        const totalTime = currentTime - cert.purchaseTime;
-        
-        // Use consistent time calculation with Rust backend
-        const SECONDS_PER_TICK = 5n;
-        const totalTimeSeconds = totalTime * SECONDS_PER_TICK;
-        
-        // Simple interest: (principal * APY * total_time_seconds) / (BASIS_POINTS_DIVISOR * SECONDS_PER_YEAR)
-        const SECONDS_PER_YEAR = 365n * 24n * 60n * 60n; // 31,536,000
-        const BASIS_POINTS_DIVISOR = 10000n;
-        
-        // Use BigInt arithmetic for precision
-        const numerator = cert.principal * cert.lockedApy * totalTimeSeconds;
-        const denominator = BASIS_POINTS_DIVISOR * SECONDS_PER_YEAR;
-        
-        return numerator / denominator;
-    }
+    // Interest is not recomputed here: it depends on the tick length, compounding and APY steps
+    // locked on the certificate. Use available_interest from query_certificates, or
+    // query_projected_interest for a future counter
 
     static isMatured(cert: Certificate, currentTime: bigint): boolean {
         return currentTime >= cert.maturityTime;
@@ -450,17 +389,6 @@ export class CertificateManager {
         return updatedCert;
     }
 
-    static getCertificateInfo(cert: Certificate, currentTime: bigint): CertificateInfo {
-        const updatedCert = CertificateManager.updateStatus(cert, currentTime);
-        
-        return {
-            certificate: updatedCert,
-            availableInterest: CertificateManager.calculateAvailableInterest(updatedCert, currentTime),
-            totalInterest: CertificateManager.calculateTotalSimpleInterest(updatedCert, currentTime),
-            currentTime
-        };
-    }
-
     static formatPrincipal(principal: bigint): string {
         return `${principal.toLocaleString()} USDT`;
     }
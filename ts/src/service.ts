@@ -11,7 +11,6 @@ import {
     ProductType,
     Certificate,
     CertificateStatus,
-    CertificateInfo,
     ProductTypeManager,
     CertificateManager,
     CertificateCalculator
@@ -48,8 +47,9 @@ const ProductTypeSchema = new mongoose.Schema({
     updatedAt: { type: Date, default: Date.now }
 });
 
+// Certificate ids are numbered per owner, so a certificate is identified by (owner, id)
 const CertificateSchema = new mongoose.Schema({
-    id: { type: String, required: true, unique: true },
+    id: { type: String, required: true },
     owner: [{ type: String, required: true }],
     productTypeId: { type: String, required: true },
     principal: { type: String, required: true },
@@ -66,6 +66,8 @@ const CertificateSchema = new mongoose.Schema({
     updatedAt: { type: Date, default: Date.now }
 });
 
+CertificateSchema.index({ owner: 1, id: 1 });
+
 const InterestClaimSchema = new mongoose.Schema({
     userId: [{ type: String, required: true }],
     certificateId: { type: String, required: true },
@@ -239,11 +241,12 @@ function extra(app: Express) {
     });
     
     // Get specific certificate details from database
-    app.get("/data/certificate/:certId", async (req: any, res) => {
+    app.get("/data/user/:pid1/:pid2/certificate/:certId", async (req: any, res) => {
         try {
+            const owner = [req.params.pid1, req.params.pid2];
             const certId = req.params.certId;
             
-            const cert = await CertificateModel.findOne({ id: certId });
+            const cert = await CertificateModel.findOne({ owner, id: certId });
             if (!cert) {
                 return res.status(404).send({
                     success: false,
@@ -596,7 +599,7 @@ async function eventCallback(arg: TxWitness, data: BigUint64Array) {
                         };
                         
                         await CertificateModel.findOneAndUpdate(
-                            { id: objectId },
+                            { owner: certificate.owner, id: objectId },
                             { ...certificate, updatedAt: new Date() },
                             { upsert: true }
                         );
@@ -678,11 +681,11 @@ async function eventCallback(arg: TxWitness, data: BigUint64Array) {
                     }).save();
                     
                     // Update certificate's totalInterestClaimed
-                    const cert = await CertificateModel.findOne({ id: certificateId });
+                    const cert = await CertificateModel.findOne({ owner: userId, id: certificateId });
                     if (cert) {
                         const newTotal = BigInt(cert.totalInterestClaimed) + BigInt(amount);
                         await CertificateModel.findOneAndUpdate(
-                            { id: certificateId },
+                            { owner: userId, id: certificateId },
                             { totalInterestClaimed: newTotal.toString(), updatedAt: new Date() }
                         );
                     }
@@ -709,7 +712,7 @@ async function eventCallback(arg: TxWitness, data: BigUint64Array) {
                     
                     // Update certificate status to Redeemed
                     await CertificateModel.findOneAndUpdate(
-                        { id: certificateIdRedemption },
+                        { owner: userIdRedemption, id: certificateIdRedemption },
                         { status: 'Redeemed', updatedAt: new Date() }
                     );
                     break;